@@ -90,6 +90,37 @@ pub struct BakeArgs {
     /// Override target peak brightness in nits (falls back to metadata or 1600 nits)
     #[arg(long = "target-peak", value_name = "NITS")]
     pub target_peak_nits: Option<f32>,
+
+    /// Drop EXIF (including GPS) from the output; gain-map XMP/ISO metadata and MPF are kept
+    #[arg(long = "strip-metadata")]
+    pub strip_metadata: bool,
+
+    /// Decode each result and re-encode with finer gain-map settings until the HDR
+    /// reconstruction reaches --target-psnr (at most 4 passes)
+    #[arg(long = "two-pass")]
+    pub two_pass: bool,
+
+    /// Reconstruction PSNR (dB, 10-bit PQ) that ends --two-pass early
+    #[arg(
+        long = "target-psnr",
+        default_value_t = 45.0,
+        value_name = "DB",
+        requires = "two_pass"
+    )]
+    pub target_psnr: f64,
+
+    /// Compare downscaled HDR and SDR bases and warn if they look like different scenes
+    #[arg(long = "check-alignment")]
+    pub check_alignment: bool,
+
+    /// TOML manifest of [[job]] entries (hdr, sdr, out, per-job overrides) to bake in batch
+    #[arg(
+        long,
+        value_hint = ValueHint::FilePath,
+        value_name = "FILE",
+        conflicts_with_all = ["inputs", "hdr", "sdr", "out"]
+    )]
+    pub manifest: Option<PathBuf>,
 }
 
 #[derive(Args, Debug, Clone)]
@@ -119,4 +150,38 @@ pub struct MotionArgs {
     /// Presentation timestamp (microseconds) for the still frame within the motion clip
     #[arg(long = "timestamp-us", default_value_t = 0)]
     pub presentation_timestamp_us: u64,
+
+    /// Extra secondary item for the container as MIME:SEMANTIC:PATH, e.g.
+    /// image/jpeg:Depth:depth.jpg (repeatable; appended in the order given)
+    #[arg(long = "item", value_name = "MIME:SEMANTIC:PATH", value_parser = parse_item_spec)]
+    pub items: Vec<ItemSpec>,
+
+    /// Position of the video among the secondary items (0 = right after the primary);
+    /// defaults to after every --item
+    #[arg(long = "video-index", value_name = "N")]
+    pub video_index: Option<usize>,
+}
+
+/// A `--item` secondary container item.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ItemSpec {
+    pub mime: String,
+    pub semantic: String,
+    pub path: PathBuf,
+}
+
+fn parse_item_spec(spec: &str) -> Result<ItemSpec, String> {
+    let mut parts = spec.splitn(3, ':');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(mime), Some(semantic), Some(path))
+            if !mime.is_empty() && !semantic.is_empty() && !path.is_empty() =>
+        {
+            Ok(ItemSpec {
+                mime: mime.to_string(),
+                semantic: semantic.to_string(),
+                path: PathBuf::from(path),
+            })
+        }
+        _ => Err(format!("expected MIME:SEMANTIC:PATH, got {spec:?}")),
+    }
 }
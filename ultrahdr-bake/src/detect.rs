@@ -6,7 +6,7 @@ use std::{
 
 use anyhow::{Context, Result, bail, ensure};
 use memchr::memmem;
-use ultrahdr::{CompressedImage, Decoder, Error, GainMapMetadata, sys};
+use ultrahdr::{CompressedImage, Decoder, GainMapMetadata, sys};
 
 // Tunable knobs for XMP scanning. Bump these if your XMP lives deeper in the file.
 const XMP_SCAN_LIMIT_BYTES: usize = 256 * 1024;
@@ -14,32 +14,61 @@ const XMP_CHUNK_SIZE: usize = 8192;
 const XMP_EXTRA_TAIL_CHUNK: usize = 4096;
 const XMP_EXTRA_TAIL_READS: usize = 4;
 
-#[derive(Debug, Clone, Copy)]
+/// Why an input was taken as the HDR side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum HdrDetection {
+    /// Given with `--hdr`/`--sdr`; nothing was detected.
+    Explicit,
     ProbeGainMapMetadata,
 }
 
 impl HdrDetection {
     pub fn as_str(&self) -> &'static str {
-        "libuhdr probe found gain map metadata"
+        match self {
+            HdrDetection::Explicit => "given with --hdr",
+            HdrDetection::ProbeGainMapMetadata => "libuhdr probe found gain map metadata",
+        }
     }
 }
 
-#[derive(Debug)]
+/// How much the chosen HDR input can be trusted to carry an HDR rendition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Confidence {
+    /// Chosen by the user.
+    Certain,
+    /// Its gain map advertises headroom above SDR white.
+    High,
+    /// It has a gain map, but the metadata advertises no headroom (capacity max <= 1).
+    Low,
+}
+
+#[derive(Debug, PartialEq, Eq)]
 pub struct InputPair {
     pub hdr: PathBuf,
     pub sdr: PathBuf,
 }
 
-pub fn resolve_inputs(args: &crate::cli::BakeArgs) -> Result<InputPair> {
+/// Resolved inputs plus why the HDR side was picked; printing is left to the caller.
+#[derive(Debug, PartialEq, Eq)]
+pub struct DetectionResult {
+    pub pair: InputPair,
+    pub hdr_reason: HdrDetection,
+    pub confidence: Confidence,
+}
+
+pub fn resolve_inputs(args: &crate::cli::BakeArgs) -> Result<DetectionResult> {
     if args.hdr.is_some() || args.sdr.is_some() {
         ensure!(
             args.hdr.is_some() && args.sdr.is_some(),
             "Provide both --hdr and --sdr together (or omit both to auto-detect)"
         );
-        return Ok(InputPair {
-            hdr: args.hdr.clone().expect("hdr is_some checked"),
-            sdr: args.sdr.clone().expect("sdr is_some checked"),
+        return Ok(DetectionResult {
+            pair: InputPair {
+                hdr: args.hdr.clone().expect("hdr is_some checked"),
+                sdr: args.sdr.clone().expect("sdr is_some checked"),
+            },
+            hdr_reason: HdrDetection::Explicit,
+            confidence: Confidence::Certain,
         });
     }
 
@@ -54,7 +83,7 @@ pub fn resolve_inputs(args: &crate::cli::BakeArgs) -> Result<InputPair> {
     auto_detect_pair(&args.inputs[0], &args.inputs[1])
 }
 
-fn resolve_by_original_id(seed: &Path) -> Result<InputPair> {
+fn resolve_by_original_id(seed: &Path) -> Result<DetectionResult> {
     let seed_doc_id = original_document_id(seed)?.ok_or_else(|| {
         anyhow::anyhow!(
             "Input {} missing XMP OriginalDocumentID; cannot find pair",
@@ -127,47 +156,48 @@ fn resolve_by_original_id(seed: &Path) -> Result<InputPair> {
     }
 }
 
-fn auto_detect_pair(a: &Path, b: &Path) -> Result<InputPair> {
-    let a_det = detect_hdr_candidate(a)?;
-    let b_det = detect_hdr_candidate(b)?;
+fn auto_detect_pair(a: &Path, b: &Path) -> Result<DetectionResult> {
+    let a_meta = detect_hdr_candidate(a)?;
+    let b_meta = detect_hdr_candidate(b)?;
+    classify(a, a_meta, b, b_meta)
+}
 
-    match (a_det, b_det) {
-        (Some(reason), None) => {
-            println!(
-                "Auto-detected HDR input: {} ({})",
-                a.display(),
-                reason.as_str()
-            );
-            Ok(InputPair {
-                hdr: a.to_path_buf(),
-                sdr: b.to_path_buf(),
-            })
-        }
-        (None, Some(reason)) => {
-            println!(
-                "Auto-detected HDR input: {} ({})",
-                b.display(),
-                reason.as_str()
-            );
-            Ok(InputPair {
-                hdr: b.to_path_buf(),
-                sdr: a.to_path_buf(),
-            })
-        }
+/// Pick the HDR side from each input's probed gain-map metadata.
+fn classify(
+    a: &Path,
+    a_meta: Option<GainMapMetadata>,
+    b: &Path,
+    b_meta: Option<GainMapMetadata>,
+) -> Result<DetectionResult> {
+    let (hdr, sdr, meta) = match (a_meta, b_meta) {
+        (Some(meta), None) => (a, b, meta),
+        (None, Some(meta)) => (b, a, meta),
         (Some(_), Some(_)) => bail!(
             "Both inputs look like UltraHDR (ISO 21496 gain map metadata). Please specify --hdr and --sdr explicitly."
         ),
         (None, None) => bail!(
             "Could not find ISO 21496 gain map metadata in either input. Specify --hdr and --sdr explicitly."
         ),
-    }
+    };
+    let confidence = if meta.hdr_capacity_max > 1.0 {
+        Confidence::High
+    } else {
+        Confidence::Low
+    };
+    Ok(DetectionResult {
+        pair: InputPair {
+            hdr: hdr.to_path_buf(),
+            sdr: sdr.to_path_buf(),
+        },
+        hdr_reason: HdrDetection::ProbeGainMapMetadata,
+        confidence,
+    })
 }
 
-fn detect_hdr_candidate(path: &Path) -> Result<Option<HdrDetection>> {
+fn detect_hdr_candidate(path: &Path) -> Result<Option<GainMapMetadata>> {
     let mut bytes =
         fs::read(path).with_context(|| format!("Failed to read input {}", path.display()))?;
-    let meta = probe_gainmap_metadata(&mut bytes)?;
-    Ok(meta.map(|_| HdrDetection::ProbeGainMapMetadata))
+    probe_gainmap_metadata(&mut bytes)
 }
 
 pub fn probe_gainmap_metadata(buf: &mut [u8]) -> Result<Option<GainMapMetadata>> {
@@ -177,14 +207,11 @@ pub fn probe_gainmap_metadata(buf: &mut [u8]) -> Result<Option<GainMapMetadata>>
         sys::uhdr_color_gamut::UHDR_CG_UNSPECIFIED,
         sys::uhdr_color_transfer::UHDR_CT_UNSPECIFIED,
         sys::uhdr_color_range::UHDR_CR_UNSPECIFIED,
-    );
+    )?;
     dec.set_image(&mut comp)?;
     match dec.gainmap_metadata() {
         Ok(meta) => Ok(meta),
-        Err(Error {
-            code: sys::uhdr_codec_err_t::UHDR_CODEC_INVALID_PARAM,
-            ..
-        }) => {
+        Err(e) if e.is_invalid_param() => {
             // Not an UltraHDR/gain map JPEG.
             Ok(None)
         }
@@ -272,3 +299,56 @@ fn read_exact_allow_short<R: Read>(r: &mut R, buf: &mut [u8]) -> Result<usize> {
     }
     Ok(filled)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(capacity_max: f32) -> GainMapMetadata {
+        GainMapMetadata {
+            max_content_boost: [capacity_max; 3],
+            min_content_boost: [1.0; 3],
+            gamma: [1.0; 3],
+            offset_sdr: [1.0 / 64.0; 3],
+            offset_hdr: [1.0 / 64.0; 3],
+            hdr_capacity_min: 1.0,
+            hdr_capacity_max: capacity_max,
+            use_base_cg: true,
+            base_rendition_is_hdr: false,
+        }
+    }
+
+    #[test]
+    fn one_sided_gain_map_picks_that_input_as_hdr() {
+        let (a, b) = (Path::new("a.jpg"), Path::new("b.jpg"));
+        let first = classify(a, Some(metadata(4.0)), b, None).unwrap();
+        assert_eq!(
+            first.pair,
+            InputPair {
+                hdr: a.into(),
+                sdr: b.into()
+            }
+        );
+        assert_eq!(first.hdr_reason, HdrDetection::ProbeGainMapMetadata);
+        assert_eq!(first.confidence, Confidence::High);
+
+        let second = classify(a, None, b, Some(metadata(1.0))).unwrap();
+        assert_eq!(
+            second.pair,
+            InputPair {
+                hdr: b.into(),
+                sdr: a.into()
+            }
+        );
+        assert_eq!(second.confidence, Confidence::Low);
+    }
+
+    #[test]
+    fn both_or_neither_gain_map_is_ambiguous() {
+        let (a, b) = (Path::new("a.jpg"), Path::new("b.jpg"));
+        let both = classify(a, Some(metadata(4.0)), b, Some(metadata(4.0))).unwrap_err();
+        assert!(both.to_string().contains("Both inputs"), "{both}");
+        let neither = classify(a, None, b, None).unwrap_err();
+        assert!(neither.to_string().contains("either input"), "{neither}");
+    }
+}
@@ -1,10 +1,14 @@
 use std::{fs, path::Path};
 
 use anyhow::{Context, Result, ensure};
-use ultrahdr::{CompressedImage, Decoder, Encoder, ImgLabel, sys};
+use ultrahdr::{
+    ColorLabel, CompressedImage, DecodedPacked, Decoder, GAINMAP_BIT_DEPTH, HdrIntent,
+    MergeOptions, merge_decoded, sys,
+};
 
-use crate::color::{detect_icc_color_gamut, gamut_label};
+use crate::color::detect_icc_color_gamut;
 use crate::detect::probe_gainmap_metadata;
+use crate::strip::strip_metadata;
 
 pub fn run_encoding(
     args: &crate::cli::BakeArgs,
@@ -27,51 +31,16 @@ pub fn run_encoding(
     let gainmap_meta = probe_gainmap_metadata(&mut hdr_bytes)?;
 
     if let Some(cg) = hdr_icc_gamut {
-        println!("HDR ICC gamut: {}", gamut_label(cg));
+        println!("HDR ICC gamut: {}", cg.label());
     }
     if let Some(cg) = sdr_icc_gamut {
-        println!("SDR ICC gamut: {}", gamut_label(cg));
+        println!("SDR ICC gamut: {}", cg.label());
     }
 
-    // Decode HDR intent from UltraHDR JPEG.
-    let mut dec = Decoder::new()?;
-    let mut hdr_comp = CompressedImage::from_bytes(
-        &mut hdr_bytes,
-        hdr_icc_gamut.unwrap_or(sys::uhdr_color_gamut::UHDR_CG_UNSPECIFIED),
-        sys::uhdr_color_transfer::UHDR_CT_UNSPECIFIED,
-        sys::uhdr_color_range::UHDR_CR_UNSPECIFIED,
-    );
-    dec.set_image(&mut hdr_comp)?;
-    let mut hdr_view = dec.decode_packed_view(
-        sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA1010102,
-        sys::uhdr_color_transfer::UHDR_CT_PQ,
-    )?;
-    if hdr_view.meta().0 == sys::uhdr_color_gamut::UHDR_CG_UNSPECIFIED {
-        hdr_view
-            .set_color_gamut(hdr_icc_gamut.unwrap_or(sys::uhdr_color_gamut::UHDR_CG_DISPLAY_P3));
-    }
-    if hdr_view.meta().1 == sys::uhdr_color_transfer::UHDR_CT_UNSPECIFIED {
-        hdr_view.set_color_transfer(sys::uhdr_color_transfer::UHDR_CT_PQ);
+    if args.check_alignment {
+        check_alignment(&hdr_bytes, &sdr_bytes)?;
     }
-    hdr_view.set_color_range(sys::uhdr_color_range::UHDR_CR_FULL_RANGE);
-
-    // Encode with provided SDR base JPEG.
-    let mut enc = Encoder::new()?;
-    enc.set_raw_image_view(&mut hdr_view, ImgLabel::UHDR_HDR_IMG)?;
 
-    let mut sdr_comp = CompressedImage::from_bytes(
-        &mut sdr_bytes,
-        sdr_icc_gamut.unwrap_or(sys::uhdr_color_gamut::UHDR_CG_DISPLAY_P3),
-        sys::uhdr_color_transfer::UHDR_CT_SRGB,
-        sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
-    );
-    enc.set_compressed_image(&mut sdr_comp, ImgLabel::UHDR_SDR_IMG)?;
-
-    enc.set_quality(args.base_quality, ImgLabel::UHDR_BASE_IMG)?;
-    enc.set_quality(args.gainmap_quality, ImgLabel::UHDR_GAIN_MAP_IMG)?;
-    enc.set_gainmap_scale_factor(args.gainmap_scale)?;
-    enc.set_using_multi_channel_gainmap(args.multichannel_gainmap)?;
-    enc.set_gainmap_gamma(1.0)?;
     let target_peak = args
         .target_peak_nits
         .or_else(|| gainmap_meta.as_ref().map(|m| m.target_display_peak_nits()))
@@ -84,18 +53,200 @@ pub fn run_encoding(
         );
     }
     println!("Using target peak brightness: {:.1} nits", target_peak);
-    enc.set_target_display_peak_brightness(target_peak)?;
-    enc.set_output_format(sys::uhdr_codec::UHDR_CODEC_JPG)?;
-    enc.set_preset(sys::uhdr_enc_preset::UHDR_USAGE_BEST_QUALITY)?;
-    enc.encode()?;
-
-    let out_view = enc
-        .encoded_stream()
-        .context("Encode returned null output")?;
-    let out_bytes = out_view.bytes()?;
-    fs::write(out_path, out_bytes)
+    println!(
+        "Gain map: {}-bit {}, q={}, scale={}",
+        GAINMAP_BIT_DEPTH,
+        if args.multichannel_gainmap {
+            "RGB"
+        } else {
+            "luma"
+        },
+        args.gainmap_quality,
+        args.gainmap_scale
+    );
+
+    let mut opts = MergeOptions {
+        base_quality: args.base_quality,
+        gainmap_quality: args.gainmap_quality,
+        gainmap_scale: args.gainmap_scale,
+        multichannel_gainmap: args.multichannel_gainmap,
+        target_peak_nits: Some(target_peak),
+        hdr_gamut: hdr_icc_gamut,
+        sdr_gamut: sdr_icc_gamut,
+    };
+    let mut intent = HdrIntent::decode(&mut hdr_bytes, hdr_icc_gamut)?;
+    let mut out_bytes = merge_decoded(&mut intent, &mut sdr_bytes, &opts)?.data;
+
+    if args.two_pass {
+        let mut psnr = reconstruction_psnr(intent.pixels(), &mut out_bytes)?;
+        println!(
+            "Pass 1: PSNR {:.2} dB (gain map q={}, scale={})",
+            psnr, opts.gainmap_quality, opts.gainmap_scale
+        );
+        for pass in 2..=MAX_PASSES {
+            if psnr >= args.target_psnr || !refine(&mut opts) {
+                break;
+            }
+            let mut candidate = merge_decoded(&mut intent, &mut sdr_bytes, &opts)?.data;
+            let candidate_psnr = reconstruction_psnr(intent.pixels(), &mut candidate)?;
+            println!(
+                "Pass {pass}: PSNR {:.2} dB (gain map q={}, scale={})",
+                candidate_psnr, opts.gainmap_quality, opts.gainmap_scale
+            );
+            if candidate_psnr > psnr {
+                psnr = candidate_psnr;
+                out_bytes = candidate;
+            }
+        }
+        println!("Final reconstruction PSNR: {:.2} dB", psnr);
+    }
+
+    if args.strip_metadata {
+        out_bytes = strip_metadata(&out_bytes).context("Failed to strip metadata")?;
+        println!("Stripped EXIF metadata");
+    }
+    fs::write(out_path, &out_bytes)
         .with_context(|| format!("Failed to write output {}", out_path.display()))?;
 
     println!("Wrote {}", out_path.display());
     Ok(())
 }
+
+/// Upper bound on encode passes in `--two-pass` mode.
+const MAX_PASSES: usize = 4;
+
+/// Move the gain-map settings towards higher fidelity for the next `--two-pass` pass;
+/// returns `false` once nothing is left to raise.
+fn refine(opts: &mut MergeOptions) -> bool {
+    if opts.gainmap_quality < 100 {
+        opts.gainmap_quality = (opts.gainmap_quality + 10).min(100);
+        true
+    } else if opts.gainmap_scale > 1 {
+        opts.gainmap_scale = (opts.gainmap_scale / 2).max(1);
+        true
+    } else {
+        false
+    }
+}
+
+/// Decode `encoded` back to the HDR intent and compare it against `reference`.
+fn reconstruction_psnr(reference: &DecodedPacked, encoded: &mut [u8]) -> Result<f64> {
+    let mut dec = Decoder::new()?;
+    let mut comp = CompressedImage::from_bytes(
+        encoded,
+        sys::uhdr_color_gamut::UHDR_CG_UNSPECIFIED,
+        sys::uhdr_color_transfer::UHDR_CT_UNSPECIFIED,
+        sys::uhdr_color_range::UHDR_CR_UNSPECIFIED,
+    )?;
+    dec.set_image(&mut comp)?;
+    let decoded = dec.decode_to_owned(reference.fmt, reference.ct)?;
+    ensure!(
+        (decoded.width, decoded.height) == (reference.width, reference.height),
+        "Decoded size {}x{} differs from HDR intent {}x{}",
+        decoded.width,
+        decoded.height,
+        reference.width,
+        reference.height
+    );
+    Ok(psnr_1010102(&reference.data, &decoded.data))
+}
+
+/// PSNR over the RGB channels of two RGBA1010102 buffers, in dB.
+fn psnr_1010102(a: &[u8], b: &[u8]) -> f64 {
+    let channels = |px: &[u8]| {
+        let v = u32::from_le_bytes([px[0], px[1], px[2], px[3]]);
+        [v & 0x3FF, (v >> 10) & 0x3FF, (v >> 20) & 0x3FF]
+    };
+    let mut sum = 0f64;
+    let mut count = 0usize;
+    for (pa, pb) in a.chunks_exact(4).zip(b.chunks_exact(4)) {
+        for (ca, cb) in channels(pa).into_iter().zip(channels(pb)) {
+            let d = f64::from(ca) - f64::from(cb);
+            sum += d * d;
+        }
+        count += 3;
+    }
+    if count == 0 || sum == 0.0 {
+        return f64::INFINITY;
+    }
+    let mse = sum / count as f64;
+    10.0 * (1023.0f64 * 1023.0 / mse).log10()
+}
+
+/// Side of the luma grid both bases are averaged down to for `--check-alignment`.
+const ALIGN_GRID: usize = 32;
+/// Correlation below which the two bases are reported as likely different scenes.
+const ALIGN_MIN_CORRELATION: f64 = 0.8;
+
+/// Warn when the SDR base of the HDR input and the SDR input do not look like the same
+/// scene. Only runs when both decode to the same dimensions.
+fn check_alignment(hdr_bytes: &[u8], sdr_bytes: &[u8]) -> Result<()> {
+    let hdr_base = decode_sdr_rendition(hdr_bytes).context("Decode HDR base for alignment")?;
+    let sdr_base = decode_sdr_rendition(sdr_bytes).context("Decode SDR base for alignment")?;
+    if (hdr_base.width, hdr_base.height) != (sdr_base.width, sdr_base.height) {
+        println!("Skipping alignment check: HDR and SDR dimensions differ");
+        return Ok(());
+    }
+    let corr = correlation(&luma_grid(&hdr_base), &luma_grid(&sdr_base));
+    println!("HDR/SDR base luma correlation: {:.3}", corr);
+    if corr < ALIGN_MIN_CORRELATION {
+        eprintln!(
+            "Warning: HDR and SDR inputs look like different scenes (correlation {:.3} < {:.1}); \
+             the gain map will be misaligned",
+            corr, ALIGN_MIN_CORRELATION
+        );
+    }
+    Ok(())
+}
+
+fn decode_sdr_rendition(bytes: &[u8]) -> Result<DecodedPacked> {
+    let mut buf = bytes.to_vec();
+    let mut dec = Decoder::new()?;
+    let mut comp = CompressedImage::from_bytes(
+        &mut buf,
+        sys::uhdr_color_gamut::UHDR_CG_UNSPECIFIED,
+        sys::uhdr_color_transfer::UHDR_CT_UNSPECIFIED,
+        sys::uhdr_color_range::UHDR_CR_UNSPECIFIED,
+    )?;
+    dec.set_image(&mut comp)?;
+    Ok(dec.decode_to_owned(
+        sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA8888,
+        sys::uhdr_color_transfer::UHDR_CT_SRGB,
+    )?)
+}
+
+/// Box-average RGBA8888 luma onto an `ALIGN_GRID`×`ALIGN_GRID` grid.
+fn luma_grid(img: &DecodedPacked) -> Vec<f64> {
+    let (w, h) = (img.width as usize, img.height as usize);
+    let mut sums = vec![0f64; ALIGN_GRID * ALIGN_GRID];
+    let mut counts = vec![0u32; ALIGN_GRID * ALIGN_GRID];
+    for (i, px) in img.data.chunks_exact(4).enumerate() {
+        let (x, y) = (i % w, i / w);
+        let cell = (y * ALIGN_GRID / h) * ALIGN_GRID + x * ALIGN_GRID / w;
+        sums[cell] +=
+            0.2126 * f64::from(px[0]) + 0.7152 * f64::from(px[1]) + 0.0722 * f64::from(px[2]);
+        counts[cell] += 1;
+    }
+    sums.iter()
+        .zip(&counts)
+        .map(|(s, &c)| if c == 0 { 0.0 } else { s / f64::from(c) })
+        .collect()
+}
+
+/// Pearson correlation of two equally sized samples; flat inputs count as aligned.
+fn correlation(a: &[f64], b: &[f64]) -> f64 {
+    let n = a.len() as f64;
+    let mean_a = a.iter().sum::<f64>() / n;
+    let mean_b = b.iter().sum::<f64>() / n;
+    let (mut cov, mut var_a, mut var_b) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b) {
+        let (dx, dy) = (x - mean_a, y - mean_b);
+        cov += dx * dy;
+        var_a += dx * dx;
+        var_b += dy * dy;
+    }
+    if var_a == 0.0 || var_b == 0.0 {
+        return 1.0;
+    }
+    cov / (var_a * var_b).sqrt()
+}
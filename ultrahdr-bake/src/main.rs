@@ -10,7 +10,9 @@ mod cli;
 mod color;
 mod detect;
 mod encode;
+mod manifest;
 mod motion;
+mod strip;
 
 fn main() -> Result<()> {
     let args = cli::Cli::parse();
@@ -20,12 +22,17 @@ fn main() -> Result<()> {
 fn run(cmd: cli::Command) -> Result<()> {
     match cmd {
         cli::Command::Bake(args) => {
+            if let Some(path) = &args.manifest {
+                return manifest::run_manifest(&args, path);
+            }
             ensure!(
                 args.inputs.is_empty() || (args.hdr.is_none() && args.sdr.is_none()),
                 "Provide either two positional JPEGs for auto-detection or --hdr/--sdr, not both"
             );
 
-            let inputs = detect::resolve_inputs(&args)?;
+            let detection = detect::resolve_inputs(&args)?;
+            report_detection(&detection);
+            let inputs = detection.pair;
             let out_path = resolve_out_path(&args, &inputs);
             encode::run_encoding(&args, &inputs, &out_path)
         }
@@ -42,6 +49,20 @@ fn run(cmd: cli::Command) -> Result<()> {
     }
 }
 
+fn report_detection(detection: &detect::DetectionResult) {
+    if detection.hdr_reason == detect::HdrDetection::Explicit {
+        return;
+    }
+    println!(
+        "Auto-detected HDR input: {} ({})",
+        detection.pair.hdr.display(),
+        detection.hdr_reason.as_str()
+    );
+    if detection.confidence == detect::Confidence::Low {
+        eprintln!("Warning: its gain map metadata advertises no HDR headroom");
+    }
+}
+
 fn resolve_out_path(args: &cli::BakeArgs, inputs: &detect::InputPair) -> PathBuf {
     args.out
         .clone()
@@ -0,0 +1,170 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail, ensure};
+use serde::Deserialize;
+
+use crate::cli::BakeArgs;
+use crate::detect::InputPair;
+
+/// Batch manifest: a list of `[[job]]` tables.
+///
+/// ```toml
+/// [[job]]
+/// hdr = "shots/a_hdr.jpg"
+/// sdr = "shots/a_sdr.jpg"
+/// out = "out/a.jpg"      # optional, defaults to <sdr>-merge.<ext>
+/// gm-q = 90              # optional overrides of the matching CLI flags
+/// target-peak = 1000.0
+/// ```
+///
+/// Relative paths are resolved against the manifest's directory. Flags given on the
+/// command line act as defaults for every job.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    #[serde(default, rename = "job")]
+    jobs: Vec<Job>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct Job {
+    hdr: PathBuf,
+    sdr: PathBuf,
+    out: Option<PathBuf>,
+    base_q: Option<i32>,
+    gm_q: Option<i32>,
+    scale: Option<i32>,
+    multichannel: Option<bool>,
+    target_peak: Option<f32>,
+    strip_metadata: Option<bool>,
+}
+
+impl Job {
+    fn apply(&self, defaults: &BakeArgs) -> Result<BakeArgs> {
+        let mut args = defaults.clone();
+        if let Some(q) = self.base_q {
+            ensure!((1..=100).contains(&q), "base-q must be within 1-100");
+            args.base_quality = q;
+        }
+        if let Some(q) = self.gm_q {
+            ensure!((1..=100).contains(&q), "gm-q must be within 1-100");
+            args.gainmap_quality = q;
+        }
+        if let Some(scale) = self.scale {
+            ensure!(scale >= 1, "scale must be at least 1");
+            args.gainmap_scale = scale;
+        }
+        if let Some(mc) = self.multichannel {
+            args.multichannel_gainmap = mc;
+        }
+        if let Some(peak) = self.target_peak {
+            args.target_peak_nits = Some(peak);
+        }
+        if let Some(strip) = self.strip_metadata {
+            args.strip_metadata = strip;
+        }
+        Ok(args)
+    }
+}
+
+/// Run every job in the manifest, reporting each outcome; fails if any job failed.
+pub fn run_manifest(defaults: &BakeArgs, path: &Path) -> Result<()> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read manifest {}", path.display()))?;
+    let manifest: Manifest = toml::from_str(&text)
+        .with_context(|| format!("Failed to parse manifest {}", path.display()))?;
+    ensure!(
+        !manifest.jobs.is_empty(),
+        "Manifest {} contains no [[job]] entries",
+        path.display()
+    );
+
+    let base = path.parent().unwrap_or_else(|| Path::new("."));
+    let total = manifest.jobs.len();
+    let mut failed = 0;
+    for (i, job) in manifest.jobs.iter().enumerate() {
+        println!("[{}/{}] {}", i + 1, total, job.sdr.display());
+        match run_job(defaults, base, job) {
+            Ok(out) => println!("[{}/{}] ok: {}", i + 1, total, out.display()),
+            Err(err) => {
+                failed += 1;
+                eprintln!("[{}/{}] failed: {:#}", i + 1, total, err);
+            }
+        }
+    }
+
+    println!("{} of {} jobs succeeded", total - failed, total);
+    if failed > 0 {
+        bail!("{failed} of {total} manifest jobs failed");
+    }
+    Ok(())
+}
+
+fn run_job(defaults: &BakeArgs, base: &Path, job: &Job) -> Result<PathBuf> {
+    let args = job.apply(defaults)?;
+    let inputs = InputPair {
+        hdr: base.join(&job.hdr),
+        sdr: base.join(&job.sdr),
+    };
+    let out_path = match &job.out {
+        Some(out) => base.join(out),
+        None => crate::default_out_for_sdr(&inputs.sdr),
+    };
+    crate::encode::run_encoding(&args, &inputs, &out_path)?;
+    Ok(out_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::cli::{Cli, Command};
+
+    fn default_args() -> BakeArgs {
+        match Cli::parse_from(["ultrahdr-bake", "bake", "--manifest", "jobs.toml"]).into_command() {
+            Command::Bake(args) => args,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn job_overrides_apply_on_top_of_cli_defaults() {
+        let manifest: Manifest = toml::from_str(
+            r#"
+            [[job]]
+            hdr = "a_hdr.jpg"
+            sdr = "a_sdr.jpg"
+
+            [[job]]
+            hdr = "b_hdr.jpg"
+            sdr = "b_sdr.jpg"
+            out = "b.jpg"
+            gm-q = 70
+            target-peak = 1000.0
+            "#,
+        )
+        .expect("parse manifest");
+        assert_eq!(manifest.jobs.len(), 2);
+
+        let defaults = default_args();
+        let first = manifest.jobs[0].apply(&defaults).expect("apply");
+        assert_eq!(first.gainmap_quality, defaults.gainmap_quality);
+        assert_eq!(first.target_peak_nits, defaults.target_peak_nits);
+
+        let second = manifest.jobs[1].apply(&defaults).expect("apply");
+        assert_eq!(second.gainmap_quality, 70);
+        assert_eq!(second.target_peak_nits, Some(1000.0));
+        assert_eq!(manifest.jobs[1].out.as_deref(), Some(Path::new("b.jpg")));
+    }
+
+    #[test]
+    fn unknown_job_keys_are_rejected() {
+        let parsed = toml::from_str::<Manifest>("[[job]]\nhdr = \"a\"\nsdr = \"b\"\nquality = 1\n");
+        assert!(parsed.is_err());
+    }
+}
@@ -0,0 +1,171 @@
+use anyhow::{Context, Result};
+
+/// Drop EXIF (and with it any GPS IFD) from the primary image of an UltraHDR JPEG.
+///
+/// XMP, ICC, the ISO 21496-1 gain-map metadata and MPF are kept, and the MPF index is
+/// patched in place so the gain map stays addressable after the primary image shrinks.
+pub fn strip_metadata(bytes: &[u8]) -> Result<Vec<u8>> {
+    ultrahdr::strip_exif(bytes).context("Strip EXIF from primary image")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detect::probe_gainmap_metadata;
+    use img_parts::jpeg::markers;
+    use ultrahdr::{
+        Encoder, GainMapSource, ImgLabel, JpegSource, RawImage, extract_app_segments, sys,
+    };
+
+    const EXIF_PREFIX: &[u8] = b"Exif\0\0";
+
+    const W: u32 = 16;
+    const H: u32 = 16;
+
+    /// Encode a flat raw HDR/SDR pair after `configure` has adjusted the encoder.
+    fn encode_pair(configure: impl FnOnce(&mut Encoder)) -> Vec<u8> {
+        let mut hdr = Vec::with_capacity((W * H * 4) as usize);
+        for _ in 0..W * H {
+            hdr.extend_from_slice(&(593u32 | (593 << 10) | (593 << 20) | (3 << 30)).to_le_bytes());
+        }
+        let mut sdr = [128u8, 128, 128, 255].repeat((W * H) as usize);
+        let mut hdr_raw = RawImage::packed(
+            sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA1010102,
+            W,
+            H,
+            &mut hdr,
+            sys::uhdr_color_gamut::UHDR_CG_BT_2100,
+            sys::uhdr_color_transfer::UHDR_CT_PQ,
+            sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+        )
+        .unwrap();
+        let mut sdr_raw = RawImage::rgba8888(
+            W,
+            H,
+            &mut sdr,
+            sys::uhdr_color_gamut::UHDR_CG_BT_709,
+            sys::uhdr_color_transfer::UHDR_CT_SRGB,
+            sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+        )
+        .unwrap();
+
+        let mut enc = Encoder::new().unwrap();
+        configure(&mut enc);
+        enc.set_raw_image(&mut hdr_raw, ImgLabel::UHDR_HDR_IMG)
+            .unwrap();
+        enc.set_raw_image(&mut sdr_raw, ImgLabel::UHDR_SDR_IMG)
+            .unwrap();
+        enc.encode().unwrap();
+        enc.encoded_stream().unwrap().bytes().unwrap().to_vec()
+    }
+
+    fn encode_with_exif() -> Vec<u8> {
+        let mut exif = EXIF_PREFIX.to_vec();
+        exif.extend_from_slice(b"MM\0\x2a\0\0\0\x08\0\0");
+        encode_pair(|enc| enc.add_app_segment(markers::APP1, exif).unwrap())
+    }
+
+    fn has_exif(bytes: &[u8]) -> bool {
+        extract_app_segments(bytes, markers::APP1)
+            .iter()
+            .any(|p| p.starts_with(EXIF_PREFIX))
+    }
+
+    #[test]
+    fn strip_removes_exif_but_keeps_gainmap() {
+        let encoded = encode_with_exif();
+        assert!(has_exif(&encoded));
+
+        let mut stripped = strip_metadata(&encoded).unwrap();
+        assert!(!has_exif(&stripped));
+        assert!(stripped.len() < encoded.len());
+        assert!(
+            probe_gainmap_metadata(&mut stripped).unwrap().is_some(),
+            "stripped output no longer probes as UltraHDR"
+        );
+    }
+
+    /// JPEG marker segment with its length prefix.
+    fn segment(marker: u8, payload: &[u8]) -> Vec<u8> {
+        let mut out = vec![0xFF, marker];
+        out.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+        out.extend_from_slice(payload);
+        out
+    }
+
+    /// Little-endian (`II`) MPF payload as camera pipelines write it: version, image count
+    /// and MP entry tags, two entries, then an attribute IFD the rewrite must keep.
+    fn le_mpf(primary_size: u32, gainmap_size: u32, gainmap_offset: u32) -> Vec<u8> {
+        let mut p = b"MPF\0II*\0".to_vec();
+        p.extend_from_slice(&8u32.to_le_bytes());
+        p.extend_from_slice(&3u16.to_le_bytes());
+        p.extend_from_slice(&[0x00, 0xB0, 7, 0, 4, 0, 0, 0, b'0', b'1', b'0', b'0']);
+        p.extend_from_slice(&[0x01, 0xB0, 4, 0, 1, 0, 0, 0, 2, 0, 0, 0]);
+        p.extend_from_slice(&[0x02, 0xB0, 7, 0, 32, 0, 0, 0, 50, 0, 0, 0]);
+        p.extend_from_slice(&82u32.to_le_bytes()); // attribute IFD
+        for (attr, size, offset) in [
+            (0x0003_0000u32, primary_size, 0),
+            (0, gainmap_size, gainmap_offset),
+        ] {
+            p.extend_from_slice(&attr.to_le_bytes());
+            p.extend_from_slice(&size.to_le_bytes());
+            p.extend_from_slice(&offset.to_le_bytes());
+            p.extend_from_slice(&[0; 4]);
+        }
+        p.extend_from_slice(&1u16.to_le_bytes());
+        p.extend_from_slice(&[0x01, 0xB1, 4, 0, 1, 0, 0, 0, 1, 0, 0, 0]); // MPIndividualNum
+        p.extend_from_slice(&0u32.to_le_bytes());
+        p
+    }
+
+    /// Primary image with EXIF and an `II` MPF index, followed by a gain-map JPEG.
+    fn little_endian_ultrahdr(gainmap: &[u8]) -> Vec<u8> {
+        let mut exif = EXIF_PREFIX.to_vec();
+        exif.extend_from_slice(b"II*\0\x08\0\0\0\0\0");
+        let build = |primary_size, offset| {
+            let mut out = vec![0xFF, 0xD8];
+            out.extend(segment(markers::APP1, &exif));
+            out.extend(segment(
+                markers::APP2,
+                &le_mpf(primary_size, gainmap.len() as u32, offset),
+            ));
+            out.extend(segment(0xDB, &[0; 5]));
+            out.extend_from_slice(&[0xFF, 0xDA, 0, 2, 1, 2, 3, 0xFF, 0xD9]);
+            out
+        };
+        let primary_len = build(0, 0).len() as u32;
+        let tiff_base = 2 + 4 + exif.len() as u32 + 4 + 4;
+        let mut out = build(primary_len, primary_len - tiff_base);
+        out.extend_from_slice(gainmap);
+        out
+    }
+
+    #[test]
+    fn strip_keeps_little_endian_mpf_and_gainmap() {
+        let gainmap = [0xFF, 0xD8, 0xFF, 0xDA, 0, 2, 9, 9, 0xFF, 0xD9];
+        let source = little_endian_ultrahdr(&gainmap);
+        let source_mpf = mpf_payload(&source);
+
+        let stripped = strip_metadata(&source).unwrap();
+        assert!(!has_exif(&stripped));
+        let mpf = mpf_payload(&stripped);
+        assert_eq!(&mpf[4..8], b"II*\0");
+        // Only the two entries' size/offset words change; every other tag and the
+        // attribute IFD survive byte for byte.
+        assert_eq!(mpf.len(), source_mpf.len());
+        assert_eq!(mpf[..58], source_mpf[..58]);
+        assert_eq!(mpf[86..], source_mpf[86..]);
+        let reached = JpegSource::new(&stripped)
+            .gainmap_image()
+            .unwrap()
+            .expect("gain map unreachable after strip");
+        assert_eq!(reached.data, gainmap);
+    }
+
+    fn mpf_payload(bytes: &[u8]) -> Vec<u8> {
+        extract_app_segments(bytes, markers::APP2)
+            .into_iter()
+            .find(|p| p.starts_with(b"MPF\0"))
+            .expect("MPF segment")
+    }
+}
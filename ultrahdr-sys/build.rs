@@ -166,21 +166,32 @@ fn main() {
     let source_dir = locate_src_dir(&manifest_dir);
     let out_dir = PathBuf::from(env::var("OUT_DIR").expect("OUT_DIR"));
 
-    // docs.rs builds in a sandboxed environment without network access.
-    // Skip the full CMake build and only generate bindings for documentation.
-    if env::var("DOCS_RS").is_ok() {
-        println!("cargo:warning=Building for docs.rs: skipping CMake build");
-        let bindings = bindgen::Builder::default()
+    // docs.rs builds in a sandboxed environment without network access, and
+    // ULTRAHDR_BINDINGS_ONLY serves targets that cannot build the C++ library at
+    // all (the `no_std` core of `ultrahdr` uses the types but no functions).
+    // Skip the full CMake build and only generate bindings.
+    println!("cargo:rerun-if-env-changed=ULTRAHDR_BINDINGS_ONLY");
+    let docs_rs = env::var("DOCS_RS").is_ok();
+    if docs_rs || env::var("ULTRAHDR_BINDINGS_ONLY").is_ok() {
+        if docs_rs {
+            println!("cargo:warning=Building for docs.rs: skipping CMake build");
+        }
+        let mut bindings = bindgen::Builder::default()
             .header(source_dir.join("ultrahdr_api.h").to_string_lossy())
             .clang_arg(format!("-I{}", source_dir.display()))
+            .use_core()
             .rustified_enum("uhdr_.*")
             .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()))
             .layout_tests(false)
             .allowlist_function("uhdr_.*")
             .allowlist_type("uhdr_.*")
-            .allowlist_var("UHDR_.*")
-            .generate()
-            .expect("bindgen failed");
+            .allowlist_var("UHDR_.*");
+        if !docs_rs {
+            // Struct layouts must match the (possibly 32-bit) target, not the host.
+            let target = env::var("TARGET").expect("TARGET");
+            bindings = bindings.clang_arg(format!("--target={target}"));
+        }
+        let bindings = bindings.generate().expect("bindgen failed");
         bindings
             .write_to_file(out_dir.join("bindings.rs"))
             .expect("failed to write bindings");
@@ -380,10 +391,13 @@ fn main() {
     let mut bindings = bindgen::Builder::default()
         .header(source_dir.join("ultrahdr_api.h").to_string_lossy())
         .clang_arg(format!("-I{}", source_dir.display()))
+        .use_core()
         .rustified_enum("uhdr_.*")
         .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()))
         .layout_tests(false)
         .clang_arg(format!("--target={}", bindgen_target));
+    // Keep the allowlist independent of features: the effect entry points
+    // (`uhdr_add_effect_*`) run on the CPU and `gles` only adds GPU acceleration.
     if !is_wasm {
         bindings = bindings
             .allowlist_function("uhdr_.*")
@@ -1,5 +1,27 @@
+#![no_std]
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
 #![allow(non_upper_case_globals)]
 
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::ffi::c_int;
+
+    type Codec = *mut uhdr_codec_private_t;
+
+    /// The effects are CPU features of libultrahdr; only their GPU acceleration depends on
+    /// the `gles` feature, so the bindings must exist in every build.
+    #[test]
+    fn effect_functions_are_bound_in_default_build() {
+        let _: unsafe extern "C" fn(Codec, uhdr_mirror_direction_t) -> uhdr_error_info_t =
+            uhdr_add_effect_mirroring;
+        let _: unsafe extern "C" fn(Codec, c_int) -> uhdr_error_info_t = uhdr_add_effect_rotate;
+        let _: unsafe extern "C" fn(Codec, c_int, c_int, c_int, c_int) -> uhdr_error_info_t =
+            uhdr_add_effect_crop;
+        let _: unsafe extern "C" fn(Codec, c_int, c_int) -> uhdr_error_info_t =
+            uhdr_add_effect_resize;
+    }
+}
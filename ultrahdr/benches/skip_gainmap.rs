@@ -0,0 +1,71 @@
+//! Decode throughput of the `set_skip_gainmap` thumbnail path against a full decode.
+//!
+//! Both variants request RGBA8888/sRGB, so the difference is the gain-map parse and decode
+//! that skipping avoids, plus the cost of stripping the stream in `set_image`. Run with
+//! `cargo bench -p ultrahdr --bench skip_gainmap`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use ultrahdr::pack_rgba1010102;
+use ultrahdr::prelude::*;
+
+const WIDTH: u32 = 1920;
+const HEIGHT: u32 = 1080;
+const ITERATIONS: u32 = 20;
+
+fn main() -> Result<()> {
+    let mut stream = sample_stream()?;
+    println!("{WIDTH}x{HEIGHT} UltraHDR JPEG, {} bytes", stream.len());
+    for skip in [false, true] {
+        // One untimed run warms up the allocator and libjpeg-turbo's tables.
+        decode(&mut stream, skip)?;
+        let mut total = Duration::ZERO;
+        for _ in 0..ITERATIONS {
+            let start = Instant::now();
+            black_box(decode(&mut stream, skip)?);
+            total += start.elapsed();
+        }
+        println!("skip_gainmap={skip}: {:?} per decode", total / ITERATIONS);
+    }
+    Ok(())
+}
+
+fn decode(stream: &mut [u8], skip: bool) -> Result<DecodedPacked> {
+    let mut dec = Decoder::new()?;
+    dec.set_skip_gainmap(skip);
+    let mut comp = CompressedImage::from_bytes(
+        stream,
+        sys::uhdr_color_gamut::UHDR_CG_UNSPECIFIED,
+        sys::uhdr_color_transfer::UHDR_CT_UNSPECIFIED,
+        sys::uhdr_color_range::UHDR_CR_UNSPECIFIED,
+    )?;
+    dec.set_image(&mut comp)?;
+    dec.decode_to_owned(
+        sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA8888,
+        sys::uhdr_color_transfer::UHDR_CT_SRGB,
+    )
+}
+
+/// Encode a PQ gradient with highlights above SDR white, so the gain map is not flat.
+fn sample_stream() -> Result<Vec<u8>> {
+    let mut hdr = Vec::with_capacity((WIDTH * HEIGHT * 4) as usize);
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let v = 0.3 + 0.45 * x as f32 / WIDTH as f32;
+            let tint = 0.1 * y as f32 / HEIGHT as f32;
+            hdr.extend_from_slice(&pack_rgba1010102(v, v - tint, v + tint, 1.0).to_le_bytes());
+        }
+    }
+    let mut raw = RawImage::packed(
+        sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA1010102,
+        WIDTH,
+        HEIGHT,
+        &mut hdr,
+        sys::uhdr_color_gamut::UHDR_CG_BT_2100,
+        sys::uhdr_color_transfer::UHDR_CT_PQ,
+        sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+    )?;
+    let mut enc = Encoder::new()?;
+    enc.set_raw_image(&mut raw, ImgLabel::UHDR_HDR_IMG)?;
+    Ok(enc.encode_to_owned()?.data)
+}
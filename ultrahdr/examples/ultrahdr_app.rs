@@ -3,7 +3,7 @@ use clap::{Parser, Subcommand, ValueEnum};
 use std::fs::{self, File};
 use std::io::Write;
 use std::path::PathBuf;
-use ultrahdr::{CompressedImage, Decoder, Encoder, ImgFormat, ImgLabel, RawImage, sys};
+use ultrahdr::prelude::*;
 
 #[derive(Debug, Parser)]
 #[command(about = "Rust port of ultrahdr_app: encode/decode UltraHDR streams")]
@@ -61,6 +61,9 @@ enum Command {
         /// Output transfer
         #[arg(long, value_enum, default_value = "pq")]
         transfer: Transfer,
+        /// Prepend a header with format, dimensions, stride and color metadata
+        #[arg(long, default_value_t = false)]
+        with_header: bool,
     },
 }
 
@@ -88,9 +91,9 @@ enum RawFmt {
 impl RawFmt {
     fn to_img_fmt(&self) -> ImgFormat {
         match self {
-            RawFmt::Rgba8888 => sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA8888,
-            RawFmt::Rgba1010102 => sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA1010102,
-            RawFmt::RgbaF16 => sys::uhdr_img_fmt::UHDR_IMG_FMT_64bppRGBAHalfFloat,
+            RawFmt::Rgba8888 => ImgFormat::UHDR_IMG_FMT_32bppRGBA8888,
+            RawFmt::Rgba1010102 => ImgFormat::UHDR_IMG_FMT_32bppRGBA1010102,
+            RawFmt::RgbaF16 => ImgFormat::UHDR_IMG_FMT_64bppRGBAHalfFloat,
         }
     }
 }
@@ -103,11 +106,11 @@ enum Transfer {
 }
 
 impl Transfer {
-    fn to_ct(&self) -> sys::uhdr_color_transfer {
+    fn to_ct(&self) -> ColorTransfer {
         match self {
-            Transfer::Pq => sys::uhdr_color_transfer::UHDR_CT_PQ,
-            Transfer::Hlg => sys::uhdr_color_transfer::UHDR_CT_HLG,
-            Transfer::Srgb => sys::uhdr_color_transfer::UHDR_CT_SRGB,
+            Transfer::Pq => ColorTransfer::UHDR_CT_PQ,
+            Transfer::Hlg => ColorTransfer::UHDR_CT_HLG,
+            Transfer::Srgb => ColorTransfer::UHDR_CT_SRGB,
         }
     }
 }
@@ -143,7 +146,8 @@ fn main() -> Result<()> {
             out_raw,
             fmt,
             transfer,
-        } => decode(uhdr, out_raw, fmt, transfer),
+            with_header,
+        } => decode(uhdr, out_raw, fmt, transfer, with_header),
     }
 }
 
@@ -172,30 +176,30 @@ fn encode(args: EncodeArgs) -> Result<()> {
         width,
         height,
         &mut hdr_bytes,
-        sys::uhdr_color_gamut::UHDR_CG_DISPLAY_P3,
-        sys::uhdr_color_transfer::UHDR_CT_PQ,
-        sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+        ColorGamut::UHDR_CG_DISPLAY_P3,
+        ColorTransfer::UHDR_CT_PQ,
+        ColorRange::UHDR_CR_FULL_RANGE,
     )?;
 
-    let mut enc = Encoder::new()?;
+    let mut enc = Encoder::builder()
+        .base_quality(base_q)
+        .gainmap_quality(gm_q)
+        .gainmap_scale(scale)
+        .multichannel(mc)
+        .gamma(1.0)
+        .target_peak_nits(10000.0)
+        .output_format(Codec::UHDR_CODEC_JPG)
+        .preset(EncPreset::UHDR_USAGE_BEST_QUALITY)
+        .build()?;
     enc.set_raw_image(&mut hdr_raw, ImgLabel::UHDR_HDR_IMG)?;
 
     let mut sdr_comp = CompressedImage::from_bytes(
         &mut sdr_bytes,
-        sys::uhdr_color_gamut::UHDR_CG_DISPLAY_P3,
-        sys::uhdr_color_transfer::UHDR_CT_SRGB,
-        sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
-    );
+        ColorGamut::UHDR_CG_DISPLAY_P3,
+        ColorTransfer::UHDR_CT_SRGB,
+        ColorRange::UHDR_CR_FULL_RANGE,
+    )?;
     enc.set_compressed_image(&mut sdr_comp, ImgLabel::UHDR_SDR_IMG)?;
-
-    enc.set_quality(base_q, ImgLabel::UHDR_BASE_IMG)?;
-    enc.set_quality(gm_q, ImgLabel::UHDR_GAIN_MAP_IMG)?;
-    enc.set_gainmap_scale_factor(scale)?;
-    enc.set_using_multi_channel_gainmap(mc)?;
-    enc.set_gainmap_gamma(1.0)?;
-    enc.set_target_display_peak_brightness(10000.0)?;
-    enc.set_output_format(sys::uhdr_codec::UHDR_CODEC_JPG)?;
-    enc.set_preset(sys::uhdr_enc_preset::UHDR_USAGE_BEST_QUALITY)?;
     enc.encode()?;
 
     let out_img = enc
@@ -212,35 +216,33 @@ fn decode(
     out_raw_path: PathBuf,
     fmt: RawFmt,
     transfer: Transfer,
+    with_header: bool,
 ) -> Result<()> {
-    let mut uhdr_bytes =
-        fs::read(&uhdr_path).with_context(|| format!("Failed to read {}", uhdr_path.display()))?;
-    let mut dec = Decoder::new()?;
-    let mut comp = CompressedImage::from_bytes(
-        &mut uhdr_bytes,
-        sys::uhdr_color_gamut::UHDR_CG_UNSPECIFIED,
-        sys::uhdr_color_transfer::UHDR_CT_UNSPECIFIED,
-        sys::uhdr_color_range::UHDR_CR_UNSPECIFIED,
-    );
-    dec.set_image(&mut comp)?;
+    let mut dec = Decoder::open(&uhdr_path)?;
 
     let img_fmt = fmt.to_img_fmt();
     let decoded = dec.decode_packed_view(img_fmt, transfer.to_ct())?;
     let mut file = File::create(&out_raw_path)
         .with_context(|| format!("Failed to write {}", out_raw_path.display()))?;
-    for y in 0..decoded.height() as usize {
-        let row = decoded.row(y)?;
-        file.write_all(row)
+    if with_header {
+        decoded
+            .to_owned()?
+            .write_with_header(&mut file)
             .with_context(|| format!("Failed to write {}", out_raw_path.display()))?;
+    } else {
+        for row in decoded.rows() {
+            file.write_all(row?)
+                .with_context(|| format!("Failed to write {}", out_raw_path.display()))?;
+        }
     }
     println!(
-        "Decoded {} -> {} ({}x{}, {:?} {:?})",
+        "Decoded {} -> {} ({}x{}, {:?} {})",
         uhdr_path.display(),
         out_raw_path.display(),
         decoded.width(),
         decoded.height(),
         img_fmt,
-        transfer.to_ct()
+        transfer.to_ct().label()
     );
     Ok(())
 }
@@ -0,0 +1,365 @@
+//! Structural checks of an UltraHDR JPEG against the container and metadata rules of the
+//! UltraHDR / ISO 21496-1 specifications.
+
+use crate::jpeg::{self, JpegInfo, Segment};
+use crate::metadata;
+use crate::types::GainMapMetadata;
+
+/// Outcome of a single conformance check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CheckStatus {
+    /// The file satisfies the rule.
+    Pass,
+    /// The file deviates in a way common readers tolerate.
+    Warn,
+    /// The file breaks the rule; readers may ignore the gain map or reject the file.
+    Fail,
+}
+
+/// One entry of a [`ConformanceReport`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConformanceCheck {
+    /// Short identifier of the rule, e.g. `"mpf"` or `"capacity-ordering"`.
+    pub name: &'static str,
+    /// Result of the check.
+    pub status: CheckStatus,
+    /// Human-readable explanation.
+    pub message: String,
+}
+
+/// Result of [`check_conformance`], one entry per check that could be run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConformanceReport {
+    /// Checks in the order they were run.
+    pub checks: Vec<ConformanceCheck>,
+}
+
+impl ConformanceReport {
+    /// Worst status among the checks ([`CheckStatus::Pass`] for an empty report).
+    pub fn status(&self) -> CheckStatus {
+        self.checks
+            .iter()
+            .map(|c| c.status)
+            .max()
+            .unwrap_or(CheckStatus::Pass)
+    }
+
+    /// Whether no check failed. Warnings do not count as failures.
+    pub fn passed(&self) -> bool {
+        self.status() != CheckStatus::Fail
+    }
+
+    /// Look up a check by name.
+    pub fn check(&self, name: &str) -> Option<&ConformanceCheck> {
+        self.checks.iter().find(|c| c.name == name)
+    }
+
+    fn push(&mut self, name: &'static str, status: CheckStatus, message: impl Into<String>) {
+        self.checks.push(ConformanceCheck {
+            name,
+            status,
+            message: message.into(),
+        });
+    }
+}
+
+/// Validate an UltraHDR JPEG for release gating.
+///
+/// Runs, in order: primary JPEG header parsing (`jpeg`), presence and shape of the MPF
+/// index (`mpf`), resolution of every MPF offset (`mpf-offsets`), the gain-map image header
+/// (`gainmap-image`), gain-map metadata carriage in both images (`metadata-carriage`),
+/// metadata field ranges as parsed by libultrahdr (`metadata-ranges`), capacity and boost
+/// ordering (`capacity-ordering`) and the gain-map color space against `use_base_cg`
+/// (`color-space`). Checks that depend on a failed one are not run, so a missing MPF index
+/// yields a short report.
+///
+/// This goes beyond a successful [`Decoder::probe`](crate::Decoder::probe): libultrahdr
+/// tolerates several of these deviations while other readers do not.
+pub fn check_conformance(bytes: &[u8]) -> ConformanceReport {
+    use CheckStatus::*;
+    let mut report = ConformanceReport::default();
+
+    let (primary_segments, base) = match jpeg::parse_header(bytes)
+        .and_then(|(segments, _)| JpegInfo::parse(bytes).map(|info| (segments, info)))
+    {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            report.push(
+                "jpeg",
+                Fail,
+                format!("primary image is not a valid JPEG: {err}"),
+            );
+            return report;
+        }
+    };
+    report.push(
+        "jpeg",
+        Pass,
+        format!("primary image is {}x{}", base.width, base.height),
+    );
+
+    let entries = match jpeg::mpf_entries(bytes) {
+        Ok(Some(entries)) if entries.len() >= 2 => entries,
+        Ok(Some(entries)) => {
+            report.push(
+                "mpf",
+                Fail,
+                format!(
+                    "MPF index lists {} image(s); a gain map needs 2",
+                    entries.len()
+                ),
+            );
+            return report;
+        }
+        _ => {
+            report.push("mpf", Fail, "primary image has no MPF index");
+            return report;
+        }
+    };
+    let (primary, _) = entries[0];
+    if primary.offset != 0 || primary.size == 0 || primary.size as usize > bytes.len() {
+        report.push(
+            "mpf",
+            Fail,
+            format!(
+                "MPF entry 0 must describe the primary image at offset 0 (size {}, offset {})",
+                primary.size, primary.offset
+            ),
+        );
+        return report;
+    }
+    report.push(
+        "mpf",
+        Pass,
+        format!("MPF index lists {} images", entries.len()),
+    );
+
+    let primary_len = primary.size as usize;
+    let mut offset_errors = Vec::new();
+    for (i, (entry, start)) in entries.iter().enumerate().skip(1) {
+        let end = start.checked_add(entry.size as usize);
+        if entry.offset == 0 || end.is_none_or(|end| end > bytes.len()) {
+            offset_errors.push(format!("image {i} points outside the file"));
+        } else if *start < primary_len {
+            offset_errors.push(format!("image {i} overlaps the primary image"));
+        } else if !bytes[*start..].starts_with(&[0xFF, jpeg::SOI]) {
+            offset_errors.push(format!("image {i} does not start with SOI"));
+        }
+    }
+    if !offset_errors.is_empty() {
+        report.push("mpf-offsets", Fail, offset_errors.join("; "));
+        return report;
+    }
+    report.push("mpf-offsets", Pass, "every MPF entry resolves to a JPEG");
+
+    let (entry, start) = entries[1];
+    let gainmap = &bytes[start..start + entry.size as usize];
+    let (gainmap_segments, info) = match jpeg::parse_header(gainmap)
+        .and_then(|(segments, _)| JpegInfo::parse(gainmap).map(|info| (segments, info)))
+    {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            report.push(
+                "gainmap-image",
+                Fail,
+                format!("gain-map image is not a valid JPEG: {err}"),
+            );
+            return report;
+        }
+    };
+    if info.components != 1 && info.components != 3 {
+        report.push(
+            "gainmap-image",
+            Fail,
+            format!(
+                "gain map has {} components; expected 1 or 3",
+                info.components
+            ),
+        );
+    } else if info.width > base.width || info.height > base.height {
+        report.push(
+            "gainmap-image",
+            Warn,
+            format!(
+                "gain map ({}x{}) is larger than the base image ({}x{})",
+                info.width, info.height, base.width, base.height
+            ),
+        );
+    } else {
+        report.push(
+            "gainmap-image",
+            Pass,
+            format!(
+                "gain map is {}x{} with {} component(s)",
+                info.width, info.height, info.components
+            ),
+        );
+    }
+
+    let gainmap_iso = gainmap_segments.iter().any(metadata::is_iso_segment);
+    let gainmap_xmp = gainmap_segments.iter().any(metadata::is_xmp_segment);
+    let primary_iso = primary_segments.iter().any(metadata::is_iso_segment);
+    let primary_xmp = primary_segments.iter().any(|s| {
+        s.marker == jpeg::APP1
+            && s.data.starts_with(metadata::XMP_NAMESPACE)
+            && contains(&s.data, br#"Item:Semantic="GainMap""#)
+    });
+    if !gainmap_iso && !gainmap_xmp {
+        report.push(
+            "metadata-carriage",
+            Fail,
+            "gain-map image carries neither ISO 21496-1 nor hdrgm XMP metadata",
+        );
+    } else if (gainmap_iso && !primary_iso) || (gainmap_xmp && !primary_xmp) {
+        report.push(
+            "metadata-carriage",
+            Warn,
+            "primary image lacks the ISO version block or XMP Container matching the gain map",
+        );
+    } else {
+        report.push(
+            "metadata-carriage",
+            Pass,
+            match (gainmap_iso, gainmap_xmp) {
+                (true, true) => "ISO 21496-1 and XMP metadata present",
+                (true, false) => "ISO 21496-1 metadata present",
+                _ => "XMP metadata present",
+            },
+        );
+    }
+
+    let meta = match metadata::probe_metadata(bytes) {
+        Ok(meta) => meta,
+        Err(err) => {
+            report.push(
+                "metadata-ranges",
+                Fail,
+                format!("libultrahdr could not read the gain-map metadata: {err}"),
+            );
+            return report;
+        }
+    };
+    match meta.validate() {
+        Ok(()) => report.push("metadata-ranges", Pass, "all fields are in range"),
+        Err(err) => report.push("metadata-ranges", Fail, err.detail.unwrap_or_default()),
+    }
+    check_ordering(&mut report, &meta);
+    check_color_space(&mut report, &meta, &primary_segments, &gainmap_segments);
+    report
+}
+
+fn check_ordering(report: &mut ConformanceReport, meta: &GainMapMetadata) {
+    let mut problems = Vec::new();
+    if meta.hdr_capacity_max < meta.hdr_capacity_min {
+        problems.push(format!(
+            "hdr_capacity_max {} < hdr_capacity_min {}",
+            meta.hdr_capacity_max, meta.hdr_capacity_min
+        ));
+    }
+    for c in 0..3 {
+        if meta.max_content_boost[c] < meta.min_content_boost[c] {
+            problems.push(format!(
+                "channel {c}: max_content_boost {} < min_content_boost {}",
+                meta.max_content_boost[c], meta.min_content_boost[c]
+            ));
+        }
+    }
+    if problems.is_empty() {
+        report.push(
+            "capacity-ordering",
+            CheckStatus::Pass,
+            "capacity and boost ranges are ordered",
+        );
+    } else {
+        report.push("capacity-ordering", CheckStatus::Fail, problems.join("; "));
+    }
+}
+
+fn check_color_space(
+    report: &mut ConformanceReport,
+    meta: &GainMapMetadata,
+    primary: &[Segment],
+    gainmap: &[Segment],
+) {
+    let base_icc = icc_chunks(primary);
+    let gainmap_icc = icc_chunks(gainmap);
+    let (status, message) = match (meta.use_base_cg, gainmap_icc.is_empty()) {
+        (true, true) => (CheckStatus::Pass, "gain map uses the base color space"),
+        (true, false) if gainmap_icc == base_icc => (
+            CheckStatus::Pass,
+            "gain map repeats the base ICC profile and uses the base color space",
+        ),
+        (true, false) => (
+            CheckStatus::Warn,
+            "use_base_cg is set but the gain map carries a different ICC profile",
+        ),
+        (false, false) => (
+            CheckStatus::Pass,
+            "gain map describes its color space with an ICC profile",
+        ),
+        (false, true) => (
+            CheckStatus::Warn,
+            "use_base_cg is clear but the gain map has no ICC profile describing its color space",
+        ),
+    };
+    report.push("color-space", status, message);
+}
+
+fn icc_chunks(segments: &[Segment]) -> Vec<&[u8]> {
+    segments
+        .iter()
+        .filter(|s| jpeg::is_icc_segment(s))
+        .map(|s| s.data.as_slice())
+        .collect()
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jpeg::tests::{baseline_sof, jpeg, ultrahdr_like_with};
+
+    #[test]
+    fn plain_jpeg_fails_on_missing_mpf() {
+        let report = check_conformance(&jpeg(&[baseline_sof(8, 8, 1)], &[1, 2]));
+        assert_eq!(report.check("jpeg").unwrap().status, CheckStatus::Pass);
+        assert_eq!(report.check("mpf").unwrap().status, CheckStatus::Fail);
+        assert!(!report.passed());
+        assert_eq!(report.checks.len(), 2);
+    }
+
+    #[test]
+    fn structure_checks_run_before_metadata() {
+        let (stream, _) = ultrahdr_like_with(&[baseline_sof(8, 8, 1)], &[baseline_sof(16, 4, 1)]);
+        let report = check_conformance(&stream);
+        let status = |name| report.check(name).unwrap().status;
+        assert_eq!(status("mpf"), CheckStatus::Pass);
+        assert_eq!(status("mpf-offsets"), CheckStatus::Pass);
+        assert_eq!(status("gainmap-image"), CheckStatus::Warn);
+        assert_eq!(status("metadata-carriage"), CheckStatus::Fail);
+    }
+
+    #[test]
+    fn oversized_gainmap_still_has_its_components_checked() {
+        let (stream, _) = ultrahdr_like_with(&[baseline_sof(8, 8, 1)], &[baseline_sof(16, 4, 2)]);
+        let report = check_conformance(&stream);
+        let check = report.check("gainmap-image").unwrap();
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert!(check.message.contains("2 components"), "{}", check.message);
+    }
+
+    #[test]
+    fn offsets_outside_the_file_fail() {
+        let (mut stream, secondary) = ultrahdr_like_with(&[baseline_sof(8, 8, 1)], &[]);
+        stream.truncate(stream.len() - secondary.len() / 2);
+        let report = check_conformance(&stream);
+        assert_eq!(
+            report.check("mpf-offsets").unwrap().status,
+            CheckStatus::Fail
+        );
+        assert_eq!(report.status(), CheckStatus::Fail);
+    }
+}
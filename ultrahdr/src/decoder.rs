@@ -1,27 +1,173 @@
+use crate::effects;
+use crate::encoder::Encoder;
 use crate::error::{Error, Result, check};
+use crate::exif::EXIF_SIGNATURE;
+use crate::jpeg;
+use crate::metadata;
+use crate::source::{GainMapSource, JpegSource};
 use crate::sys;
-use crate::types::{ColorTransfer, CompressedImage, DecodedPackedView, GainMapMetadata, ImgFormat};
+use crate::types::{
+    AsCompressedImage, ColorGamut, ColorRange, ColorTransfer, CompressedImage, DecodedPacked,
+    DecodedPackedView, EncodedImage, GainMapInfo, GainMapMetadata, ImgFormat, MirrorDirection,
+    OutputIntent, OwnedCompressedImage, ProbeInfo, Rect, bytes_per_pixel,
+};
+use std::fs;
+use std::mem::ManuallyDrop;
+use std::path::Path;
 use std::ptr::NonNull;
 
 /// UltraHDR JPEG decoder. Owns the underlying `uhdr_codec_private_t` and provides
 /// safe access to decoded pixel buffers and gain-map metadata.
+///
+/// `Send` but not `Sync`: it can move between threads, but even probing mutates the codec
+/// context (see the crate-level "Concurrency" notes).
 pub struct Decoder {
     raw: NonNull<sys::uhdr_codec_private_t>,
+    /// Color metadata passed with the compressed image in [`set_image`](Self::set_image).
+    input_meta: Option<(ColorGamut, ColorTransfer, ColorRange)>,
+    /// Hand only the base image to libultrahdr, see [`set_skip_gainmap`](Self::set_skip_gainmap).
+    skip_gainmap: bool,
+    /// Rendition to decode, see [`set_output_intent`](Self::set_output_intent).
+    output_intent: OutputIntent,
 }
 
 impl Decoder {
     /// Create a new decoder instance.
+    ///
+    /// No global initialization is involved; instances are independent of each other.
     pub fn new() -> Result<Self> {
         let ptr = unsafe { sys::uhdr_create_decoder() };
         NonNull::new(ptr)
-            .map(|raw| Decoder { raw })
+            .map(|raw| Decoder {
+                raw,
+                input_meta: None,
+                skip_gainmap: false,
+                output_intent: OutputIntent::Hdr,
+            })
             .ok_or_else(Error::alloc)
     }
 
+    /// Read the file at `path` and return a decoder with it set as the image.
+    ///
+    /// libultrahdr copies the compressed stream into the codec context, so the file's bytes
+    /// are dropped before this returns. The color metadata is left unspecified. A read failure is reported as
+    /// `UHDR_CODEC_UNKNOWN_ERROR` naming the path.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let data = fs::read(path).map_err(|err| {
+            Error::io_context(format_args!("cannot read {}", path.display()), err)
+        })?;
+        let mut img = OwnedCompressedImage::new(
+            data,
+            sys::uhdr_color_gamut::UHDR_CG_UNSPECIFIED,
+            sys::uhdr_color_transfer::UHDR_CT_UNSPECIFIED,
+            sys::uhdr_color_range::UHDR_CR_UNSPECIFIED,
+        )?;
+        let mut dec = Decoder::new()?;
+        dec.set_image(&mut img)?;
+        Ok(dec)
+    }
+
+    /// Take ownership of a decoder handle created elsewhere (e.g. by C code in the same
+    /// process).
+    ///
+    /// The returned `Decoder` calls `uhdr_release_decoder` on drop; use
+    /// [`into_raw`](Self::into_raw) to hand the handle back instead. The wrapper does not
+    /// know about an image set through the C API, so methods that need its description
+    /// (e.g. [`probe_info`](Self::probe_info)) report "no image set" until
+    /// [`set_image`](Self::set_image) is called.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ptr` is null.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must come from `uhdr_create_decoder`, must not have been released, and nothing
+    /// else may use or release it while the returned `Decoder` owns it. As the wrapper is
+    /// `Send`, GPU acceleration must not be enabled on it (its EGL context is thread-bound).
+    pub unsafe fn from_raw(ptr: *mut sys::uhdr_codec_private_t) -> Self {
+        Decoder {
+            raw: NonNull::new(ptr).expect("null decoder handle"),
+            input_meta: None,
+            skip_gainmap: false,
+            output_intent: OutputIntent::Hdr,
+        }
+    }
+
+    /// Give up ownership of the underlying handle without releasing it.
+    ///
+    /// The caller becomes responsible for calling `uhdr_release_decoder` exactly once (or
+    /// for passing the pointer back to [`from_raw`](Self::from_raw)).
+    pub fn into_raw(self) -> *mut sys::uhdr_codec_private_t {
+        ManuallyDrop::new(self).raw.as_ptr()
+    }
+
     /// Provide the compressed image to decode.
-    pub fn set_image(&mut self, img: &mut CompressedImage<'_>) -> Result<()> {
-        let err = unsafe { sys::uhdr_dec_set_image(self.raw.as_ptr(), img.as_mut_ptr()) };
-        check(err)
+    ///
+    /// Accepts a borrowed [`CompressedImage`] or an
+    /// [`OwnedCompressedImage`](crate::OwnedCompressedImage). With
+    /// [`set_skip_gainmap`](Self::set_skip_gainmap) enabled only the base image is passed on.
+    /// libultrahdr copies the stream, so `img` may be dropped or reused once this returns.
+    pub fn set_image(&mut self, img: &mut impl AsCompressedImage) -> Result<()> {
+        let mut img = img.as_compressed_image();
+        let (cg, ct, range) = (img.inner.cg, img.inner.ct, img.inner.range);
+        let err = if self.skip_gainmap {
+            // The stripped copy only has to live through the call: libultrahdr copies it.
+            let mut base = JpegSource::new(img.as_slice()).base_image()?;
+            let mut base = CompressedImage::from_bytes(&mut base.data, cg, ct, range)?;
+            unsafe { sys::uhdr_dec_set_image(self.raw.as_ptr(), base.as_mut_ptr()) }
+        } else {
+            unsafe { sys::uhdr_dec_set_image(self.raw.as_ptr(), img.as_mut_ptr()) }
+        };
+        check(err)?;
+        self.input_meta = Some((cg, ct, range));
+        Ok(())
+    }
+
+    /// Decode only the base (SDR) rendition of subsequently set images.
+    ///
+    /// Takes effect at the next [`set_image`](Self::set_image) or
+    /// [`set_source`](Self::set_source): the decoder is then handed the primary JPEG with
+    /// its MPF index and gain-map metadata removed, so libultrahdr never parses, decodes or
+    /// applies the gain map. Useful for thumbnailing, where the gain-map decode and the
+    /// per-pixel HDR reconstruction are wasted work. Decodes then behave as for a plain JPEG:
+    /// gain-map queries report none and [`decode_native`](Self::decode_native) yields 8-bit
+    /// sRGB. The stripped copy costs one pass over the input bytes.
+    pub fn set_skip_gainmap(&mut self, skip: bool) {
+        self.skip_gainmap = skip;
+    }
+
+    /// Choose between the HDR rendition and the untouched SDR base image at decode time.
+    ///
+    /// With [`OutputIntent::SdrBase`] every decode yields the base image as 8-bit sRGB
+    /// RGBA8888: libultrahdr returns the SDR intent as-is for sRGB output and never applies
+    /// the gain map. Unlike [`set_skip_gainmap`](Self::set_skip_gainmap) this works on an
+    /// image that is already set, and gain-map queries keep reporting the map. Requesting
+    /// any other format or transfer (e.g. through
+    /// [`decode_packed_view`](Self::decode_packed_view)) fails with
+    /// `UHDR_CODEC_INVALID_PARAM` while the intent is `SdrBase`.
+    pub fn set_output_intent(&mut self, intent: OutputIntent) {
+        self.output_intent = intent;
+    }
+
+    /// Provide the image to decode as separate parts from any container.
+    ///
+    /// libultrahdr only parses UltraHDR JPEG streams, so a source with a gain map is first
+    /// assembled into one (no recompression; see
+    /// [`Encoder::set_gainmap_source`](crate::Encoder::set_gainmap_source)). A source
+    /// without a gain map decodes its base image directly.
+    pub fn set_source(&mut self, src: &impl GainMapSource) -> Result<()> {
+        let mut stream = if !self.skip_gainmap && src.gainmap_image()?.is_some() {
+            let mut enc = Encoder::new()?;
+            enc.set_gainmap_source(src)?;
+            enc.encode_to_owned()?
+        } else {
+            src.base_image()?
+        };
+        let mut comp =
+            CompressedImage::from_bytes(&mut stream.data, stream.cg, stream.ct, stream.range)?;
+        self.set_image(&mut comp)
     }
 
     /// Choose the packed pixel layout for the decoded output.
@@ -37,17 +183,108 @@ impl Decoder {
     }
 
     /// Clamp the maximum display boost applied by the decoder when reconstructing HDR.
+    ///
+    /// `boost` is the target display's peak as a multiple of SDR white (e.g. about 4.9 for a
+    /// 1000-nit panel, see [`SDR_WHITE_NITS`](crate::SDR_WHITE_NITS)). This is the only
+    /// boost control libultrahdr's decoder has: the content boost range is fixed by the
+    /// gain-map metadata and can be read, not overridden, through
+    /// [`gainmap_metadata`](Self::gainmap_metadata) (`min/max_content_boost`). The applied
+    /// gain is limited by whichever of the two is lower.
+    ///
+    /// Fails with `UHDR_CODEC_INVALID_PARAM` unless `boost` is finite and `>= 1.0`; lower
+    /// values would darken the output below the SDR rendition.
     pub fn set_out_max_display_boost(&mut self, boost: f32) -> Result<()> {
+        check_display_boost(boost)?;
         let err = unsafe { sys::uhdr_dec_set_out_max_display_boost(self.raw.as_ptr(), boost) };
         check(err)
     }
 
+    /// Mirror the decoded image along `direction` after decoding.
+    ///
+    /// Effects are applied in the order they are added, on the CPU unless GPU acceleration
+    /// is available (`gles` feature).
+    pub fn add_effect_mirror(&mut self, direction: MirrorDirection) -> Result<()> {
+        effects::mirror(self.raw, direction)
+    }
+
+    /// Rotate the decoded image clockwise by 90, 180 or 270 degrees after decoding.
+    pub fn add_effect_rotate(&mut self, degrees: u32) -> Result<()> {
+        effects::rotate(self.raw, degrees)
+    }
+
+    /// Crop the decoded image to the pixel columns `left..right` and rows `top..bottom`
+    /// after decoding.
+    pub fn add_effect_crop(&mut self, left: u32, right: u32, top: u32, bottom: u32) -> Result<()> {
+        effects::crop(self.raw, left, right, top, bottom)
+    }
+
+    /// Resize the decoded image to `width`×`height` after decoding.
+    pub fn add_effect_resize(&mut self, width: u32, height: u32) -> Result<()> {
+        effects::resize(self.raw, width, height)
+    }
+
     /// Parse the JPEG headers and any embedded gain map without decoding pixels.
     pub fn probe(&mut self) -> Result<()> {
         let err = unsafe { sys::uhdr_dec_probe(self.raw.as_ptr()) };
         check(err)
     }
 
+    /// Probe the headers and return everything known before decoding in one struct.
+    ///
+    /// Unlike [`probe`](Self::probe) followed by individual getters, the result is a
+    /// self-contained snapshot. `probe` remains for callers that only need the side effect.
+    /// Nothing is decoded, so this is cheap enough to size buffers or pick a downscale
+    /// factor up front. Fails if no image was set.
+    pub fn probe_info(&mut self) -> Result<ProbeInfo> {
+        let (cg, ct, range) = self
+            .input_meta
+            .ok_or_else(|| Error::invalid_param("no image set"))?;
+        let gainmap = self.gainmap_info()?;
+        let (width, height) = self.image_dimensions()?;
+        let (fmt, _) = native_layout(self.output_intent, gainmap.is_some());
+        Ok(ProbeInfo {
+            width,
+            height,
+            fmt,
+            cg,
+            ct,
+            range,
+            gainmap,
+        })
+    }
+
+    /// Probe and return the gain map's width and height, or `None` without a gain map.
+    ///
+    /// Compare with the base dimensions (or use [`ProbeInfo::gainmap_scale_factor`]) to
+    /// recover the downscale factor the image was encoded with.
+    pub fn gainmap_dimensions(&mut self) -> Result<Option<(u32, u32)>> {
+        Ok(self.gainmap_info()?.map(|info| (info.width, info.height)))
+    }
+
+    /// Estimate the memory a decode to `fmt` needs, without decoding.
+    ///
+    /// Probes the headers and returns `width * height * bytes_per_pixel(fmt)` for the output,
+    /// plus 4 bytes per gain-map pixel when the image has
+    /// one. libultrahdr's own scratch buffers are not included. Fails for formats without a
+    /// packed layout and when the size does not fit in a `u64`, so callers never attempt
+    /// the allocation.
+    pub fn estimated_decode_bytes(&mut self, fmt: ImgFormat) -> Result<u64> {
+        let bpp = bytes_per_pixel(fmt)?;
+        let gainmap = self.gainmap_dimensions()?;
+        let (width, height) = self.image_dimensions()?;
+        decode_budget(width, height, bpp, gainmap)
+    }
+
+    /// Base image dimensions; requires a successful probe.
+    fn image_dimensions(&mut self) -> Result<(u32, u32)> {
+        let width = unsafe { sys::uhdr_dec_get_image_width(self.raw.as_ptr()) };
+        let height = unsafe { sys::uhdr_dec_get_image_height(self.raw.as_ptr()) };
+        match (u32::try_from(width), u32::try_from(height)) {
+            (Ok(width), Ok(height)) => Ok((width, height)),
+            _ => Err(Error::invalid_param("image dimensions unavailable")),
+        }
+    }
+
     /// Read gain map metadata (if present). Requires a previously set image.
     pub fn gainmap_metadata(&mut self) -> Result<Option<GainMapMetadata>> {
         self.probe()?;
@@ -56,11 +293,117 @@ impl Decoder {
             return Ok(None);
         }
         // SAFETY: pointer owned by decoder; copied into owned struct.
-        Ok(Some(GainMapMetadata::from_sys(unsafe { &*ptr })))
+        let mut meta = GainMapMetadata::from_sys(unsafe { &*ptr });
+        // The C struct has no base-rendition field, so read it from the gain map's own
+        // metadata segments.
+        let block = unsafe { sys::uhdr_dec_get_gainmap_image(self.raw.as_ptr()) };
+        if let Some(gainmap) = unsafe { mem_block_bytes(block) } {
+            meta.base_rendition_is_hdr = metadata::base_rendition_is_hdr(gainmap);
+        }
+        Ok(Some(meta))
+    }
+
+    /// Probe once and return gain-map metadata, dimensions and compressed size together.
+    ///
+    /// Returns `Ok(None)` for images without a gain map.
+    pub fn gainmap_info(&mut self) -> Result<Option<GainMapInfo>> {
+        let Some(metadata) = self.gainmap_metadata()? else {
+            return Ok(None);
+        };
+        let width = unsafe { sys::uhdr_dec_get_gainmap_width(self.raw.as_ptr()) };
+        let height = unsafe { sys::uhdr_dec_get_gainmap_height(self.raw.as_ptr()) };
+        let (Ok(width), Ok(height)) = (u32::try_from(width), u32::try_from(height)) else {
+            return Err(Error::invalid_param("gain map dimensions unavailable"));
+        };
+        let block = unsafe { sys::uhdr_dec_get_gainmap_image(self.raw.as_ptr()) };
+        if block.is_null() {
+            return Err(Error::invalid_param("gain map image unavailable"));
+        }
+        // SAFETY: pointer owned by decoder and valid until the next probe/reset.
+        let byte_len = unsafe { (*block).data_sz };
+        Ok(Some(GainMapInfo {
+            metadata,
+            width,
+            height,
+            byte_len,
+        }))
+    }
+
+    /// Copy out the compressed gain-map image that libultrahdr located in the input.
+    ///
+    /// This is the secondary JPEG found through the MPF index, byte for byte, including its
+    /// own metadata segments. libultrahdr does not report color metadata for it, so `cg` and
+    /// `ct` are unspecified and `range` is full range, as for any JPEG. Probes the headers
+    /// first (a no-op once probed) and returns `Ok(None)` for images without a gain map.
+    pub fn gainmap_image(&mut self) -> Result<Option<EncodedImage>> {
+        if self.input_meta.is_none() {
+            return Err(Error::invalid_param("no image set"));
+        }
+        if self.gainmap_metadata()?.is_none() {
+            return Ok(None);
+        }
+        // SAFETY: block owned by decoder and valid until the next probe/reset; copied out.
+        let data = unsafe { mem_block_bytes(sys::uhdr_dec_get_gainmap_image(self.raw.as_ptr())) }
+            .ok_or_else(|| Error::invalid_param("gain map image unavailable"))?
+            .to_vec();
+        Ok(Some(EncodedImage {
+            data,
+            cg: sys::uhdr_color_gamut::UHDR_CG_UNSPECIFIED,
+            ct: sys::uhdr_color_transfer::UHDR_CT_UNSPECIFIED,
+            range: sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+        }))
+    }
+
+    /// Copy out the ICC profile of the base image, if it carries one.
+    ///
+    /// This is the profile libultrahdr extracted while probing, returned as a plain ICC blob
+    /// ready for a color management library: the APP2 `ICC_PROFILE` chunk header is removed.
+    /// Requires a previously set image; probes the headers first.
+    pub fn icc_profile(&mut self) -> Result<Option<Vec<u8>>> {
+        if self.input_meta.is_none() {
+            return Err(Error::invalid_param("no image set"));
+        }
+        self.probe()?;
+        // SAFETY: block owned by decoder and valid until the next probe/reset; copied out.
+        let Some(bytes) = (unsafe { mem_block_bytes(sys::uhdr_dec_get_icc(self.raw.as_ptr())) })
+        else {
+            return Ok(None);
+        };
+        let profile = bytes
+            .strip_prefix(jpeg::ICC_SIGNATURE)
+            // Skip the chunk sequence number and chunk count.
+            .map_or(bytes, |rest| rest.get(2..).unwrap_or_default());
+        Ok((!profile.is_empty()).then(|| profile.to_vec()))
+    }
+
+    /// Copy out the EXIF block of the base image, if it carries one.
+    ///
+    /// The `Exif\0\0` identifier of the APP1 segment is removed, so the bytes start with the
+    /// TIFF header, which is what EXIF parsers such as `kamadak-exif`'s `Reader::read_raw`
+    /// expect. Requires a previously set image; probes the headers first.
+    pub fn exif(&mut self) -> Result<Option<Vec<u8>>> {
+        if self.input_meta.is_none() {
+            return Err(Error::invalid_param("no image set"));
+        }
+        self.probe()?;
+        // SAFETY: block owned by decoder and valid until the next probe/reset; copied out.
+        let Some(bytes) = (unsafe { mem_block_bytes(sys::uhdr_dec_get_exif(self.raw.as_ptr())) })
+        else {
+            return Ok(None);
+        };
+        let tiff = bytes.strip_prefix(EXIF_SIGNATURE).unwrap_or(bytes);
+        Ok((!tiff.is_empty()).then(|| tiff.to_vec()))
     }
 
     /// Decode the current image using the configured output format/transfer.
+    ///
+    /// With [`OutputIntent::SdrBase`] the output is forced to RGBA8888/sRGB first.
     pub fn decode(&mut self) -> Result<()> {
+        if self.output_intent == OutputIntent::SdrBase {
+            let (fmt, ct) = SDR_BASE_OUTPUT;
+            self.set_out_img_format(fmt)?;
+            self.set_out_color_transfer(ct)?;
+        }
         let err = unsafe { sys::uhdr_decode(self.raw.as_ptr()) };
         check(err)
     }
@@ -71,6 +414,7 @@ impl Decoder {
         fmt: ImgFormat,
         ct: ColorTransfer,
     ) -> Result<DecodedPackedView<'_>> {
+        check_output_intent(self.output_intent, fmt, ct)?;
         self.set_out_img_format(fmt)?;
         self.set_out_color_transfer(ct)?;
         self.decode()?;
@@ -80,6 +424,132 @@ impl Decoder {
         DecodedPackedView::new(raw)
     }
 
+    /// Decode and hand the packed pixels to `on_row` one row at a time, top to bottom.
+    ///
+    /// libultrahdr has no scanline API and always decodes the whole frame into its own
+    /// buffer. Rows are passed straight out of that buffer, without stride padding, so no
+    /// second frame-sized copy is ever made; peak memory is the decoder's frame alone. The
+    /// first error returned by `on_row` stops the iteration and is propagated.
+    pub fn decode_streaming(
+        &mut self,
+        fmt: ImgFormat,
+        ct: ColorTransfer,
+        mut on_row: impl FnMut(usize, &[u8]) -> Result<()>,
+    ) -> Result<()> {
+        let view = self.decode_packed_view(fmt, ct)?;
+        for (y, row) in view.rows().enumerate() {
+            on_row(y, row?)?;
+        }
+        Ok(())
+    }
+
+    /// Decode and copy the pixels out, releasing the borrow on the decoder.
+    ///
+    /// Equivalent to [`decode_packed_view`](Self::decode_packed_view) followed by
+    /// [`DecodedPackedView::to_owned`]; convenient when collecting results across files.
+    pub fn decode_to_owned(&mut self, fmt: ImgFormat, ct: ColorTransfer) -> Result<DecodedPacked> {
+        self.decode_packed_view(fmt, ct)?.to_owned()
+    }
+
+    /// Decode and borrow the gain-map pixels themselves.
+    ///
+    /// The view is `8bppYCbCr400` for a single-channel map or RGBA8888 for a multi-channel
+    /// one, at gain-map resolution. libultrahdr always reconstructs the gain map while
+    /// decoding, so this runs the regular decode with the configured output settings.
+    /// Fails when the image has no gain map.
+    pub fn decode_gainmap_view(&mut self) -> Result<DecodedPackedView<'_>> {
+        if self.gainmap_metadata()?.is_none() {
+            return Err(Error::invalid_param("image has no gain map"));
+        }
+        self.decode()?;
+        let ptr = unsafe { sys::uhdr_get_decoded_gainmap_image(self.raw.as_ptr()) };
+        if ptr.is_null() {
+            return Err(Error::invalid_param("decoded gain map is null"));
+        }
+        // SAFETY: pointer owned by decoder and valid until the next decode/reset.
+        DecodedPackedView::new(unsafe { &mut *ptr })
+    }
+
+    /// Decode and return the gain map with each channel in its own buffer.
+    ///
+    /// Every returned image is `8bppYCbCr400` at gain-map resolution; the length of the
+    /// vector is the channel count (1, or 3 for a multi-channel map). Fails when the image
+    /// has no gain map.
+    pub fn decode_gainmap_channels(&mut self) -> Result<Vec<DecodedPacked>> {
+        self.decode_gainmap_view()?
+            .to_owned()?
+            .split_gainmap_channels()
+    }
+
+    /// Decode and return only the pixels inside `rect`.
+    ///
+    /// The rectangle is validated against the probed image dimensions first. libultrahdr
+    /// has no partial JPEG decode (its crop effect runs after a full decode), so this
+    /// decodes the whole frame and copies out the region; the saving is in the returned
+    /// buffer, not in decode time or peak memory.
+    pub fn decode_region(
+        &mut self,
+        rect: Rect,
+        fmt: ImgFormat,
+        ct: ColorTransfer,
+    ) -> Result<DecodedPacked> {
+        self.probe()?;
+        let (width, height) = self.image_dimensions()?;
+        if !rect.fits_within(width, height) {
+            return Err(Error::invalid_param("region outside image bounds"));
+        }
+        self.decode_packed_view(fmt, ct)?.crop_to_owned(rect)
+    }
+
+    /// Suggest an output layout and transfer for the current image.
+    ///
+    /// The mapping is:
+    /// - no gain map: the 8-bit sRGB base is all there is, so `(RGBA8888, sRGB)`;
+    /// - gain map present: `(RGBA1010102, PQ)`, which holds the full HDR rendition in
+    ///   4 bytes per pixel and is ready for display or HDR encoders;
+    /// - gain map present and the image was set with an HLG transfer: `(RGBA1010102, HLG)`,
+    ///   so broadcast-style content stays in its own transfer.
+    ///
+    /// With [`OutputIntent::SdrBase`] it is always `(RGBA8888, sRGB)`.
+    ///
+    /// Use [`decode_native`](Self::decode_native) for linear half-float processing instead.
+    pub fn recommended_output(&mut self) -> Result<(ImgFormat, ColorTransfer)> {
+        let has_gainmap =
+            self.output_intent != OutputIntent::SdrBase && self.gainmap_metadata()?.is_some();
+        let input_ct = self.input_meta.map(|(_, ct, _)| ct);
+        Ok(recommended_layout(
+            self.output_intent,
+            has_gainmap,
+            input_ct,
+        ))
+    }
+
+    /// Decode into the layout libultrahdr produces without an extra transfer conversion.
+    ///
+    /// Plain JPEGs decode to 8-bit sRGB RGBA8888 (the base image as stored). Images with a
+    /// gain map decode to linear RGBA half-float, the domain the gain map is applied in.
+    /// With [`OutputIntent::SdrBase`] the base image is returned as 8-bit sRGB. Check
+    /// [`DecodedPackedView::fmt`] for the chosen layout.
+    pub fn decode_native(&mut self) -> Result<DecodedPackedView<'_>> {
+        let has_gainmap =
+            self.output_intent != OutputIntent::SdrBase && self.gainmap_metadata()?.is_some();
+        let (fmt, ct) = native_layout(self.output_intent, has_gainmap);
+        self.decode_packed_view(fmt, ct)
+    }
+
+    /// Reset all state so the decoder can be reused for another image.
+    ///
+    /// Drops the set image, decoded buffers and output settings in the codec context, and
+    /// clears [`set_skip_gainmap`](Self::set_skip_gainmap) and
+    /// [`set_output_intent`](Self::set_output_intent); afterwards the decoder behaves like
+    /// one fresh from [`new`](Self::new) without reallocating the handle.
+    pub fn reset(&mut self) {
+        self.input_meta = None;
+        self.skip_gainmap = false;
+        self.output_intent = OutputIntent::Hdr;
+        unsafe { sys::uhdr_reset_decoder(self.raw.as_ptr()) }
+    }
+
     /// Borrow the decoded image owned by the decoder; remains valid until decoder is dropped/reset.
     pub(crate) fn decoded_image(&mut self) -> Option<&mut sys::uhdr_raw_image> {
         let ptr = unsafe { sys::uhdr_get_decoded_image(self.raw.as_ptr()) };
@@ -91,8 +561,182 @@ impl Decoder {
     }
 }
 
+/// Borrow the bytes of a decoder-owned memory block; `None` when absent or empty.
+///
+/// # Safety
+/// `block` must be null or point to a block whose data stays valid for `'a`.
+unsafe fn mem_block_bytes<'a>(block: *const sys::uhdr_mem_block) -> Option<&'a [u8]> {
+    let block = unsafe { block.as_ref()? };
+    if block.data.is_null() || block.data_sz == 0 {
+        return None;
+    }
+    Some(unsafe { std::slice::from_raw_parts(block.data as *const u8, block.data_sz) })
+}
+
+// SAFETY: the codec context has no thread affinity, see the crate-level "Concurrency" notes.
+unsafe impl Send for Decoder {}
+
 impl Drop for Decoder {
     fn drop(&mut self) {
         unsafe { sys::uhdr_release_decoder(self.raw.as_ptr()) }
     }
 }
+
+fn check_display_boost(boost: f32) -> Result<()> {
+    if !(boost.is_finite() && boost >= 1.0) {
+        return Err(Error::invalid_param(
+            "max display boost must be finite and >= 1.0",
+        ));
+    }
+    Ok(())
+}
+
+/// Upper bound on the decoded gain map's size per pixel (RGBA8888 for a multi-channel map).
+const GAINMAP_DECODE_BYTES_PER_PIXEL: u64 = 4;
+
+fn decode_budget(width: u32, height: u32, bpp: usize, gainmap: Option<(u32, u32)>) -> Result<u64> {
+    let overflow = || Error::invalid_param("decode size overflows u64");
+    let output = u64::from(width)
+        .checked_mul(u64::from(height))
+        .and_then(|px| px.checked_mul(bpp as u64))
+        .ok_or_else(overflow)?;
+    let gainmap = match gainmap {
+        Some((w, h)) => u64::from(w)
+            .checked_mul(u64::from(h))
+            .and_then(|px| px.checked_mul(GAINMAP_DECODE_BYTES_PER_PIXEL))
+            .ok_or_else(overflow)?,
+        None => 0,
+    };
+    output.checked_add(gainmap).ok_or_else(overflow)
+}
+
+/// Output settings for which libultrahdr returns the base image without the gain map.
+const SDR_BASE_OUTPUT: (ImgFormat, ColorTransfer) = (
+    sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA8888,
+    sys::uhdr_color_transfer::UHDR_CT_SRGB,
+);
+
+/// Layout produced by [`Decoder::decode_native`] and reported by [`Decoder::probe_info`].
+fn native_layout(intent: OutputIntent, has_gainmap: bool) -> (ImgFormat, ColorTransfer) {
+    if intent == OutputIntent::SdrBase || !has_gainmap {
+        return SDR_BASE_OUTPUT;
+    }
+    (
+        sys::uhdr_img_fmt::UHDR_IMG_FMT_64bppRGBAHalfFloat,
+        sys::uhdr_color_transfer::UHDR_CT_LINEAR,
+    )
+}
+
+/// Layout picked by [`Decoder::recommended_output`].
+fn recommended_layout(
+    intent: OutputIntent,
+    has_gainmap: bool,
+    input_ct: Option<ColorTransfer>,
+) -> (ImgFormat, ColorTransfer) {
+    if intent == OutputIntent::SdrBase || !has_gainmap {
+        return SDR_BASE_OUTPUT;
+    }
+    let ct = match input_ct {
+        Some(sys::uhdr_color_transfer::UHDR_CT_HLG) => sys::uhdr_color_transfer::UHDR_CT_HLG,
+        _ => sys::uhdr_color_transfer::UHDR_CT_PQ,
+    };
+    (sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA1010102, ct)
+}
+
+fn check_output_intent(intent: OutputIntent, fmt: ImgFormat, ct: ColorTransfer) -> Result<()> {
+    if intent == OutputIntent::SdrBase && (fmt, ct) != SDR_BASE_OUTPUT {
+        return Err(Error::invalid_param(
+            "SDR base intent only decodes to RGBA8888 with sRGB transfer",
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reset_returns_to_fresh_state() {
+        let mut dec = Decoder::new().unwrap();
+        dec.set_skip_gainmap(true);
+        dec.set_output_intent(OutputIntent::SdrBase);
+        dec.input_meta = Some((
+            sys::uhdr_color_gamut::UHDR_CG_BT_709,
+            sys::uhdr_color_transfer::UHDR_CT_SRGB,
+            sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+        ));
+        dec.reset();
+        assert!(!dec.skip_gainmap);
+        assert_eq!(dec.output_intent, OutputIntent::Hdr);
+        let err = dec.probe_info().unwrap_err();
+        assert!(err.to_string().contains("no image set"), "{err}");
+    }
+
+    #[test]
+    fn open_reports_unreadable_path() {
+        let err = Decoder::open("does/not/exist.jpg").err().unwrap();
+        assert_eq!(err.code, sys::uhdr_codec_err_t::UHDR_CODEC_UNKNOWN_ERROR);
+        assert!(err.to_string().contains("does/not/exist.jpg"), "{err}");
+    }
+
+    #[test]
+    fn display_boost_below_one_is_rejected() {
+        for boost in [0.0, 0.99, -1.0, f32::NAN, f32::INFINITY] {
+            let err = check_display_boost(boost).unwrap_err();
+            assert_eq!(err.code, sys::uhdr_codec_err_t::UHDR_CODEC_INVALID_PARAM);
+        }
+        check_display_boost(1.0).unwrap();
+        check_display_boost(4.9).unwrap();
+    }
+
+    #[test]
+    fn sdr_base_intent_only_allows_8bit_srgb() {
+        let (fmt, ct) = SDR_BASE_OUTPUT;
+        check_output_intent(OutputIntent::SdrBase, fmt, ct).unwrap();
+        let pq = sys::uhdr_color_transfer::UHDR_CT_PQ;
+        assert!(check_output_intent(OutputIntent::SdrBase, fmt, pq).is_err());
+        let half = sys::uhdr_img_fmt::UHDR_IMG_FMT_64bppRGBAHalfFloat;
+        assert!(check_output_intent(OutputIntent::SdrBase, half, ct).is_err());
+        check_output_intent(OutputIntent::Hdr, half, pq).unwrap();
+    }
+
+    #[test]
+    fn recommended_layout_keeps_hlg_and_defaults_to_pq() {
+        let hdr = OutputIntent::Hdr;
+        let (hlg, pq) = (
+            sys::uhdr_color_transfer::UHDR_CT_HLG,
+            sys::uhdr_color_transfer::UHDR_CT_PQ,
+        );
+        let packed = sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA1010102;
+        assert_eq!(recommended_layout(hdr, true, Some(hlg)), (packed, hlg));
+        assert_eq!(recommended_layout(hdr, true, Some(pq)), (packed, pq));
+        assert_eq!(recommended_layout(hdr, true, None), (packed, pq));
+        assert_eq!(recommended_layout(hdr, false, Some(hlg)), SDR_BASE_OUTPUT);
+        assert_eq!(
+            recommended_layout(OutputIntent::SdrBase, true, Some(hlg)),
+            SDR_BASE_OUTPUT
+        );
+    }
+
+    #[test]
+    fn native_layout_is_linear_half_float_only_for_hdr_with_gainmap() {
+        let half = (
+            sys::uhdr_img_fmt::UHDR_IMG_FMT_64bppRGBAHalfFloat,
+            sys::uhdr_color_transfer::UHDR_CT_LINEAR,
+        );
+        assert_eq!(native_layout(OutputIntent::Hdr, true), half);
+        assert_eq!(native_layout(OutputIntent::Hdr, false), SDR_BASE_OUTPUT);
+        assert_eq!(native_layout(OutputIntent::SdrBase, true), SDR_BASE_OUTPUT);
+    }
+
+    #[test]
+    fn decode_budget_adds_gainmap_allowance_and_detects_overflow() {
+        assert_eq!(decode_budget(4000, 3000, 8, None).unwrap(), 96_000_000);
+        assert_eq!(
+            decode_budget(4000, 3000, 4, Some((1000, 750))).unwrap(),
+            48_000_000 + 3_000_000
+        );
+        assert!(decode_budget(u32::MAX, u32::MAX, 8, None).is_err());
+    }
+}
@@ -0,0 +1,212 @@
+//! Self-describing raw pixel dumps of [`DecodedPacked`] images.
+
+use crate::sys;
+use crate::types::{
+    ColorGamut, ColorRange, ColorTransfer, DecodedPacked, ImgFormat, bytes_per_pixel,
+};
+use std::io::{self, Read, Write};
+
+/// Leading bytes of a dump written by [`DecodedPacked::write_with_header`].
+pub const RAW_DUMP_MAGIC: [u8; 8] = *b"UHDRRAW\0";
+
+/// Size of the dump header in bytes.
+pub const RAW_DUMP_HEADER_LEN: usize = 36;
+
+impl DecodedPacked {
+    /// Write a self-describing dump: a 36-byte header, then the pixel rows.
+    ///
+    /// Header fields are little-endian:
+    ///
+    /// | offset | size | field                                                   |
+    /// |--------|------|---------------------------------------------------------|
+    /// | 0      | 8    | magic `UHDRRAW\0`                                       |
+    /// | 8      | 4    | pixel format (`uhdr_img_fmt` value, i32)                |
+    /// | 12     | 4    | width in pixels (u32)                                   |
+    /// | 16     | 4    | height in pixels (u32)                                  |
+    /// | 20     | 4    | row stride in bytes (u32)                               |
+    /// | 24     | 4    | color gamut (`uhdr_color_gamut` value, i32)             |
+    /// | 28     | 4    | transfer function (`uhdr_color_transfer` value, i32)    |
+    /// | 32     | 4    | color range (`uhdr_color_range` value, i32)             |
+    ///
+    /// `height` rows of `stride` bytes follow; only the first `width * bytes_per_pixel` bytes
+    /// of each row are pixels.
+    ///
+    /// Rows are written tightly packed, so the stride equals the row size. Use
+    /// `w.write_all(&self.data)` for a headerless dump.
+    pub fn write_with_header<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let stride = bytes_per_pixel(self.fmt)
+            .ok()
+            .and_then(|bpp| (self.width as usize).checked_mul(bpp))
+            .and_then(|stride| u32::try_from(stride).ok())
+            .ok_or_else(|| invalid("unsupported format or oversized rows"))?;
+        let mut header = Vec::with_capacity(RAW_DUMP_HEADER_LEN);
+        header.extend_from_slice(&RAW_DUMP_MAGIC);
+        header.extend_from_slice(&(self.fmt as i32).to_le_bytes());
+        header.extend_from_slice(&self.width.to_le_bytes());
+        header.extend_from_slice(&self.height.to_le_bytes());
+        header.extend_from_slice(&stride.to_le_bytes());
+        header.extend_from_slice(&(self.cg as i32).to_le_bytes());
+        header.extend_from_slice(&(self.ct as i32).to_le_bytes());
+        header.extend_from_slice(&(self.range as i32).to_le_bytes());
+        w.write_all(&header)?;
+        w.write_all(&self.data)
+    }
+
+    /// Read a dump produced by [`write_with_header`](Self::write_with_header).
+    ///
+    /// Row padding beyond the pixels is dropped. Fails with
+    /// [`io::ErrorKind::InvalidData`] for a bad magic, an unknown enum value, a format
+    /// other than the packed ones, or a stride shorter than a row.
+    pub fn read_with_header<R: Read>(r: &mut R) -> io::Result<DecodedPacked> {
+        let mut header = [0u8; RAW_DUMP_HEADER_LEN];
+        r.read_exact(&mut header)?;
+        if header[..8] != RAW_DUMP_MAGIC {
+            return Err(invalid("not a raw dump (bad magic)"));
+        }
+        let field = |i: usize| {
+            let at = 8 + 4 * i;
+            [header[at], header[at + 1], header[at + 2], header[at + 3]]
+        };
+        let fmt = img_format(i32::from_le_bytes(field(0)))?;
+        let width = u32::from_le_bytes(field(1));
+        let height = u32::from_le_bytes(field(2));
+        let stride = u32::from_le_bytes(field(3)) as usize;
+        let cg = color_gamut(i32::from_le_bytes(field(4)))?;
+        let ct = color_transfer(i32::from_le_bytes(field(5)))?;
+        let range = color_range(i32::from_le_bytes(field(6)))?;
+
+        let bpp = bytes_per_pixel(fmt).map_err(|_| invalid("unsupported pixel format"))?;
+        let row_bytes = (width as usize)
+            .checked_mul(bpp)
+            .ok_or_else(|| invalid("row size overflow"))?;
+        if stride < row_bytes {
+            return Err(invalid("stride smaller than a row"));
+        }
+        let len = row_bytes
+            .checked_mul(height as usize)
+            .ok_or_else(|| invalid("image size overflow"))?;
+        // Grow with the input rather than trusting the header with one large allocation.
+        let mut data = Vec::new();
+        let padding = (stride - row_bytes) as u64;
+        for _ in 0..height {
+            let read = r.by_ref().take(row_bytes as u64).read_to_end(&mut data)?;
+            let skipped = io::copy(&mut r.by_ref().take(padding), &mut io::sink())?;
+            if read != row_bytes || skipped != padding {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+        }
+        debug_assert_eq!(data.len(), len);
+        Ok(DecodedPacked {
+            fmt,
+            cg,
+            ct,
+            range,
+            width,
+            height,
+            data,
+        })
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn img_format(v: i32) -> io::Result<ImgFormat> {
+    [
+        sys::uhdr_img_fmt::UHDR_IMG_FMT_8bppYCbCr400,
+        sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA8888,
+        sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA1010102,
+        sys::uhdr_img_fmt::UHDR_IMG_FMT_64bppRGBAHalfFloat,
+    ]
+    .into_iter()
+    .find(|&f| f as i32 == v)
+    .ok_or_else(|| invalid("unsupported pixel format"))
+}
+
+fn color_gamut(v: i32) -> io::Result<ColorGamut> {
+    [
+        sys::uhdr_color_gamut::UHDR_CG_UNSPECIFIED,
+        sys::uhdr_color_gamut::UHDR_CG_BT_709,
+        sys::uhdr_color_gamut::UHDR_CG_DISPLAY_P3,
+        sys::uhdr_color_gamut::UHDR_CG_BT_2100,
+    ]
+    .into_iter()
+    .find(|&cg| cg as i32 == v)
+    .ok_or_else(|| invalid("unknown color gamut"))
+}
+
+fn color_transfer(v: i32) -> io::Result<ColorTransfer> {
+    [
+        sys::uhdr_color_transfer::UHDR_CT_UNSPECIFIED,
+        sys::uhdr_color_transfer::UHDR_CT_LINEAR,
+        sys::uhdr_color_transfer::UHDR_CT_HLG,
+        sys::uhdr_color_transfer::UHDR_CT_PQ,
+        sys::uhdr_color_transfer::UHDR_CT_SRGB,
+    ]
+    .into_iter()
+    .find(|&ct| ct as i32 == v)
+    .ok_or_else(|| invalid("unknown color transfer"))
+}
+
+fn color_range(v: i32) -> io::Result<ColorRange> {
+    [
+        sys::uhdr_color_range::UHDR_CR_UNSPECIFIED,
+        sys::uhdr_color_range::UHDR_CR_LIMITED_RANGE,
+        sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+    ]
+    .into_iter()
+    .find(|&range| range as i32 == v)
+    .ok_or_else(|| invalid("unknown color range"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image() -> DecodedPacked {
+        DecodedPacked {
+            fmt: sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA1010102,
+            cg: sys::uhdr_color_gamut::UHDR_CG_BT_2100,
+            ct: sys::uhdr_color_transfer::UHDR_CT_PQ,
+            range: sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+            width: 3,
+            height: 2,
+            data: (0..24).collect(),
+        }
+    }
+
+    #[test]
+    fn dump_round_trips_through_header() {
+        let img = image();
+        let mut out = Vec::new();
+        img.write_with_header(&mut out).unwrap();
+        assert_eq!(out.len(), RAW_DUMP_HEADER_LEN + img.data.len());
+        assert_eq!(&out[20..24], &12u32.to_le_bytes());
+        let back = DecodedPacked::read_with_header(&mut out.as_slice()).unwrap();
+        assert_eq!(back, img);
+    }
+
+    #[test]
+    fn reader_drops_row_padding_and_rejects_bad_headers() {
+        let img = image();
+        let mut out = Vec::new();
+        img.write_with_header(&mut out).unwrap();
+        let mut padded = out[..RAW_DUMP_HEADER_LEN].to_vec();
+        padded[20..24].copy_from_slice(&16u32.to_le_bytes());
+        for row in img.data.chunks(12) {
+            padded.extend_from_slice(row);
+            padded.extend_from_slice(&[0xEE; 4]);
+        }
+        let back = DecodedPacked::read_with_header(&mut padded.as_slice()).unwrap();
+        assert_eq!(back, img);
+
+        let mut bad = out.clone();
+        bad[0] = b'X';
+        let err = DecodedPacked::read_with_header(&mut bad.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let mut bad = out;
+        bad[20..24].copy_from_slice(&4u32.to_le_bytes());
+        assert!(DecodedPacked::read_with_header(&mut bad.as_slice()).is_err());
+    }
+}
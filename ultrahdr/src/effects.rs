@@ -0,0 +1,53 @@
+//! Image editing effects (`uhdr_add_effect_*`) shared by [`Encoder`](crate::Encoder) and
+//! [`Decoder`](crate::Decoder).
+//!
+//! The effects run on the CPU and are available in every build; the `gles` feature only
+//! adds optional GPU acceleration for them.
+
+use crate::error::{Error, Result, check};
+use crate::sys;
+use crate::types::MirrorDirection;
+use std::ffi::c_int;
+use std::ptr::NonNull;
+
+type Codec = NonNull<sys::uhdr_codec_private_t>;
+
+pub(crate) fn mirror(codec: Codec, direction: MirrorDirection) -> Result<()> {
+    check(unsafe { sys::uhdr_add_effect_mirroring(codec.as_ptr(), direction) })
+}
+
+pub(crate) fn rotate(codec: Codec, degrees: u32) -> Result<()> {
+    if !matches!(degrees, 90 | 180 | 270) {
+        return Err(Error::invalid_param(
+            "rotation must be 90, 180 or 270 degrees",
+        ));
+    }
+    check(unsafe { sys::uhdr_add_effect_rotate(codec.as_ptr(), degrees as c_int) })
+}
+
+pub(crate) fn crop(codec: Codec, left: u32, right: u32, top: u32, bottom: u32) -> Result<()> {
+    if left >= right || top >= bottom {
+        return Err(Error::invalid_param(
+            "crop needs left < right and top < bottom",
+        ));
+    }
+    let (left, right, top, bottom) = (
+        to_c_int(left)?,
+        to_c_int(right)?,
+        to_c_int(top)?,
+        to_c_int(bottom)?,
+    );
+    check(unsafe { sys::uhdr_add_effect_crop(codec.as_ptr(), left, right, top, bottom) })
+}
+
+pub(crate) fn resize(codec: Codec, width: u32, height: u32) -> Result<()> {
+    if width == 0 || height == 0 {
+        return Err(Error::invalid_param("resize dimensions must be non-zero"));
+    }
+    let (width, height) = (to_c_int(width)?, to_c_int(height)?);
+    check(unsafe { sys::uhdr_add_effect_resize(codec.as_ptr(), width, height) })
+}
+
+fn to_c_int(v: u32) -> Result<c_int> {
+    c_int::try_from(v).map_err(|_| Error::invalid_param("effect coordinate out of range"))
+}
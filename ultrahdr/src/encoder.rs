@@ -1,31 +1,174 @@
+use crate::effects;
 use crate::error::{Error, Result, check};
+use crate::exif;
+use crate::gainmap;
+use crate::huffman;
+use crate::icc;
+use crate::jpeg::{self, JpegInfo};
+use crate::metadata;
+use crate::source::GainMapSource;
 use crate::sys;
 use crate::types::{
-    Codec, CompressedImage, DecodedPackedView, EncPreset, EncodedView, ImgLabel, OwnedPackedImage,
-    RawImage,
+    AsCompressedImage, ChromaSubsampling, Codec, ColorGamut, ColorRange, ColorTransfer,
+    CompressedImage, DecodedPacked, DecodedPackedView, EncPreset, EncodedImage, EncodedView,
+    GainMapMetadata, ImgLabel, MirrorDirection, OwnedPackedImage, RawImage, SDR_WHITE_NITS,
+    copy_raw_packed,
 };
+use std::ffi::c_void;
+use std::io::Write;
+use std::mem::{self, ManuallyDrop};
+use std::ops::RangeInclusive;
 use std::ptr::NonNull;
 
+/// libultrahdr's default JPEG quality for gain-map images.
+const DEFAULT_GAINMAP_QUALITY: i32 = 95;
+
+/// libultrahdr's default content boost bounds, meaning "derive from the content".
+const DEFAULT_CONTENT_BOOST: (f32, f32) = (f32::MIN_POSITIVE, f32::MAX);
+
 /// UltraHDR JPEG encoder. Owns the underlying `uhdr_codec_private_t` and can be reused
 /// across multiple encodes by calling [`reset`](Self::reset).
+///
+/// `Send` but not `Sync`: it can move between threads, but every configuration and query
+/// call writes to the codec context (see the crate-level "Concurrency" notes).
 pub struct Encoder {
     raw: NonNull<sys::uhdr_codec_private_t>,
+    /// Labels of the images attached so far, checked before `uhdr_encode`.
+    inputs: Vec<ImgLabel>,
+    app_segments: Vec<(u8, Vec<u8>)>,
+    metadata_formats: Option<(bool, bool)>,
+    deterministic: bool,
+    jpeg_optimize: bool,
+    orientation: Option<u8>,
+    base_icc: Option<Vec<u8>>,
+    gainmap_icc: Option<Vec<u8>>,
+    /// Gain-map quality last passed to [`set_quality`](Self::set_quality).
+    gainmap_quality: i32,
+    gainmap_subsampling: Option<ChromaSubsampling>,
+    /// `(min, max)` content boost last passed to `uhdr_enc_set_min_max_content_boost`.
+    content_boost: (f32, f32),
+    /// `(sdr, hdr)` offsets written into the metadata of a caller-computed gain map.
+    gainmap_offsets: Option<(f32, f32)>,
+    /// Caller-computed gain map, compressed on the Rust side in [`encode`](Self::encode).
+    raw_gainmap: Option<(DecodedPacked, GainMapMetadata)>,
+    /// Renditions for an HDR-base file, encoded on the Rust side in [`encode`](Self::encode).
+    hdr_base: Option<HdrBase>,
+    patched: Option<PatchedStream>,
+    /// Descriptor of the gain-map JPEG inside the current output, filled lazily by
+    /// [`encoded_gainmap`](Self::encoded_gainmap).
+    gainmap_stream: Option<sys::uhdr_compressed_image>,
+}
+
+/// Encoded stream rewritten on the Rust side after `uhdr_encode` (e.g. extra APP segments).
+struct PatchedStream {
+    /// Backing storage for `raw.data`.
+    _data: Vec<u8>,
+    raw: sys::uhdr_compressed_image,
+}
+
+impl PatchedStream {
+    fn new(mut data: Vec<u8>, cg: ColorGamut, ct: ColorTransfer, range: ColorRange) -> Self {
+        let raw = sys::uhdr_compressed_image {
+            data: data.as_mut_ptr() as *mut c_void,
+            data_sz: data.len(),
+            capacity: data.len(),
+            cg,
+            ct,
+            range,
+        };
+        Self { _data: data, raw }
+    }
+}
+
+/// Inputs of [`Encoder::set_hdr_base`].
+struct HdrBase {
+    /// Compressed HDR rendition, written as the primary image.
+    base: Vec<u8>,
+    hdr: DecodedPacked,
+    sdr: DecodedPacked,
 }
 
 impl Encoder {
+    /// Start a chainable configuration; see [`EncoderBuilder`].
+    pub fn builder() -> EncoderBuilder {
+        EncoderBuilder::default()
+    }
+
     /// Create a new encoder instance.
+    ///
+    /// No global initialization is involved; instances are independent of each other.
     pub fn new() -> Result<Self> {
         let ptr = unsafe { sys::uhdr_create_encoder() };
         NonNull::new(ptr)
-            .map(|raw| Encoder { raw })
+            .map(Self::with_handle)
             .ok_or_else(Error::alloc)
     }
 
+    /// Take ownership of an encoder handle created elsewhere (e.g. by C code in the same
+    /// process).
+    ///
+    /// The returned `Encoder` calls `uhdr_release_encoder` on drop; use
+    /// [`into_raw`](Self::into_raw) to hand the handle back instead. Settings already applied
+    /// through the C API are kept, while the wrapper-side options (APP segments, ICC
+    /// profiles, orientation, deterministic/optimized output, ...) start out unset.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ptr` is null.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must come from `uhdr_create_encoder`, must not have been released, and nothing
+    /// else may use or release it while the returned `Encoder` owns it. As the wrapper is
+    /// `Send`, GPU acceleration must not be enabled on it (its EGL context is thread-bound).
+    pub unsafe fn from_raw(ptr: *mut sys::uhdr_codec_private_t) -> Self {
+        Self::with_handle(NonNull::new(ptr).expect("null encoder handle"))
+    }
+
+    /// Give up ownership of the underlying handle without releasing it.
+    ///
+    /// The caller becomes responsible for calling `uhdr_release_encoder` exactly once (or
+    /// for passing the pointer back to [`from_raw`](Self::from_raw)). Wrapper-side options
+    /// and a Rust-side rewritten output stream live outside the C context and are dropped;
+    /// `uhdr_get_encoded_stream` on the handle returns libultrahdr's own output.
+    pub fn into_raw(self) -> *mut sys::uhdr_codec_private_t {
+        let mut this = ManuallyDrop::new(self);
+        this.clear_state();
+        // `ManuallyDrop` skips the field destructors as well; `clear` keeps the capacity.
+        drop(mem::take(&mut this.inputs));
+        drop(mem::take(&mut this.app_segments));
+        this.raw.as_ptr()
+    }
+
+    fn with_handle(raw: NonNull<sys::uhdr_codec_private_t>) -> Self {
+        Encoder {
+            raw,
+            inputs: Vec::new(),
+            app_segments: Vec::new(),
+            metadata_formats: None,
+            deterministic: false,
+            jpeg_optimize: false,
+            orientation: None,
+            base_icc: None,
+            gainmap_icc: None,
+            gainmap_quality: DEFAULT_GAINMAP_QUALITY,
+            gainmap_subsampling: None,
+            content_boost: DEFAULT_CONTENT_BOOST,
+            gainmap_offsets: None,
+            raw_gainmap: None,
+            hdr_base: None,
+            patched: None,
+            gainmap_stream: None,
+        }
+    }
+
     /// Provide a packed raw buffer to use as input.
     pub fn set_raw_image(&mut self, img: &mut RawImage<'_>, intent: ImgLabel) -> Result<()> {
         let err =
             unsafe { sys::uhdr_enc_set_raw_image(self.raw.as_ptr(), img.as_mut_ptr(), intent) };
-        check(err)
+        check(err)?;
+        self.attached(intent);
+        Ok(())
     }
 
     /// Provide a decoded view obtained from a [`Decoder`] as input.
@@ -36,36 +179,329 @@ impl Encoder {
     ) -> Result<()> {
         let err =
             unsafe { sys::uhdr_enc_set_raw_image(self.raw.as_ptr(), img.as_raw_mut(), intent) };
-        check(err)
+        check(err)?;
+        self.attached(intent);
+        Ok(())
     }
 
     /// Provide an owned packed buffer to use as input.
+    ///
+    /// A non-default [`OwnedPackedImage::orientation`] is written to the output as an EXIF
+    /// Orientation tag: an existing tag is updated, otherwise a minimal EXIF block is added.
+    /// An image with the default orientation keeps the one already set, and one whose
+    /// orientation conflicts with an earlier non-default value is rejected.
     pub fn set_raw_owned_image(
         &mut self,
         img: &mut OwnedPackedImage,
         intent: ImgLabel,
     ) -> Result<()> {
+        let orientation = img.orientation();
+        if orientation != 1 && self.orientation.is_some_and(|set| set != orientation) {
+            return Err(Error::invalid_param(
+                "input images carry conflicting EXIF orientations",
+            ));
+        }
         let err =
             unsafe { sys::uhdr_enc_set_raw_image(self.raw.as_ptr(), img.as_raw_mut(), intent) };
-        check(err)
+        check(err)?;
+        self.attached(intent);
+        if orientation != 1 {
+            self.orientation = Some(orientation);
+        }
+        Ok(())
+    }
+
+    /// Provide pixels previously copied out of a [`Decoder`] (e.g. after editing) as input.
+    ///
+    /// libultrahdr copies the buffer, so `img` may be dropped or mutated after this returns.
+    pub fn set_decoded_packed(&mut self, img: &mut DecodedPacked, intent: ImgLabel) -> Result<()> {
+        let mut raw = img.as_raw_image()?;
+        self.set_raw_image(&mut raw, intent)
     }
 
     /// Provide a compressed base image (JPEG) to be fused with a gain map.
+    ///
+    /// Accepts a [`CompressedImage`] from [`from_bytes`](CompressedImage::from_bytes) or an
+    /// [`OwnedCompressedImage`](crate::OwnedCompressedImage).
     pub fn set_compressed_image(
         &mut self,
-        img: &mut CompressedImage<'_>,
+        img: &mut impl AsCompressedImage,
         intent: ImgLabel,
     ) -> Result<()> {
+        let mut img = img.as_compressed_image();
+        if img.read_only {
+            return Err(Error::invalid_param(
+                "encoder inputs need a mutable buffer (CompressedImage::from_bytes)",
+            ));
+        }
         let err = unsafe {
             sys::uhdr_enc_set_compressed_image(self.raw.as_ptr(), img.as_mut_ptr(), intent)
         };
+        check(err)?;
+        self.attached(intent);
+        Ok(())
+    }
+
+    /// Provide the compressed base image, gain-map image and metadata from `src`.
+    ///
+    /// libultrahdr assembles them into an UltraHDR JPEG without recompressing either image.
+    /// Fails if the source has no gain map, or if its metadata is invalid or describes an
+    /// HDR base rendition (libultrahdr only writes SDR-base files; see
+    /// [`assemble_gainmap_jpeg`](crate::assemble_gainmap_jpeg) for that layout).
+    pub fn set_gainmap_source(&mut self, src: &impl GainMapSource) -> Result<()> {
+        let (Some(mut gainmap), Some(meta)) = (src.gainmap_image()?, src.gainmap_metadata()?)
+        else {
+            return Err(Error::invalid_param("source has no gain map"));
+        };
+        meta.validate()?;
+        if meta.base_rendition_is_hdr {
+            return Err(Error::invalid_param(
+                "libultrahdr cannot assemble an HDR base rendition; use assemble_gainmap_jpeg",
+            ));
+        }
+        let mut base = src.base_image()?;
+        let mut base_img =
+            CompressedImage::from_bytes(&mut base.data, base.cg, base.ct, base.range)?;
+        self.set_compressed_image(&mut base_img, ImgLabel::UHDR_BASE_IMG)?;
+
+        let mut gainmap_img =
+            CompressedImage::from_bytes(&mut gainmap.data, gainmap.cg, gainmap.ct, gainmap.range)?;
+        let mut raw_meta = meta.to_sys();
+        let err = unsafe {
+            sys::uhdr_enc_set_gainmap_image(
+                self.raw.as_ptr(),
+                gainmap_img.as_mut_ptr(),
+                &mut raw_meta,
+            )
+        };
+        check(err)?;
+        self.attached(ImgLabel::UHDR_GAIN_MAP_IMG);
+        Ok(())
+    }
+
+    /// Provide a gain map computed outside libultrahdr, with the metadata describing it.
+    ///
+    /// Use with a compressed base image (`UHDR_BASE_IMG`); libultrahdr then assembles the
+    /// two instead of deriving a gain map from HDR and SDR inputs. `img` must be
+    /// `UHDR_IMG_FMT_8bppYCbCr400` for a single-channel map or `UHDR_IMG_FMT_32bppRGBA8888`
+    /// (alpha ignored) for a multi-channel one. The metadata must pass
+    /// [`GainMapMetadata::validate`] and may not describe an HDR base rendition.
+    ///
+    /// libultrahdr only accepts compressed gain maps, so the pixels are copied now and coded
+    /// as a baseline JPEG during [`encode`](Self::encode), at the gain-map quality from
+    /// [`set_quality`](Self::set_quality) (95 by default) and the subsampling from
+    /// [`set_gainmap_chroma_subsampling`](Self::set_gainmap_chroma_subsampling).
+    pub fn set_gainmap_image(
+        &mut self,
+        img: &mut RawImage<'_>,
+        metadata: &GainMapMetadata,
+    ) -> Result<()> {
+        metadata.validate()?;
+        if metadata.base_rendition_is_hdr {
+            return Err(Error::invalid_param(
+                "libultrahdr cannot assemble an HDR base rendition; use assemble_gainmap_jpeg",
+            ));
+        }
+        gainmap::check_pixels(&img.inner)?;
+        let (cg, ct, range) = img.meta();
+        let pixels = DecodedPacked {
+            fmt: img.fmt(),
+            cg,
+            ct,
+            range,
+            width: img.width(),
+            height: img.height(),
+            data: copy_raw_packed(&img.inner)?,
+        };
+        self.raw_gainmap = Some((pixels, metadata.clone()));
+        self.attached(ImgLabel::UHDR_GAIN_MAP_IMG);
+        Ok(())
+    }
+
+    /// Write an HDR-base file: `base` is the primary image and the gain map derives SDR.
+    ///
+    /// `base` is the JPEG coding of the HDR rendition, e.g. 8-bit PQ or HLG with a matching
+    /// ICC profile, since a JPEG cannot hold the 10-bit or float pixels themselves. `hdr`
+    /// holds those pixels (`RGBA1010102` with PQ or HLG, or half-float linear) and `sdr`
+    /// the SDR rendition (`RGBA8888`, sRGB) at the same dimensions. libultrahdr only writes
+    /// SDR-base files, so [`encode`](Self::encode) computes a full-resolution,
+    /// single-channel gain map from the two on the Rust side, codes it at the gain-map
+    /// quality, and assembles the file with
+    /// [`assemble_gainmap_jpeg`](crate::assemble_gainmap_jpeg), setting
+    /// `base_rendition_is_hdr` in both metadata formats. The content boost bounds and
+    /// gain-map offsets apply when set; other images may not be attached.
+    pub fn set_hdr_base(
+        &mut self,
+        base: &[u8],
+        hdr: &mut RawImage<'_>,
+        sdr: &mut RawImage<'_>,
+    ) -> Result<()> {
+        JpegInfo::parse(base)?;
+        let copy = |img: &RawImage<'_>| -> Result<DecodedPacked> {
+            let (cg, ct, range) = img.meta();
+            Ok(DecodedPacked {
+                fmt: img.fmt(),
+                cg,
+                ct,
+                range,
+                width: img.width(),
+                height: img.height(),
+                data: copy_raw_packed(&img.inner)?,
+            })
+        };
+        let hdr = copy(hdr)?;
+        let sdr = copy(sdr)?;
+        // Catch unsupported formats and mismatched sizes now rather than at encode time.
+        let _ = hdr.pixel_nits()?;
+        let _ = sdr.pixel_nits()?;
+        if (hdr.width, hdr.height) != (sdr.width, sdr.height) {
+            return Err(Error::invalid_param(format!(
+                "HDR image is {}x{} but SDR image is {}x{}",
+                hdr.width, hdr.height, sdr.width, sdr.height
+            )));
+        }
+        self.hdr_base = Some(HdrBase {
+            base: base.to_vec(),
+            hdr,
+            sdr,
+        });
+        Ok(())
+    }
+
+    /// Compute, code and assemble the gain map of a [`set_hdr_base`](Self::set_hdr_base)
+    /// encode; the file becomes the patched output stream.
+    fn encode_hdr_base(&mut self) -> Result<()> {
+        let Some(src) = &self.hdr_base else {
+            return Ok(());
+        };
+        if !self.inputs.is_empty() {
+            return Err(Error::invalid_param(
+                "an HDR base cannot be combined with other images",
+            ));
+        }
+        if self.gainmap_subsampling.is_some() {
+            return Err(Error::invalid_param(
+                "gain-map chroma subsampling only applies to set_gainmap_image",
+            ));
+        }
+        let content_boost =
+            (self.content_boost != DEFAULT_CONTENT_BOOST).then_some(self.content_boost);
+        let offsets = self
+            .gainmap_offsets
+            .unwrap_or((gainmap::DEFAULT_OFFSET, gainmap::DEFAULT_OFFSET));
+        let (mut pixels, meta) =
+            gainmap::compute(&src.hdr, &src.sdr, content_boost, offsets, true)?;
+        let map = RawImage::packed(
+            sys::uhdr_img_fmt::UHDR_IMG_FMT_8bppYCbCr400,
+            src.hdr.width,
+            src.hdr.height,
+            &mut pixels,
+            sys::uhdr_color_gamut::UHDR_CG_UNSPECIFIED,
+            sys::uhdr_color_transfer::UHDR_CT_UNSPECIFIED,
+            sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+        )?;
+        let compressed =
+            gainmap::compress(&map.inner, self.gainmap_quality, ChromaSubsampling::Yuv444)?;
+        let data = gainmap::assemble_gainmap_jpeg(&src.base, &compressed, &meta)?;
+        self.patched = Some(PatchedStream::new(
+            data,
+            src.hdr.cg,
+            src.hdr.ct,
+            sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+        ));
+        Ok(())
+    }
+
+    /// Choose the chroma subsampling of a multi-channel gain map passed to
+    /// [`set_gainmap_image`](Self::set_gainmap_image); 4:4:4 by default.
+    ///
+    /// libultrahdr has no subsampling control for gain maps it computes and codes itself,
+    /// and a single-channel map has no chroma to subsample, so [`encode`](Self::encode)
+    /// fails if this is set without an `RGBA8888` gain map from `set_gainmap_image`. The
+    /// base image is unaffected.
+    pub fn set_gainmap_chroma_subsampling(&mut self, subsampling: ChromaSubsampling) -> Result<()> {
+        if let Some((pixels, _)) = &self.raw_gainmap {
+            check_subsampled_gainmap(pixels)?;
+        }
+        self.gainmap_subsampling = Some(subsampling);
+        Ok(())
+    }
+
+    /// Compress a gain map from [`set_gainmap_image`](Self::set_gainmap_image) and hand it
+    /// to libultrahdr.
+    fn attach_raw_gainmap(&mut self) -> Result<()> {
+        let Some((pixels, meta)) = &mut self.raw_gainmap else {
+            if self.gainmap_subsampling.is_some() {
+                return Err(Error::invalid_param(
+                    "gain-map chroma subsampling only applies to set_gainmap_image",
+                ));
+            }
+            if self.gainmap_offsets.is_some() {
+                return Err(Error::invalid_param(
+                    "gain-map offsets only apply to set_gainmap_image",
+                ));
+            }
+            return Ok(());
+        };
+        if let Some((sdr, hdr)) = self.gainmap_offsets {
+            meta.offset_sdr = [sdr; 3];
+            meta.offset_hdr = [hdr; 3];
+        }
+        if self.gainmap_subsampling.is_some() {
+            check_subsampled_gainmap(pixels)?;
+        }
+        let raw = pixels.as_raw_image()?;
+        let mut data = gainmap::compress(
+            &raw.inner,
+            self.gainmap_quality,
+            self.gainmap_subsampling.unwrap_or_default(),
+        )?;
+        let mut compressed = CompressedImage::from_bytes(
+            &mut data,
+            sys::uhdr_color_gamut::UHDR_CG_UNSPECIFIED,
+            sys::uhdr_color_transfer::UHDR_CT_UNSPECIFIED,
+            sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+        )?;
+        let mut raw_meta = meta.to_sys();
+        let err = unsafe {
+            sys::uhdr_enc_set_gainmap_image(
+                self.raw.as_ptr(),
+                compressed.as_mut_ptr(),
+                &mut raw_meta,
+            )
+        };
         check(err)
     }
 
+    fn attached(&mut self, label: ImgLabel) {
+        if !self.inputs.contains(&label) {
+            self.inputs.push(label);
+        }
+    }
+
+    /// Fail early, with a clear message, when the attached images cannot make an UltraHDR
+    /// file: libultrahdr needs an HDR intent, or a base image plus a compressed gain map.
+    fn check_inputs(&self) -> Result<()> {
+        let has = |label| self.inputs.contains(&label);
+        if self.inputs.is_empty() {
+            return Err(Error::invalid_param("no images set"));
+        }
+        if has(ImgLabel::UHDR_HDR_IMG)
+            || (has(ImgLabel::UHDR_BASE_IMG) && has(ImgLabel::UHDR_GAIN_MAP_IMG))
+        {
+            return Ok(());
+        }
+        Err(Error::invalid_param("no HDR image set"))
+    }
+
     /// Set JPEG quality for the given image label (base or gain map).
     pub fn set_quality(&mut self, quality: i32, label: ImgLabel) -> Result<()> {
         let err = unsafe { sys::uhdr_enc_set_quality(self.raw.as_ptr(), quality, label) };
-        check(err)
+        check(err)?;
+        if label == ImgLabel::UHDR_GAIN_MAP_IMG {
+            self.gainmap_quality = quality;
+        }
+        Ok(())
     }
 
     /// Control the gain-map scale factor (higher values bias toward HDR detail).
@@ -83,11 +519,70 @@ impl Encoder {
     }
 
     /// Adjust the gain-map gamma curve.
+    ///
+    /// The gain map is always stored at [`GAINMAP_BIT_DEPTH`](crate::GAINMAP_BIT_DEPTH) bits;
+    /// the gamma redistributes those code values across the boost range, which can reduce
+    /// banding where smooth gradients concentrate.
     pub fn set_gainmap_gamma(&mut self, gamma: f32) -> Result<()> {
         let err = unsafe { sys::uhdr_enc_set_gainmap_gamma(self.raw.as_ptr(), gamma) };
         check(err)
     }
 
+    /// Set the lower bound of the content boost recorded for a gain map libultrahdr computes,
+    /// as a linear factor (e.g. `0.5` allows the HDR rendition one stop below the base).
+    ///
+    /// By default both bounds are derived from the content. `boost` must be finite, positive
+    /// and at most the maximum from [`set_max_content_boost`](Self::set_max_content_boost).
+    pub fn set_min_content_boost(&mut self, boost: f32) -> Result<()> {
+        self.set_content_boost(boost, self.content_boost.1)
+    }
+
+    /// Set the upper bound of the content boost recorded for a gain map libultrahdr
+    /// computes, as a linear factor (e.g. `4.0` for two stops over the base).
+    ///
+    /// `boost` must be finite, positive and at least the minimum from
+    /// [`set_min_content_boost`](Self::set_min_content_boost).
+    pub fn set_max_content_boost(&mut self, boost: f32) -> Result<()> {
+        self.set_content_boost(self.content_boost.0, boost)
+    }
+
+    fn set_content_boost(&mut self, min: f32, max: f32) -> Result<()> {
+        if !min.is_finite() || !max.is_finite() || min <= 0.0 {
+            return Err(Error::invalid_param(
+                "content boost must be finite and positive",
+            ));
+        }
+        if min > max {
+            return Err(Error::invalid_param(
+                "min content boost must be <= max content boost",
+            ));
+        }
+        let err = unsafe { sys::uhdr_enc_set_min_max_content_boost(self.raw.as_ptr(), min, max) };
+        check(err)?;
+        self.content_boost = (min, max);
+        Ok(())
+    }
+
+    /// Set the SDR and HDR offsets of a gain map passed to
+    /// [`set_gainmap_image`](Self::set_gainmap_image), replacing the ones in its metadata for
+    /// all channels.
+    ///
+    /// libultrahdr computes its own gain maps with fixed offsets of 1/64 and has no setting
+    /// for them, so [`encode`](Self::encode) fails if this is set without a caller-computed
+    /// gain map. Both offsets must be finite and non-negative.
+    pub fn set_gainmap_offsets(&mut self, offset_sdr: f32, offset_hdr: f32) -> Result<()> {
+        if !(offset_sdr.is_finite() && offset_hdr.is_finite())
+            || offset_sdr < 0.0
+            || offset_hdr < 0.0
+        {
+            return Err(Error::invalid_param(
+                "gain-map offsets must be finite and non-negative",
+            ));
+        }
+        self.gainmap_offsets = Some((offset_sdr, offset_hdr));
+        Ok(())
+    }
+
     /// Set the target display peak brightness (in nits) used for capacity calculations.
     pub fn set_target_display_peak_brightness(&mut self, nits: f32) -> Result<()> {
         let err =
@@ -95,26 +590,366 @@ impl Encoder {
         check(err)
     }
 
+    /// Set the target display headroom in stops above SDR white.
+    ///
+    /// Equivalent to [`set_target_display_peak_brightness`](Self::set_target_display_peak_brightness)
+    /// with `SDR_WHITE_NITS * 2^stops` nits: 0 stops is SDR white (203 nits), 3 stops is
+    /// 1624 nits. `stops` must be finite and non-negative.
+    pub fn set_headroom_stops(&mut self, stops: f32) -> Result<()> {
+        if !stops.is_finite() || stops < 0.0 {
+            return Err(Error::invalid_param(
+                "headroom stops must be finite and >= 0",
+            ));
+        }
+        self.set_target_display_peak_brightness(SDR_WHITE_NITS * stops.exp2())
+    }
+
     /// Choose a tuning preset. Higher presets may trade speed for quality.
-    pub fn set_preset(&mut self, preset: EncPreset) -> Result<()> {
-        let err = unsafe { sys::uhdr_enc_set_preset(self.raw.as_ptr(), preset) };
+    ///
+    /// Accepts an [`EncPreset`] or an [`EncoderEffort`](crate::EncoderEffort).
+    pub fn set_preset(&mut self, preset: impl Into<EncPreset>) -> Result<()> {
+        let err = unsafe { sys::uhdr_enc_set_preset(self.raw.as_ptr(), preset.into()) };
         check(err)
     }
 
-    /// Choose the output codec (UltraHDR JPEG or plain JPEG).
+    /// Choose the output codec.
+    ///
+    /// Only `UHDR_CODEC_JPG` is accepted. `uhdr_codec_t` also lists HEIF and AVIF, but
+    /// libultrahdr has no encoder for either container in any build configuration, so there
+    /// is no feature to enable; they fail here with `UHDR_CODEC_UNSUPPORTED_FEATURE` before
+    /// reaching the C library.
     pub fn set_output_format(&mut self, codec: Codec) -> Result<()> {
+        check_output_codec(codec)?;
         let err = unsafe { sys::uhdr_enc_set_output_format(self.raw.as_ptr(), codec) };
         check(err)
     }
 
+    /// Attach an extra APPn segment (e.g. C2PA in APP11) to the encoded JPEG.
+    ///
+    /// `marker` is the full marker byte, `0xE1` (APP1) through `0xEF` (APP15). Segments are
+    /// inserted after the existing application segments of the primary image once
+    /// [`encode`](Self::encode) finishes; the MPF index is updated so the gain map stays
+    /// addressable. Only applies to JPEG output.
+    pub fn add_app_segment(&mut self, marker: u8, data: Vec<u8>) -> Result<()> {
+        if !(0xE1..=jpeg::APP15).contains(&marker) {
+            return Err(Error::invalid_param("APP marker must be in APP1..=APP15"));
+        }
+        if data.len() > jpeg::MAX_SEGMENT_PAYLOAD {
+            return Err(Error::invalid_param("APP segment exceeds 64KB limit"));
+        }
+        self.app_segments.push((marker, data));
+        Ok(())
+    }
+
+    /// Set the EXIF orientation (1..=8) of the output without authoring an EXIF block.
+    ///
+    /// After [`encode`](Self::encode), an Orientation tag in the primary image's EXIF is
+    /// updated, or a minimal EXIF APP1 holding only that tag is added. This overrides the
+    /// orientation of an [`OwnedPackedImage`] attached earlier; attaching one with a
+    /// different non-default orientation afterwards fails. Only applies to JPEG output.
+    pub fn set_orientation(&mut self, orientation: u8) -> Result<()> {
+        exif::check_orientation(orientation)?;
+        self.orientation = Some(orientation);
+        Ok(())
+    }
+
+    /// Embed an ICC profile in the base image (`UHDR_BASE_IMG`) or the gain-map image
+    /// (`UHDR_GAIN_MAP_IMG`) of the output.
+    ///
+    /// libultrahdr has no ICC input, so after [`encode`](Self::encode) any profile the
+    /// chosen image already carries is replaced by `bytes`, split into APP2 chunks. A
+    /// gain-map profile only matters to readers when the metadata clears `use_base_cg`.
+    /// Only applies to JPEG output.
+    ///
+    /// `bytes` must start with a valid ICC header (declared size matching its length and
+    /// the `acsp` signature). [`icc`](crate::icc) provides profiles for the libultrahdr
+    /// gamuts.
+    pub fn set_icc_profile(&mut self, bytes: &[u8], label: ImgLabel) -> Result<()> {
+        icc::validate(bytes)?;
+        let slot = match label {
+            ImgLabel::UHDR_BASE_IMG => &mut self.base_icc,
+            ImgLabel::UHDR_GAIN_MAP_IMG => &mut self.gainmap_icc,
+            _ => {
+                return Err(Error::invalid_param(
+                    "ICC profile label must be UHDR_BASE_IMG or UHDR_GAIN_MAP_IMG",
+                ));
+            }
+        };
+        // Fail now rather than in encode() for profiles too large to chunk.
+        jpeg::set_icc_profile(&mut Vec::new(), bytes)?;
+        *slot = Some(bytes.to_vec());
+        Ok(())
+    }
+
+    /// Mirror the input along `direction` before encoding.
+    ///
+    /// Effects are applied in the order they are added, on the CPU unless GPU acceleration
+    /// is available (`gles` feature).
+    pub fn add_effect_mirror(&mut self, direction: MirrorDirection) -> Result<()> {
+        effects::mirror(self.raw, direction)
+    }
+
+    /// Rotate the input clockwise by 90, 180 or 270 degrees before encoding.
+    pub fn add_effect_rotate(&mut self, degrees: u32) -> Result<()> {
+        effects::rotate(self.raw, degrees)
+    }
+
+    /// Crop the input to the pixel columns `left..right` and rows `top..bottom` before
+    /// encoding.
+    pub fn add_effect_crop(&mut self, left: u32, right: u32, top: u32, bottom: u32) -> Result<()> {
+        effects::crop(self.raw, left, right, top, bottom)
+    }
+
+    /// Resize the input to `width`×`height` before encoding.
+    pub fn add_effect_resize(&mut self, width: u32, height: u32) -> Result<()> {
+        effects::resize(self.raw, width, height)
+    }
+
+    /// Choose which gain-map metadata formats the output carries.
+    ///
+    /// libultrahdr fixes ISO 21496-1 and XMP emission at build time (the `iso21496` and
+    /// `xmp` features). When set, the encoded stream is post-processed so it carries
+    /// exactly the requested formats: missing ones are generated from the metadata read
+    /// back from the output, and the result is probed again before [`encode`](Self::encode)
+    /// returns. At least one format must be enabled.
+    pub fn set_metadata_formats(&mut self, iso: bool, xmp: bool) -> Result<()> {
+        if !iso && !xmp {
+            return Err(Error::invalid_param(
+                "at least one gain-map metadata format is required",
+            ));
+        }
+        self.metadata_formats = Some((iso, xmp));
+        Ok(())
+    }
+
+    /// Request byte-identical output for identical inputs and settings.
+    ///
+    /// The CPU path of libultrahdr is already reproducible: worker threads compute disjoint
+    /// gain-map rows, the gain-map min/max reduction is order-independent and JPEG
+    /// compression is single-threaded. Deterministic mode keeps GPU acceleration off for
+    /// this encoder, since GPU float results can vary across drivers. Build with the
+    /// `no-threads` feature to also rule out scheduling entirely.
+    pub fn set_deterministic(&mut self, enable: bool) {
+        self.deterministic = enable;
+    }
+
+    /// Re-encode the base image and gain map with optimized Huffman tables.
+    ///
+    /// libultrahdr does not forward libjpeg's `optimize_coding`, so this runs a lossless
+    /// pass after [`encode`](Self::encode): each baseline JPEG is entropy-decoded, optimal
+    /// tables are built from its symbol statistics, and the scan is re-encoded. Pixels are
+    /// unchanged and files typically shrink by a few percent, at the cost of roughly one
+    /// extra entropy decode and encode per image. Images the pass cannot improve are kept.
+    ///
+    /// Arithmetic coding is not offered: libultrahdr has no switch for it, and browsers and
+    /// most platform decoders cannot read arithmetic-coded JPEGs, which defeats the point
+    /// of a backward-compatible UltraHDR base image.
+    pub fn set_jpeg_optimize(&mut self, enable: bool) {
+        self.jpeg_optimize = enable;
+    }
+
     /// Run the encoder with the current settings.
+    ///
+    /// Fails with `UHDR_CODEC_INVALID_PARAM` before calling into libultrahdr when no HDR
+    /// image is attached and no base image plus gain map was provided (e.g. only an SDR
+    /// image was set). With [`set_hdr_base`](Self::set_hdr_base) the file is produced on
+    /// the Rust side and libultrahdr is not called.
     pub fn encode(&mut self) -> Result<()> {
+        self.patched = None;
+        self.gainmap_stream = None;
+        if self.hdr_base.is_some() {
+            self.encode_hdr_base()?;
+            return self.post_process();
+        }
+        self.check_inputs()?;
+        self.attach_raw_gainmap()?;
+        if self.deterministic {
+            let err = unsafe { sys::uhdr_enable_gpu_acceleration(self.raw.as_ptr(), 0) };
+            check(err)?;
+        }
         let err = unsafe { sys::uhdr_encode(self.raw.as_ptr()) };
-        check(err)
+        check(err)?;
+        self.post_process()
+    }
+
+    /// Apply Rust-side edits (extra APP segments, ICC profiles, metadata formats, Huffman
+    /// optimization) to the stream produced by `uhdr_encode` or assembled for an HDR base.
+    fn post_process(&mut self) -> Result<()> {
+        if self.app_segments.is_empty()
+            && self.orientation.is_none()
+            && self.base_icc.is_none()
+            && self.gainmap_icc.is_none()
+            && self.metadata_formats.is_none()
+            && !self.jpeg_optimize
+        {
+            return Ok(());
+        }
+        let stream = match &self.patched {
+            Some(patched) => &patched.raw,
+            None => {
+                let ptr = unsafe { sys::uhdr_get_encoded_stream(self.raw.as_ptr()) };
+                if ptr.is_null() {
+                    return Err(Error::invalid_param("encoder produced no output"));
+                }
+                // SAFETY: pointer owned by encoder and valid until the next encode/reset.
+                unsafe { &*ptr }
+            }
+        };
+        let (cg, ct, range) = (stream.cg, stream.ct, stream.range);
+        let bytes = EncodedView::new(stream).bytes()?;
+        let mut data = None;
+        if !self.app_segments.is_empty() || self.orientation.is_some() || self.base_icc.is_some() {
+            let extra = &self.app_segments;
+            let orientation = self.orientation;
+            let icc = self.base_icc.as_deref();
+            data = Some(jpeg::rewrite_primary(bytes, |segments| {
+                if let Some(orientation) = orientation {
+                    exif::apply_orientation(segments, orientation)?;
+                }
+                if let Some(icc) = icc {
+                    jpeg::set_icc_profile(segments, icc)?;
+                }
+                for (marker, payload) in extra {
+                    jpeg::insert_app_segment(segments, *marker, payload.clone());
+                }
+                Ok(())
+            })?);
+        }
+        if let Some(icc) = &self.gainmap_icc {
+            let src = data.as_deref().unwrap_or(bytes);
+            let gainmap = jpeg::secondary_image(src, 1)?;
+            let (mut segments, scan_at) = jpeg::parse_header(gainmap)?;
+            jpeg::set_icc_profile(&mut segments, icc)?;
+            let image = jpeg::serialize(&segments, &gainmap[scan_at..])?;
+            data = Some(metadata::splice_gainmap(src, image)?);
+        }
+        if let Some((iso, xmp)) = self.metadata_formats {
+            let src = data.as_deref().unwrap_or(bytes);
+            data = Some(metadata::apply_formats(src, iso, xmp)?);
+        }
+        if self.jpeg_optimize {
+            let src = data.as_deref().unwrap_or(bytes);
+            if let Some(optimized) = huffman::optimize_stream(src)? {
+                data = Some(optimized);
+            }
+        }
+        if let Some(data) = data {
+            self.patched = Some(PatchedStream::new(data, cg, ct, range));
+        }
+        Ok(())
+    }
+
+    /// Rewrite the gain-map metadata of an existing UltraHDR JPEG without recompressing.
+    ///
+    /// The ISO 21496-1 block and `hdrgm` XMP packet of the gain-map image are regenerated
+    /// from `new_meta`; whichever of the two the original carries is replaced. All image
+    /// data is copied verbatim, and the MPF index plus the primary XMP `Container` length
+    /// are updated for the resized gain-map image.
+    pub fn reencode_metadata_only(original: &[u8], new_meta: &GainMapMetadata) -> Result<Vec<u8>> {
+        new_meta.validate()?;
+        let gainmap = jpeg::secondary_image(original, 1)?;
+        let (mut segments, scan_at) = jpeg::parse_header(gainmap)?;
+        if !metadata::regenerate(&mut segments, new_meta) {
+            return Err(Error::invalid_param("gain map image carries no metadata"));
+        }
+        let image = jpeg::serialize(&segments, &gainmap[scan_at..])?;
+        metadata::splice_gainmap(original, image)
+    }
+
+    /// Run [`encode`](Self::encode) and copy the stream out, releasing the borrow on the
+    /// encoder.
+    ///
+    /// Equivalent to `encode()` followed by [`encoded_stream`](Self::encoded_stream) and
+    /// [`EncodedView::to_owned`]; fails if the encode produced no output.
+    pub fn encode_to_owned(&mut self) -> Result<EncodedImage> {
+        self.encode()?;
+        self.encoded_stream()
+            .ok_or_else(|| Error::invalid_param("encoder produced no output"))?
+            .to_owned()
+    }
+
+    /// Write the encoded stream to `w`, running [`encode`](Self::encode) first if no
+    /// stream exists yet, and return the number of bytes written.
+    ///
+    /// The bytes go straight from the encoder's buffer to `w` in writes of at most 64 KiB,
+    /// so no owned copy of the stream is made (libultrahdr itself always holds the whole
+    /// stream). I/O failures are reported as
+    /// `UHDR_CODEC_UNKNOWN_ERROR` with the `io::Error` in the detail.
+    pub fn write_encoded<W: Write>(&mut self, w: &mut W) -> Result<usize> {
+        if self.encoded_stream().is_none() {
+            self.encode()?;
+        }
+        let bytes = self
+            .encoded_stream()
+            .ok_or_else(|| Error::invalid_param("encoder produced no output"))?
+            .bytes()?;
+        write_chunks(w, bytes)
+    }
+
+    /// Encode at the highest quality in `quality_bounds` whose output fits in `max_bytes`.
+    ///
+    /// Binary-searches the quality, applying each candidate to both the base image and the
+    /// gain map, for at most eight encodes. libultrahdr only accepts new
+    /// settings after a reset, which also drops the attached images and wrapper-side options,
+    /// so `configure` is called after every [`reset`](Self::reset) to attach the inputs and
+    /// apply the remaining settings; it must not set the quality itself. The best fit is
+    /// returned as an owned copy, since [`encoded_stream`](Self::encoded_stream) afterwards
+    /// holds the last attempt, which may be over budget.
+    ///
+    /// Fails with `UHDR_CODEC_INVALID_PARAM` for an empty budget, bounds outside `0..=100`,
+    /// or when even the lowest quality exceeds `max_bytes`.
+    pub fn encode_within_budget<F>(
+        &mut self,
+        max_bytes: usize,
+        quality_bounds: RangeInclusive<i32>,
+        mut configure: F,
+    ) -> Result<EncodedImage>
+    where
+        F: FnMut(&mut Encoder) -> Result<()>,
+    {
+        let (mut lo, mut hi) = quality_bounds.into_inner();
+        if max_bytes == 0 {
+            return Err(Error::invalid_param("byte budget must be non-zero"));
+        }
+        if !(0..=100).contains(&lo) || !(0..=100).contains(&hi) || lo > hi {
+            return Err(Error::invalid_param(format!(
+                "quality bounds {lo}..={hi} must lie within 0..=100"
+            )));
+        }
+        let floor = lo;
+        let mut best = None;
+        let mut smallest = usize::MAX;
+        for _ in 0..MAX_BUDGET_ATTEMPTS {
+            if lo > hi {
+                break;
+            }
+            let quality = lo + (hi - lo + 1) / 2;
+            self.reset();
+            configure(self)?;
+            self.set_quality(quality, ImgLabel::UHDR_BASE_IMG)?;
+            self.set_quality(quality, ImgLabel::UHDR_GAIN_MAP_IMG)?;
+            let encoded = self.encode_to_owned()?;
+            smallest = smallest.min(encoded.data.len());
+            if encoded.data.len() <= max_bytes {
+                best = Some(encoded);
+                lo = quality + 1;
+            } else {
+                hi = quality - 1;
+            }
+        }
+        best.ok_or_else(|| {
+            Error::invalid_param(format!(
+                "smallest output at quality >= {floor} is {smallest} bytes, over the \
+                 {max_bytes}-byte budget"
+            ))
+        })
     }
 
     /// Returns a view of the encoded stream owned by the encoder.
     pub fn encoded_stream(&mut self) -> Option<EncodedView<'_>> {
+        if let Some(patched) = &self.patched {
+            return Some(EncodedView::new(&patched.raw));
+        }
         let ptr = unsafe { sys::uhdr_get_encoded_stream(self.raw.as_ptr()) };
         if ptr.is_null() {
             None
@@ -123,14 +958,864 @@ impl Encoder {
         }
     }
 
+    /// Returns a view of the gain-map JPEG embedded in the encoded stream.
+    ///
+    /// libultrahdr has no accessor for the compressed gain map on its own, so this locates
+    /// MPF image 1 of [`encoded_stream`](Self::encoded_stream) and borrows it in place: the
+    /// bytes are a standalone JPEG carrying the gain-map metadata, exactly as written into the
+    /// UltraHDR file. Color metadata follows [`Decoder::gainmap_image`](crate::Decoder::gainmap_image)
+    /// (unspecified gamut and transfer, full range). Returns `None` before a successful
+    /// encode and for plain-JPEG output without a gain map.
+    pub fn encoded_gainmap(&mut self) -> Option<EncodedView<'_>> {
+        if self.gainmap_stream.is_none() {
+            let bytes = self.encoded_stream()?.bytes().ok()?;
+            let gainmap = jpeg::secondary_image(bytes, 1).ok()?;
+            self.gainmap_stream = Some(sys::uhdr_compressed_image {
+                data: gainmap.as_ptr() as *mut c_void,
+                data_sz: gainmap.len(),
+                capacity: gainmap.len(),
+                cg: sys::uhdr_color_gamut::UHDR_CG_UNSPECIFIED,
+                ct: sys::uhdr_color_transfer::UHDR_CT_UNSPECIFIED,
+                range: sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+            });
+        }
+        self.gainmap_stream.as_ref().map(EncodedView::new)
+    }
+
     /// Reset all state so the encoder can be reused.
     pub fn reset(&mut self) {
+        self.clear_state();
         unsafe { sys::uhdr_reset_encoder(self.raw.as_ptr()) }
     }
+
+    /// Reset the wrapper-side state, leaving the codec context untouched.
+    fn clear_state(&mut self) {
+        self.inputs.clear();
+        self.app_segments.clear();
+        self.metadata_formats = None;
+        self.deterministic = false;
+        self.jpeg_optimize = false;
+        self.orientation = None;
+        self.base_icc = None;
+        self.gainmap_icc = None;
+        self.gainmap_quality = DEFAULT_GAINMAP_QUALITY;
+        self.gainmap_subsampling = None;
+        self.content_boost = DEFAULT_CONTENT_BOOST;
+        self.gainmap_offsets = None;
+        self.raw_gainmap = None;
+        self.hdr_base = None;
+        self.patched = None;
+        self.gainmap_stream = None;
+    }
+}
+
+/// Upper bound on encodes run by [`Encoder::encode_within_budget`]; enough to bisect the
+/// whole `0..=100` quality range.
+pub(crate) const MAX_BUDGET_ATTEMPTS: usize = 8;
+
+/// Largest single write issued by [`Encoder::write_encoded`].
+pub(crate) const WRITE_CHUNK_LEN: usize = 64 * 1024;
+
+fn write_chunks<W: Write>(w: &mut W, bytes: &[u8]) -> Result<usize> {
+    for chunk in bytes.chunks(WRITE_CHUNK_LEN) {
+        w.write_all(chunk)
+            .map_err(|err| Error::io_context("writing encoded stream", err))?;
+    }
+    Ok(bytes.len())
+}
+
+/// Chainable configuration for an [`Encoder`], created with [`Encoder::builder`].
+///
+/// Setters only record values; ranges are validated and the settings are applied to a
+/// fresh encoder in [`build`](Self::build), so all configuration errors surface there.
+/// Settings left unset keep libultrahdr's defaults.
+///
+/// ```no_run
+/// use ultrahdr::{Encoder, sys};
+///
+/// let enc = Encoder::builder()
+///     .base_quality(95)
+///     .gainmap_quality(90)
+///     .gainmap_scale(2)
+///     .preset(sys::uhdr_enc_preset::UHDR_USAGE_BEST_QUALITY)
+///     .build()?;
+/// # Ok::<(), ultrahdr::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EncoderBuilder {
+    base_quality: Option<i32>,
+    gainmap_quality: Option<i32>,
+    gainmap_scale: Option<i32>,
+    multichannel: Option<bool>,
+    gamma: Option<f32>,
+    target_peak_nits: Option<f32>,
+    preset: Option<EncPreset>,
+    output_format: Option<Codec>,
+}
+
+impl EncoderBuilder {
+    /// JPEG quality of the base image (1-100).
+    pub fn base_quality(mut self, quality: i32) -> Self {
+        self.base_quality = Some(quality);
+        self
+    }
+
+    /// JPEG quality of the gain map (1-100).
+    pub fn gainmap_quality(mut self, quality: i32) -> Self {
+        self.gainmap_quality = Some(quality);
+        self
+    }
+
+    /// Gain-map downscale factor relative to the base image (at least 1).
+    pub fn gainmap_scale(mut self, factor: i32) -> Self {
+        self.gainmap_scale = Some(factor);
+        self
+    }
+
+    /// Compute one gain-map channel per color channel instead of a luma map.
+    pub fn multichannel(mut self, enable: bool) -> Self {
+        self.multichannel = Some(enable);
+        self
+    }
+
+    /// Gain-map gamma; must be finite and greater than zero.
+    pub fn gamma(mut self, gamma: f32) -> Self {
+        self.gamma = Some(gamma);
+        self
+    }
+
+    /// Target display peak brightness in nits; must be finite and greater than zero.
+    pub fn target_peak_nits(mut self, nits: f32) -> Self {
+        self.target_peak_nits = Some(nits);
+        self
+    }
+
+    /// Tuning preset, see [`Encoder::set_preset`].
+    pub fn preset(mut self, preset: impl Into<EncPreset>) -> Self {
+        self.preset = Some(preset.into());
+        self
+    }
+
+    /// Output codec, see [`Encoder::set_output_format`].
+    pub fn output_format(mut self, codec: Codec) -> Self {
+        self.output_format = Some(codec);
+        self
+    }
+
+    /// Validate the settings and create an [`Encoder`] configured with them.
+    ///
+    /// Validation runs before the codec context is created, so a bad value fails with
+    /// `UHDR_CODEC_INVALID_PARAM` without touching libultrahdr.
+    pub fn build(&self) -> Result<Encoder> {
+        self.validate()?;
+        let mut enc = Encoder::new()?;
+        if let Some(quality) = self.base_quality {
+            enc.set_quality(quality, ImgLabel::UHDR_BASE_IMG)?;
+        }
+        if let Some(quality) = self.gainmap_quality {
+            enc.set_quality(quality, ImgLabel::UHDR_GAIN_MAP_IMG)?;
+        }
+        if let Some(factor) = self.gainmap_scale {
+            enc.set_gainmap_scale_factor(factor)?;
+        }
+        if let Some(enable) = self.multichannel {
+            enc.set_using_multi_channel_gainmap(enable)?;
+        }
+        if let Some(gamma) = self.gamma {
+            enc.set_gainmap_gamma(gamma)?;
+        }
+        if let Some(nits) = self.target_peak_nits {
+            enc.set_target_display_peak_brightness(nits)?;
+        }
+        if let Some(preset) = self.preset {
+            enc.set_preset(preset)?;
+        }
+        if let Some(codec) = self.output_format {
+            enc.set_output_format(codec)?;
+        }
+        Ok(enc)
+    }
+
+    fn validate(&self) -> Result<()> {
+        let quality_ok = |q: Option<i32>| q.is_none_or(|q| (1..=100).contains(&q));
+        if !quality_ok(self.base_quality) {
+            return Err(Error::invalid_param("base quality must be in 1..=100"));
+        }
+        if !quality_ok(self.gainmap_quality) {
+            return Err(Error::invalid_param("gain-map quality must be in 1..=100"));
+        }
+        if self.gainmap_scale.is_some_and(|f| f < 1) {
+            return Err(Error::invalid_param("gain-map scale factor must be >= 1"));
+        }
+        let positive = |v: Option<f32>| v.is_none_or(|v| v.is_finite() && v > 0.0);
+        if !positive(self.gamma) {
+            return Err(Error::invalid_param(
+                "gain-map gamma must be finite and > 0",
+            ));
+        }
+        if !positive(self.target_peak_nits) {
+            return Err(Error::invalid_param(
+                "target peak brightness must be finite and > 0 nits",
+            ));
+        }
+        if let Some(codec) = self.output_format {
+            check_output_codec(codec)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reject the container codecs libultrahdr lists but cannot write.
+fn check_output_codec(codec: Codec) -> Result<()> {
+    match codec {
+        sys::uhdr_codec::UHDR_CODEC_JPG => Ok(()),
+        sys::uhdr_codec::UHDR_CODEC_HEIF | sys::uhdr_codec::UHDR_CODEC_AVIF => {
+            Err(Error::unsupported(format!(
+                "{codec:?} output is not implemented by libultrahdr; only JPEG can be written"
+            )))
+        }
+    }
+}
+
+fn check_subsampled_gainmap(pixels: &DecodedPacked) -> Result<()> {
+    if pixels.fmt == sys::uhdr_img_fmt::UHDR_IMG_FMT_8bppYCbCr400 {
+        return Err(Error::invalid_param(
+            "single-channel gain maps have no chroma to subsample",
+        ));
+    }
+    Ok(())
 }
 
+// SAFETY: the codec context has no thread affinity, see the crate-level "Concurrency" notes.
+// The descriptors in `patched` and `gainmap_stream` point into buffers the encoder owns.
+unsafe impl Send for Encoder {}
+
 impl Drop for Encoder {
     fn drop(&mut self) {
         unsafe { sys::uhdr_release_encoder(self.raw.as_ptr()) }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::Decoder;
+    use crate::types::tests::sample_metadata;
+    use crate::types::{RawImage, pack_rgba1010102, unpack_rgba1010102};
+    use std::io;
+
+    const W: u32 = 16;
+    const H: u32 = 16;
+
+    fn pack_1010102(r: u32, g: u32, b: u32) -> [u8; 4] {
+        (r | (g << 10) | (b << 20) | (3 << 30)).to_le_bytes()
+    }
+
+    /// Attach `hdr_px` (PQ RGBA1010102) and `sdr_px` (sRGB RGBA8888, tagged `sdr_range`)
+    /// as a `w`x`h` raw pair.
+    fn attach_pair(
+        enc: &mut Encoder,
+        (w, h): (u32, u32),
+        hdr_px: &mut [u8],
+        sdr_px: &mut [u8],
+        sdr_range: sys::uhdr_color_range,
+    ) -> Result<()> {
+        let mut hdr = RawImage::packed(
+            sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA1010102,
+            w,
+            h,
+            hdr_px,
+            sys::uhdr_color_gamut::UHDR_CG_BT_2100,
+            sys::uhdr_color_transfer::UHDR_CT_PQ,
+            sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+        )?;
+        let mut sdr = RawImage::rgba8888(
+            w,
+            h,
+            sdr_px,
+            sys::uhdr_color_gamut::UHDR_CG_BT_709,
+            sys::uhdr_color_transfer::UHDR_CT_SRGB,
+            sdr_range,
+        )?;
+        enc.set_raw_image(&mut hdr, ImgLabel::UHDR_HDR_IMG)?;
+        enc.set_raw_image(&mut sdr, ImgLabel::UHDR_SDR_IMG)
+    }
+
+    /// Encode a full-range `W`x`H` raw pair after `configure` has adjusted the encoder.
+    fn encode_pair(
+        hdr_px: &mut [u8],
+        sdr_px: &mut [u8],
+        configure: impl FnOnce(&mut Encoder),
+    ) -> Encoder {
+        let mut enc = Encoder::new().unwrap();
+        configure(&mut enc);
+        let full = sys::uhdr_color_range::UHDR_CR_FULL_RANGE;
+        attach_pair(&mut enc, (W, H), hdr_px, sdr_px, full).unwrap();
+        enc.encode().unwrap();
+        enc
+    }
+
+    #[test]
+    fn raw_handle_round_trips_without_release() {
+        let enc = Encoder::new().unwrap();
+        let ptr = enc.into_raw();
+        let mut enc = unsafe { Encoder::from_raw(ptr) };
+        assert!(enc.encoded_stream().is_none());
+        let dec = Decoder::new().unwrap();
+        let ptr = dec.into_raw();
+        drop(unsafe { Decoder::from_raw(ptr) });
+    }
+
+    #[test]
+    fn builder_rejects_out_of_range_settings_before_creating_codec() {
+        let cases = [
+            (Encoder::builder().base_quality(0), "base quality"),
+            (Encoder::builder().gainmap_quality(101), "gain-map quality"),
+            (Encoder::builder().gainmap_scale(0), "scale factor"),
+            (Encoder::builder().gamma(f32::NAN), "gamma"),
+            (Encoder::builder().target_peak_nits(-1.0), "peak brightness"),
+        ];
+        for (builder, needle) in cases {
+            let err = builder.validate().unwrap_err();
+            assert_eq!(err.code, sys::uhdr_codec_err_t::UHDR_CODEC_INVALID_PARAM);
+            assert!(err.to_string().contains(needle), "{err}");
+        }
+        assert!(
+            Encoder::builder()
+                .base_quality(100)
+                .gainmap_quality(1)
+                .gainmap_scale(4)
+                .validate()
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn reencode_metadata_only_rewrites_gainmap_metadata() {
+        use crate::jpeg::Segment;
+        use crate::jpeg::tests::{secondary_via_mpf, ultrahdr_like_with};
+
+        let mut container = metadata::XMP_NAMESPACE.to_vec();
+        container.extend_from_slice(
+            br#"<Container:Item Item:Semantic="GainMap" Item:Mime="image/jpeg" Item:Length="0"/>"#,
+        );
+        let (stream, _) = ultrahdr_like_with(
+            &[Segment {
+                marker: jpeg::APP1,
+                data: container,
+            }],
+            &[
+                Segment {
+                    marker: jpeg::APP1,
+                    data: metadata::xmp_payload(&sample_metadata(2.0)),
+                },
+                Segment {
+                    marker: jpeg::APP2,
+                    data: metadata::iso_payload(&sample_metadata(2.0)),
+                },
+            ],
+        );
+
+        let new_meta = sample_metadata(8.0);
+        let out = Encoder::reencode_metadata_only(&stream, &new_meta).unwrap();
+        let gainmap = secondary_via_mpf(&out);
+        assert!(gainmap.ends_with(&[9, 9, 9, 0xFF, jpeg::EOI]));
+        assert_eq!(
+            crate::extract_app_segments(gainmap, jpeg::APP2),
+            vec![metadata::iso_payload(&new_meta)]
+        );
+        assert_eq!(
+            crate::extract_app_segments(gainmap, jpeg::APP1),
+            vec![metadata::xmp_payload(&new_meta)]
+        );
+        let primary_xmp = &crate::extract_app_segments(&out, jpeg::APP1)[0];
+        let expected = format!(r#"Item:Length="{}""#, gainmap.len());
+        assert!(
+            primary_xmp
+                .windows(expected.len())
+                .any(|w| w == expected.as_bytes())
+        );
+
+        let mut invalid = new_meta.clone();
+        invalid.min_content_boost = [0.0; 3];
+        assert!(Encoder::reencode_metadata_only(&stream, &invalid).is_err());
+    }
+
+    #[test]
+    fn limited_range_sdr_with_full_range_hdr_round_trips() {
+        // Mid-gray SDR base tagged as limited range.
+        let mut sdr = vec![0u8; (W * H * 4) as usize];
+        for px in sdr.chunks_exact_mut(4) {
+            px.copy_from_slice(&[128, 128, 128, 255]);
+        }
+        // PQ code value close to SDR reference white, full range.
+        let mut hdr = Vec::with_capacity((W * H * 4) as usize);
+        for _ in 0..W * H {
+            hdr.extend_from_slice(&pack_1010102(593, 593, 593));
+        }
+
+        let limited = sys::uhdr_color_range::UHDR_CR_LIMITED_RANGE;
+        let mut enc = Encoder::new().unwrap();
+        attach_pair(&mut enc, (W, H), &mut hdr, &mut sdr, limited).unwrap();
+        enc.set_output_format(sys::uhdr_codec::UHDR_CODEC_JPG)
+            .unwrap();
+        enc.encode().unwrap();
+        let mut encoded = enc
+            .encoded_stream()
+            .expect("encoder produced no output")
+            .bytes()
+            .unwrap()
+            .to_vec();
+
+        let mut dec = Decoder::new().unwrap();
+        let mut comp = CompressedImage::from_bytes(
+            &mut encoded,
+            sys::uhdr_color_gamut::UHDR_CG_UNSPECIFIED,
+            sys::uhdr_color_transfer::UHDR_CT_UNSPECIFIED,
+            sys::uhdr_color_range::UHDR_CR_UNSPECIFIED,
+        )
+        .unwrap();
+        dec.set_image(&mut comp).unwrap();
+        assert!(dec.gainmap_metadata().unwrap().is_some());
+        let view = dec
+            .decode_packed_view(
+                sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA8888,
+                sys::uhdr_color_transfer::UHDR_CT_SRGB,
+            )
+            .unwrap();
+        assert_eq!((view.width(), view.height()), (W, H));
+        // libultrahdr expands limited-range input; decoded RGBA is always full range.
+        assert_eq!(view.meta().2, sys::uhdr_color_range::UHDR_CR_FULL_RANGE);
+        let row = view.row((H / 2) as usize).unwrap();
+        for px in row.chunks_exact(4) {
+            for &c in &px[..3] {
+                assert!(c.abs_diff(128) <= 12, "unexpected SDR value {c}");
+            }
+        }
+    }
+
+    #[test]
+    fn packed_1010102_round_trips_through_codec() {
+        // Smooth PQ ramp around SDR reference white.
+        let mut hdr = Vec::with_capacity((W * H * 4) as usize);
+        for i in 0..W * H {
+            let v = 0.45 + 0.2 * (i % W) as f32 / W as f32;
+            hdr.extend_from_slice(&pack_rgba1010102(v, v, v, 1.0).to_le_bytes());
+        }
+        let expected: Vec<_> = hdr
+            .chunks_exact(4)
+            .map(|px| unpack_rgba1010102(u32::from_le_bytes(px.try_into().unwrap())))
+            .collect();
+        let mut hdr_raw = RawImage::packed(
+            sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA1010102,
+            W,
+            H,
+            &mut hdr,
+            sys::uhdr_color_gamut::UHDR_CG_BT_2100,
+            sys::uhdr_color_transfer::UHDR_CT_PQ,
+            sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+        )
+        .unwrap();
+        let mut enc = Encoder::new().unwrap();
+        enc.set_raw_image(&mut hdr_raw, ImgLabel::UHDR_HDR_IMG)
+            .unwrap();
+        enc.set_quality(100, ImgLabel::UHDR_BASE_IMG).unwrap();
+        enc.set_quality(100, ImgLabel::UHDR_GAIN_MAP_IMG).unwrap();
+        let mut encoded = enc.encode_to_owned().unwrap().data;
+
+        let mut dec = Decoder::new().unwrap();
+        let mut comp = CompressedImage::from_bytes(
+            &mut encoded,
+            sys::uhdr_color_gamut::UHDR_CG_UNSPECIFIED,
+            sys::uhdr_color_transfer::UHDR_CT_UNSPECIFIED,
+            sys::uhdr_color_range::UHDR_CR_UNSPECIFIED,
+        )
+        .unwrap();
+        dec.set_image(&mut comp).unwrap();
+        let decoded = dec
+            .decode_to_owned(
+                sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA1010102,
+                sys::uhdr_color_transfer::UHDR_CT_PQ,
+            )
+            .unwrap();
+        for (px, want) in decoded.data.chunks_exact(4).zip(&expected) {
+            let got = unpack_rgba1010102(u32::from_le_bytes(px.try_into().unwrap()));
+            for c in 0..3 {
+                assert!((got[c] - want[c]).abs() < 0.05, "{got:?} vs {want:?}");
+            }
+        }
+    }
+
+    /// Encode noisy raw HDR + raw SDR inputs; returns (primary bytes, gain-map bytes).
+    fn encode_noisy_pair(base_q: i32, gainmap_q: i32) -> (usize, usize) {
+        const N: u32 = 64;
+        let mut seed = 0x1234_5678u32;
+        let mut next = || {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            seed >> 24
+        };
+        let mut sdr = Vec::with_capacity((N * N * 4) as usize);
+        let mut hdr = Vec::with_capacity((N * N * 4) as usize);
+        for _ in 0..N * N {
+            let (r, g, b) = (next(), next(), next());
+            sdr.extend_from_slice(&[r as u8, g as u8, b as u8, 255]);
+            let boost = 256 + next();
+            hdr.extend_from_slice(&pack_1010102(
+                (r * 2 + boost).min(1023),
+                (g * 2 + boost).min(1023),
+                (b * 2 + boost).min(1023),
+            ));
+        }
+        let mut enc = Encoder::new().unwrap();
+        let full = sys::uhdr_color_range::UHDR_CR_FULL_RANGE;
+        attach_pair(&mut enc, (N, N), &mut hdr, &mut sdr, full).unwrap();
+        enc.set_quality(base_q, ImgLabel::UHDR_BASE_IMG).unwrap();
+        enc.set_quality(gainmap_q, ImgLabel::UHDR_GAIN_MAP_IMG)
+            .unwrap();
+        enc.encode().unwrap();
+        let stream = enc.encoded_stream().unwrap().bytes().unwrap();
+        let gainmap = jpeg::secondary_image(stream, 1).unwrap().len();
+        (stream.len() - gainmap, gainmap)
+    }
+
+    #[test]
+    fn encoded_gainmap_is_the_embedded_secondary_jpeg() {
+        let mut sdr = [200u8, 120, 40, 255].repeat((W * H) as usize);
+        let mut hdr = Vec::with_capacity((W * H * 4) as usize);
+        for i in 0..W * H {
+            hdr.extend_from_slice(&pack_1010102(400 + i % 200, 500, 600));
+        }
+        let mut enc = encode_pair(&mut hdr, &mut sdr, |enc| {
+            assert!(enc.encoded_gainmap().is_none());
+        });
+        let stream = enc.encoded_stream().unwrap().to_owned().unwrap().data;
+        let gainmap = enc.encoded_gainmap().unwrap().to_owned().unwrap();
+        assert_eq!(gainmap.data, jpeg::secondary_image(&stream, 1).unwrap());
+        assert_eq!(&gainmap.data[..2], &[0xFF, 0xD8]);
+        assert_eq!(gainmap.range, sys::uhdr_color_range::UHDR_CR_FULL_RANGE);
+
+        enc.reset();
+        assert!(enc.encoded_gainmap().is_none());
+    }
+
+    #[test]
+    fn encode_within_budget_picks_highest_fitting_quality() {
+        let mut hdr = Vec::with_capacity((W * H * 4) as usize);
+        for i in 0..W * H {
+            hdr.extend_from_slice(&pack_1010102(300 + i * 13 % 500, 500 + i % 7 * 40, 600));
+        }
+        let mut sdr = [200u8, 120, 40, 255].repeat((W * H) as usize);
+        let range = sys::uhdr_color_range::UHDR_CR_FULL_RANGE;
+        let mut attach = |enc: &mut Encoder| attach_pair(enc, (W, H), &mut hdr, &mut sdr, range);
+        let mut enc = Encoder::new().unwrap();
+        let full = enc
+            .encode_within_budget(usize::MAX, 1..=100, &mut attach)
+            .unwrap();
+        let budget = full.data.len() - 1;
+        let fitted = enc
+            .encode_within_budget(budget, 1..=100, &mut attach)
+            .unwrap();
+        assert!(fitted.data.len() <= budget);
+
+        let err = enc
+            .encode_within_budget(16, 1..=100, &mut attach)
+            .unwrap_err();
+        assert_eq!(err.code, sys::uhdr_codec_err_t::UHDR_CODEC_INVALID_PARAM);
+        let err = enc
+            .encode_within_budget(budget, 0..=101, &mut attach)
+            .unwrap_err();
+        assert_eq!(err.code, sys::uhdr_codec_err_t::UHDR_CODEC_INVALID_PARAM);
+    }
+
+    #[test]
+    fn raw_sdr_path_honors_base_and_gainmap_quality_independently() {
+        let (hi_base, hi_gm) = encode_noisy_pair(95, 95);
+        let (lo_base, base_only_gm) = encode_noisy_pair(20, 95);
+        let (gm_only_base, lo_gm) = encode_noisy_pair(95, 20);
+
+        assert!(
+            lo_base < hi_base,
+            "base quality ignored: {lo_base} vs {hi_base}"
+        );
+        assert!(
+            lo_gm < hi_gm,
+            "gain-map quality ignored: {lo_gm} vs {hi_gm}"
+        );
+        // Changing one quality must not move the other image by a comparable amount.
+        assert!(gm_only_base.abs_diff(hi_base) < hi_base - lo_base);
+        assert!(base_only_gm.abs_diff(hi_gm) < hi_gm - lo_gm);
+    }
+
+    #[test]
+    fn owned_images_keep_the_first_non_default_orientation() {
+        let owned = |fmt, orientation| {
+            let meta = (
+                sys::uhdr_color_gamut::UHDR_CG_BT_709,
+                sys::uhdr_color_transfer::UHDR_CT_SRGB,
+                sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+            );
+            let buf = vec![0u8; (W * H * 4) as usize];
+            let mut img =
+                OwnedPackedImage::from_vec(buf, fmt, W, H, meta.0, meta.1, meta.2).unwrap();
+            img.set_orientation(orientation).unwrap();
+            img
+        };
+        let hdr_fmt = sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA1010102;
+        let sdr_fmt = sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA8888;
+
+        let mut enc = Encoder::new().unwrap();
+        enc.set_raw_owned_image(&mut owned(hdr_fmt, 6), ImgLabel::UHDR_HDR_IMG)
+            .unwrap();
+        enc.set_raw_owned_image(&mut owned(sdr_fmt, 1), ImgLabel::UHDR_SDR_IMG)
+            .unwrap();
+        assert_eq!(enc.orientation, Some(6));
+        let err = enc
+            .set_raw_owned_image(&mut owned(sdr_fmt, 3), ImgLabel::UHDR_SDR_IMG)
+            .unwrap_err();
+        assert!(err.to_string().contains("conflicting"), "{err}");
+
+        let mut enc = Encoder::new().unwrap();
+        enc.set_orientation(8).unwrap();
+        enc.set_raw_owned_image(&mut owned(hdr_fmt, 1), ImgLabel::UHDR_HDR_IMG)
+            .unwrap();
+        assert_eq!(enc.orientation, Some(8));
+    }
+
+    #[test]
+    fn deterministic_mode_matches_default_cpu_output() {
+        let encode = |deterministic: bool| {
+            let mut sdr = [200u8, 120, 40, 255].repeat((W * H) as usize);
+            let mut hdr = Vec::with_capacity((W * H * 4) as usize);
+            for i in 0..W * H {
+                hdr.extend_from_slice(&pack_1010102(400 + i % 200, 500, 600));
+            }
+            let mut enc = encode_pair(&mut hdr, &mut sdr, |enc| {
+                enc.set_deterministic(deterministic)
+            });
+            enc.encoded_stream().unwrap().bytes().unwrap().to_vec()
+        };
+        // GPU acceleration is off by default, so the mode only pins the CPU path that
+        // already runs here; it must not change the output.
+        assert_eq!(encode(true), encode(false));
+    }
+
+    #[test]
+    fn jpeg_optimize_shrinks_output_without_changing_pixels() {
+        let encode = |optimize: bool| {
+            let mut sdr = Vec::with_capacity((W * H * 4) as usize);
+            let mut hdr = Vec::with_capacity((W * H * 4) as usize);
+            for i in 0..W * H {
+                sdr.extend_from_slice(&[(i * 7 % 256) as u8, 120, 40, 255]);
+                hdr.extend_from_slice(&pack_1010102(300 + i * 13 % 500, 500, 600));
+            }
+            let mut enc = encode_pair(&mut hdr, &mut sdr, |enc| enc.set_jpeg_optimize(optimize));
+            enc.encoded_stream().unwrap().bytes().unwrap().to_vec()
+        };
+        let decode = |mut bytes: Vec<u8>| {
+            let mut comp = CompressedImage::from_bytes(
+                &mut bytes,
+                sys::uhdr_color_gamut::UHDR_CG_UNSPECIFIED,
+                sys::uhdr_color_transfer::UHDR_CT_UNSPECIFIED,
+                sys::uhdr_color_range::UHDR_CR_UNSPECIFIED,
+            )
+            .unwrap();
+            let mut dec = Decoder::new().unwrap();
+            dec.set_image(&mut comp).unwrap();
+            dec.decode_packed_view(
+                sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA1010102,
+                sys::uhdr_color_transfer::UHDR_CT_PQ,
+            )
+            .unwrap()
+            .to_owned()
+            .unwrap()
+        };
+
+        let plain = encode(false);
+        let optimized = encode(true);
+        assert!(optimized.len() < plain.len());
+        assert_eq!(decode(optimized), decode(plain));
+    }
+
+    #[test]
+    fn encode_without_images_fails_early() {
+        let mut enc = Encoder::new().unwrap();
+        let err = enc.encode().unwrap_err();
+        assert_eq!(err.code, sys::uhdr_codec_err_t::UHDR_CODEC_INVALID_PARAM);
+        assert_eq!(err.detail.as_deref(), Some("no images set"));
+    }
+
+    #[test]
+    fn encode_with_only_sdr_fails_early() {
+        let mut sdr = vec![128u8; (W * H * 4) as usize];
+        let mut sdr_raw = RawImage::rgba8888(
+            W,
+            H,
+            &mut sdr,
+            sys::uhdr_color_gamut::UHDR_CG_BT_709,
+            sys::uhdr_color_transfer::UHDR_CT_SRGB,
+            sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+        )
+        .unwrap();
+        let mut enc = Encoder::new().unwrap();
+        enc.set_raw_image(&mut sdr_raw, ImgLabel::UHDR_SDR_IMG)
+            .unwrap();
+        let err = enc.encode().unwrap_err();
+        assert_eq!(err.detail.as_deref(), Some("no HDR image set"));
+
+        enc.reset();
+        assert_eq!(
+            enc.encode().unwrap_err().detail.as_deref(),
+            Some("no images set")
+        );
+    }
+
+    #[test]
+    fn set_gainmap_image_validates_metadata_and_format() {
+        let mut pixels = vec![128u8; (W * H) as usize];
+        let mut gainmap = RawImage::packed(
+            sys::uhdr_img_fmt::UHDR_IMG_FMT_8bppYCbCr400,
+            W,
+            H,
+            &mut pixels,
+            sys::uhdr_color_gamut::UHDR_CG_UNSPECIFIED,
+            sys::uhdr_color_transfer::UHDR_CT_UNSPECIFIED,
+            sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+        )
+        .unwrap();
+        let mut enc = Encoder::new().unwrap();
+
+        let mut inverted = sample_metadata(4.0);
+        inverted.min_content_boost[1] = 8.0;
+        let err = enc.set_gainmap_image(&mut gainmap, &inverted).unwrap_err();
+        assert!(err.to_string().contains("max_content_boost"), "{err}");
+        let mut nan = sample_metadata(4.0);
+        nan.gamma[2] = f32::NAN;
+        assert!(enc.set_gainmap_image(&mut gainmap, &nan).is_err());
+        enc.set_gainmap_image(&mut gainmap, &sample_metadata(4.0))
+            .unwrap();
+
+        let mut rgb = vec![0u8; (W * H * 4) as usize];
+        let mut hdr = RawImage::packed(
+            sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA1010102,
+            W,
+            H,
+            &mut rgb,
+            sys::uhdr_color_gamut::UHDR_CG_BT_2100,
+            sys::uhdr_color_transfer::UHDR_CT_PQ,
+            sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+        )
+        .unwrap();
+        assert!(
+            enc.set_gainmap_image(&mut hdr, &sample_metadata(4.0))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn hdr_base_encode_writes_backward_gainmap() {
+        let base = jpeg::tests::jpeg(
+            &[jpeg::Segment {
+                marker: 0xC0,
+                data: vec![8, 0, H as u8, 0, W as u8, 1, 1, 0x11, 0],
+            }],
+            &[1, 2, 3],
+        );
+        let mut hdr_px: Vec<u8> = (0..W * H)
+            .flat_map(|i| pack_1010102(i * 4, i * 4, i * 4))
+            .collect();
+        let mut hdr = RawImage::packed(
+            sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA1010102,
+            W,
+            H,
+            &mut hdr_px,
+            sys::uhdr_color_gamut::UHDR_CG_BT_2100,
+            sys::uhdr_color_transfer::UHDR_CT_PQ,
+            sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+        )
+        .unwrap();
+        let mut sdr_px = vec![128u8; (W * H * 4) as usize];
+        let mut sdr = RawImage::rgba8888(
+            W,
+            H,
+            &mut sdr_px,
+            sys::uhdr_color_gamut::UHDR_CG_BT_709,
+            sys::uhdr_color_transfer::UHDR_CT_SRGB,
+            sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+        )
+        .unwrap();
+
+        let mut enc = Encoder::new().unwrap();
+        enc.set_hdr_base(&base, &mut hdr, &mut sdr).unwrap();
+        let out = enc.encode_to_owned().unwrap().data;
+        assert!(out.starts_with(&base[..2]));
+        let gainmap = jpeg::secondary_image(&out, 1).unwrap();
+        let info = JpegInfo::parse(gainmap).unwrap();
+        assert_eq!((info.width, info.height, info.components), (W, H, 1));
+        assert!(metadata::base_rendition_is_hdr(gainmap));
+
+        let mut enc = Encoder::new().unwrap();
+        enc.set_hdr_base(&base, &mut hdr, &mut sdr).unwrap();
+        enc.set_raw_image(&mut sdr, ImgLabel::UHDR_SDR_IMG).unwrap();
+        let err = enc.encode().unwrap_err();
+        assert!(err.to_string().contains("HDR base"), "{err}");
+    }
+
+    #[test]
+    fn content_boost_and_offset_setters_validate() {
+        let mut enc = Encoder::new().unwrap();
+        enc.set_max_content_boost(4.0).unwrap();
+        enc.set_min_content_boost(0.5).unwrap();
+        assert!(enc.set_min_content_boost(8.0).is_err());
+        assert!(enc.set_max_content_boost(0.25).is_err());
+        assert!(enc.set_max_content_boost(f32::INFINITY).is_err());
+        assert!(enc.set_min_content_boost(0.0).is_err());
+        assert_eq!(enc.content_boost, (0.5, 4.0));
+
+        assert!(enc.set_gainmap_offsets(-0.1, 0.0).is_err());
+        assert!(enc.set_gainmap_offsets(0.0, f32::NAN).is_err());
+        enc.set_gainmap_offsets(0.0, 1.0 / 64.0).unwrap();
+        let err = enc.attach_raw_gainmap().unwrap_err();
+        assert!(err.to_string().contains("offsets"), "{err}");
+    }
+
+    #[test]
+    fn write_chunks_splits_output_and_maps_io_errors() {
+        struct Recorder(Vec<usize>);
+        impl Write for Recorder {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.push(buf.len());
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        let bytes = vec![0u8; 2 * WRITE_CHUNK_LEN + 10];
+        let mut rec = Recorder(Vec::new());
+        assert_eq!(write_chunks(&mut rec, &bytes).unwrap(), bytes.len());
+        assert_eq!(rec.0, [WRITE_CHUNK_LEN, WRITE_CHUNK_LEN, 10]);
+
+        let mut full = [0u8; 4];
+        let err = write_chunks(&mut full.as_mut_slice(), &bytes).unwrap_err();
+        assert_eq!(err.code, sys::uhdr_codec_err_t::UHDR_CODEC_UNKNOWN_ERROR);
+        assert!(err.to_string().contains("writing encoded stream"), "{err}");
+    }
+
+    #[test]
+    fn heif_and_avif_output_is_reported_unsupported() {
+        check_output_codec(sys::uhdr_codec::UHDR_CODEC_JPG).unwrap();
+        for codec in [
+            sys::uhdr_codec::UHDR_CODEC_HEIF,
+            sys::uhdr_codec::UHDR_CODEC_AVIF,
+        ] {
+            let err = Encoder::builder()
+                .output_format(codec)
+                .validate()
+                .unwrap_err();
+            assert_eq!(
+                err.code,
+                sys::uhdr_codec_err_t::UHDR_CODEC_UNSUPPORTED_FEATURE
+            );
+        }
+    }
+}
@@ -1,7 +1,14 @@
 use crate::sys;
-use std::error::Error as StdError;
-use std::ffi::CStr;
-use std::fmt;
+use alloc::string::String;
+#[cfg(any(feature = "std", test))]
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use core::error::Error as StdError;
+#[cfg(feature = "std")]
+use core::ffi::CStr;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io;
 
 /// Error produced by the safe wrappers around `libultrahdr`.
 #[derive(Debug, Clone)]
@@ -10,16 +17,44 @@ pub struct Error {
     pub code: sys::uhdr_codec_err_t,
     /// Optional human-readable detail string when provided by the library.
     pub detail: Option<String>,
+    /// Underlying error from outside libultrahdr, exposed through [`StdError::source`]. An
+    /// `Arc` rather than a `Box` so that `Error` stays `Clone`.
+    source: Option<Arc<dyn StdError + Send + Sync>>,
 }
 
 /// Result alias used throughout the crate.
-pub type Result<T> = std::result::Result<T, Error>;
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Semantic category of an [`Error`], see [`Error::kind`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// A rejected argument (`UHDR_CODEC_INVALID_PARAM`). This includes inputs that are not
+    /// UltraHDR at all, e.g. asking a plain JPEG for its gain-map metadata.
+    InvalidParam,
+    /// A call made in the wrong state (`UHDR_CODEC_INVALID_OPERATION`), e.g. configuring a
+    /// codec after it ran without resetting it.
+    InvalidOperation,
+    /// A feature missing from libultrahdr or this build (`UHDR_CODEC_UNSUPPORTED_FEATURE`).
+    Unsupported,
+    /// Memory allocation failed (`UHDR_CODEC_MEM_ERROR`).
+    OutOfMemory,
+    /// The bitstream could not be coded (`UHDR_CODEC_ERROR`), e.g. truncated or corrupt data.
+    Codec,
+    /// An I/O failure outside libultrahdr, built by [`Error::io`]; the `io::Error` is the
+    /// [`source`](StdError::source).
+    Io,
+    /// Anything else (`UHDR_CODEC_UNKNOWN_ERROR` and codes that are not failures).
+    Other,
+}
 
 impl Error {
+    #[cfg(any(feature = "std", test))]
     pub(crate) fn alloc() -> Self {
         Self {
             code: sys::uhdr_codec_err_t::UHDR_CODEC_MEM_ERROR,
             detail: Some("allocation failed".to_string()),
+            source: None,
         }
     }
 
@@ -27,6 +62,95 @@ impl Error {
         Self {
             code: sys::uhdr_codec_err_t::UHDR_CODEC_INVALID_PARAM,
             detail: Some(msg.into()),
+            source: None,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn unsupported(msg: impl Into<String>) -> Self {
+        Self {
+            code: sys::uhdr_codec_err_t::UHDR_CODEC_UNSUPPORTED_FEATURE,
+            detail: Some(msg.into()),
+            source: None,
+        }
+    }
+
+    /// Wrap an I/O failure outside libultrahdr, e.g. reading an input file or writing output.
+    ///
+    /// libultrahdr has no I/O code, so this uses `UHDR_CODEC_UNKNOWN_ERROR` with the
+    /// message as detail; `err` itself is kept as the [`source`](StdError::source) and
+    /// [`kind`](Self::kind) reports [`ErrorKind::Io`].
+    #[cfg(feature = "std")]
+    pub fn io(err: io::Error) -> Self {
+        Self {
+            code: sys::uhdr_codec_err_t::UHDR_CODEC_UNKNOWN_ERROR,
+            detail: Some(err.to_string()),
+            source: Some(Arc::new(err)),
+        }
+    }
+
+    /// [`io`](Self::io) with `context` prefixed to the detail.
+    #[cfg(feature = "std")]
+    pub(crate) fn io_context(context: impl fmt::Display, err: io::Error) -> Self {
+        let mut wrapped = Self::io(err);
+        wrapped.detail = wrapped.detail.map(|msg| format!("{context}: {msg}"));
+        wrapped
+    }
+
+    /// Map [`code`](Self::code) to a semantic category.
+    pub fn kind(&self) -> ErrorKind {
+        use sys::uhdr_codec_err_t as E;
+        #[cfg(feature = "std")]
+        if self
+            .source
+            .as_ref()
+            .is_some_and(|src| src.is::<io::Error>())
+        {
+            return ErrorKind::Io;
+        }
+        match self.code {
+            E::UHDR_CODEC_INVALID_PARAM => ErrorKind::InvalidParam,
+            E::UHDR_CODEC_INVALID_OPERATION => ErrorKind::InvalidOperation,
+            E::UHDR_CODEC_UNSUPPORTED_FEATURE => ErrorKind::Unsupported,
+            E::UHDR_CODEC_MEM_ERROR => ErrorKind::OutOfMemory,
+            E::UHDR_CODEC_ERROR => ErrorKind::Codec,
+            E::UHDR_CODEC_UNKNOWN_ERROR | E::UHDR_CODEC_OK | E::UHDR_CODEC_LIST_END => {
+                ErrorKind::Other
+            }
+        }
+    }
+
+    /// Whether an argument was rejected, see [`ErrorKind::InvalidParam`].
+    pub fn is_invalid_param(&self) -> bool {
+        self.kind() == ErrorKind::InvalidParam
+    }
+
+    /// Whether memory allocation failed, see [`ErrorKind::OutOfMemory`].
+    pub fn is_mem_error(&self) -> bool {
+        self.kind() == ErrorKind::OutOfMemory
+    }
+
+    /// Whether a feature is unavailable, see [`ErrorKind::Unsupported`].
+    pub fn is_unsupported(&self) -> bool {
+        self.kind() == ErrorKind::Unsupported
+    }
+
+    /// Whether repeating the same call could plausibly succeed.
+    ///
+    /// Only memory exhaustion is treated as transient: it depends on load, not on the input.
+    /// Every other code is a deterministic property of the input or call sequence.
+    pub fn is_retryable(&self) -> bool {
+        use sys::uhdr_codec_err_t as E;
+        match self.code {
+            // Allocation failure under memory pressure; may succeed once load drops.
+            E::UHDR_CODEC_MEM_ERROR => true,
+            // Bad arguments, bad call order, or a feature missing from this build.
+            E::UHDR_CODEC_INVALID_PARAM
+            | E::UHDR_CODEC_INVALID_OPERATION
+            | E::UHDR_CODEC_UNSUPPORTED_FEATURE => false,
+            // Codec errors come from malformed or unsupported bitstreams.
+            E::UHDR_CODEC_ERROR | E::UHDR_CODEC_UNKNOWN_ERROR => false,
+            E::UHDR_CODEC_OK | E::UHDR_CODEC_LIST_END => false,
         }
     }
 }
@@ -41,8 +165,13 @@ impl fmt::Display for Error {
     }
 }
 
-impl StdError for Error {}
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.source.as_deref().map(|src| src as _)
+    }
+}
 
+#[cfg(feature = "std")]
 pub(crate) fn check(info: sys::uhdr_error_info_t) -> Result<()> {
     if info.error_code == sys::uhdr_codec_err_t::UHDR_CODEC_OK {
         return Ok(());
@@ -59,5 +188,61 @@ pub(crate) fn check(info: sys::uhdr_error_info_t) -> Result<()> {
     Err(Error {
         code: info.error_code,
         detail,
+        source: None,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_memory_errors_are_retryable() {
+        assert!(Error::alloc().is_retryable());
+        assert!(!Error::invalid_param("bad").is_retryable());
+        let codec = Error {
+            code: sys::uhdr_codec_err_t::UHDR_CODEC_ERROR,
+            detail: None,
+            source: None,
+        };
+        assert!(!codec.is_retryable());
+    }
+
+    #[test]
+    fn kind_maps_codes_to_categories() {
+        use sys::uhdr_codec_err_t as E;
+        let err = |code| Error {
+            code,
+            detail: None,
+            source: None,
+        };
+        assert!(Error::invalid_param("bad").is_invalid_param());
+        assert!(Error::alloc().is_mem_error());
+        assert!(err(E::UHDR_CODEC_UNSUPPORTED_FEATURE).is_unsupported());
+        assert_eq!(err(E::UHDR_CODEC_ERROR).kind(), ErrorKind::Codec);
+        assert_eq!(
+            err(E::UHDR_CODEC_INVALID_OPERATION).kind(),
+            ErrorKind::InvalidOperation
+        );
+        assert_eq!(err(E::UHDR_CODEC_UNKNOWN_ERROR).kind(), ErrorKind::Other);
+        assert!(!Error::alloc().is_invalid_param());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn io_errors_keep_their_source() {
+        let err = Error::io_context(
+            "reading input",
+            io::Error::new(io::ErrorKind::NotFound, "gone"),
+        );
+        assert_eq!(err.kind(), ErrorKind::Io);
+        assert_eq!(err.detail.as_deref(), Some("reading input: gone"));
+        let src = StdError::source(&err.clone()).unwrap().to_string();
+        assert_eq!(src, "gone");
+        let io_err = StdError::source(&err)
+            .and_then(|src| src.downcast_ref::<io::Error>())
+            .unwrap();
+        assert_eq!(io_err.kind(), io::ErrorKind::NotFound);
+        assert!(StdError::source(&Error::alloc()).is_none());
+    }
+}
@@ -0,0 +1,242 @@
+//! Minimal EXIF (APP1) handling: just enough TIFF to read and write the Orientation tag,
+//! plus dropping the EXIF block from an UltraHDR primary image.
+
+use crate::error::{Error, Result};
+use crate::jpeg::{self, Segment};
+use alloc::vec::Vec;
+
+/// Prefix of the APP1 payload carrying EXIF.
+pub(crate) const EXIF_SIGNATURE: &[u8] = b"Exif\0\0";
+
+#[cfg(any(feature = "std", test))]
+const TAG_ORIENTATION: u16 = 0x0112;
+#[cfg(any(feature = "std", test))]
+const TYPE_SHORT: u16 = 3;
+
+/// Reject orientation values outside the eight EXIF orientations.
+pub(crate) fn check_orientation(orientation: u8) -> Result<()> {
+    if (1..=8).contains(&orientation) {
+        Ok(())
+    } else {
+        Err(Error::invalid_param("EXIF orientation must be in 1..=8"))
+    }
+}
+
+/// A complete EXIF APP1 payload whose IFD0 holds only the Orientation tag.
+#[cfg(any(feature = "std", test))]
+pub(crate) fn orientation_payload(orientation: u8) -> Vec<u8> {
+    let mut out = EXIF_SIGNATURE.to_vec();
+    // Big-endian TIFF header, IFD0 right after it.
+    out.extend_from_slice(&[b'M', b'M', 0x00, 0x2A]);
+    out.extend_from_slice(&8u32.to_be_bytes());
+    out.extend_from_slice(&1u16.to_be_bytes());
+    out.extend_from_slice(&TAG_ORIENTATION.to_be_bytes());
+    out.extend_from_slice(&TYPE_SHORT.to_be_bytes());
+    out.extend_from_slice(&1u32.to_be_bytes());
+    // SHORT values are left-justified in the 4-byte value field.
+    out.extend_from_slice(&[0, orientation, 0, 0]);
+    // No next IFD.
+    out.extend_from_slice(&0u32.to_be_bytes());
+    out
+}
+
+/// Overwrite the Orientation tag in IFD0 of an EXIF APP1 payload.
+///
+/// Returns `false` when the payload is not EXIF or IFD0 has no Orientation entry; adding
+/// an entry would mean relocating every offset in the block.
+#[cfg(any(feature = "std", test))]
+pub(crate) fn patch_orientation(payload: &mut [u8], orientation: u8) -> bool {
+    let Some(tiff) = payload.strip_prefix(EXIF_SIGNATURE) else {
+        return false;
+    };
+    let tiff_at = EXIF_SIGNATURE.len();
+    let big_endian = match tiff.get(..2) {
+        Some(b"MM") => true,
+        Some(b"II") => false,
+        _ => return false,
+    };
+    let u16_at = |buf: &[u8], at: usize| {
+        buf.get(at..at + 2).map(|b| {
+            let b = [b[0], b[1]];
+            if big_endian {
+                u16::from_be_bytes(b)
+            } else {
+                u16::from_le_bytes(b)
+            }
+        })
+    };
+    let Some(ifd) = tiff.get(4..8).map(|b| {
+        let b = [b[0], b[1], b[2], b[3]];
+        if big_endian {
+            u32::from_be_bytes(b)
+        } else {
+            u32::from_le_bytes(b)
+        }
+    }) else {
+        return false;
+    };
+    let ifd = ifd as usize;
+    let Some(count) = u16_at(tiff, ifd) else {
+        return false;
+    };
+    for i in 0..count as usize {
+        let entry = ifd + 2 + 12 * i;
+        if u16_at(tiff, entry) == Some(TAG_ORIENTATION)
+            && u16_at(tiff, entry + 2) == Some(TYPE_SHORT)
+        {
+            let value_at = tiff_at + entry + 8;
+            let Some(value) = payload.get_mut(value_at..value_at + 2) else {
+                return false;
+            };
+            let bytes = if big_endian {
+                u16::from(orientation).to_be_bytes()
+            } else {
+                u16::from(orientation).to_le_bytes()
+            };
+            value.copy_from_slice(&bytes);
+            return true;
+        }
+    }
+    false
+}
+
+/// Set the orientation of a primary image header, adding a minimal EXIF block if it has none.
+#[cfg(any(feature = "std", test))]
+pub(crate) fn apply_orientation(segments: &mut Vec<Segment>, orientation: u8) -> Result<()> {
+    if let Some(seg) = segments.iter_mut().find(|s| is_exif(s)) {
+        if !patch_orientation(&mut seg.data, orientation) {
+            return Err(Error::invalid_param(
+                "existing EXIF block has no Orientation tag to update",
+            ));
+        }
+        return Ok(());
+    }
+    // EXIF goes first, after a JFIF APP0 if there is one.
+    let at = segments
+        .iter()
+        .position(|s| s.marker != jpeg::APP0)
+        .unwrap_or(segments.len());
+    segments.insert(
+        at,
+        Segment {
+            marker: jpeg::APP1,
+            data: orientation_payload(orientation),
+        },
+    );
+    Ok(())
+}
+
+/// Drop EXIF (and with it any GPS IFD) from the primary image of a JPEG.
+///
+/// XMP, ICC, the gain-map metadata and the MPF index are kept. Secondary images such as
+/// the gain map are carried through verbatim and their MPF entries are patched in place,
+/// in the index's own byte order, so they stay reachable after the primary image shrinks.
+/// Streams without EXIF are returned unchanged.
+pub fn strip_exif(stream: &[u8]) -> Result<Vec<u8>> {
+    let (segments, _) = jpeg::parse_header(stream)?;
+    if !segments.iter().any(is_exif) {
+        return Ok(stream.to_vec());
+    }
+    jpeg::rewrite_primary(stream, |segments| {
+        segments.retain(|s| !is_exif(s));
+        Ok(())
+    })
+}
+
+fn is_exif(seg: &Segment) -> bool {
+    seg.marker == jpeg::APP1 && seg.data.starts_with(EXIF_SIGNATURE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jpeg::tests::{secondary_via_mpf, ultrahdr_like, ultrahdr_like_with};
+
+    #[test]
+    fn generated_block_round_trips_through_patch() {
+        let mut payload = orientation_payload(1);
+        assert!(patch_orientation(&mut payload, 6));
+        assert_eq!(payload, orientation_payload(6));
+    }
+
+    #[test]
+    fn patches_little_endian_blocks() {
+        let mut payload = EXIF_SIGNATURE.to_vec();
+        payload.extend_from_slice(&[b'I', b'I', 0x2A, 0x00, 8, 0, 0, 0]);
+        payload.extend_from_slice(&2u16.to_le_bytes());
+        // Make (ASCII) then Orientation (SHORT) = 1.
+        payload.extend_from_slice(&[0x0F, 0x01, 2, 0, 4, 0, 0, 0, b'a', b'b', b'c', 0]);
+        payload.extend_from_slice(&[0x12, 0x01, 3, 0, 1, 0, 0, 0, 1, 0, 0, 0]);
+        payload.extend_from_slice(&[0, 0, 0, 0]);
+        assert!(patch_orientation(&mut payload, 8));
+        assert_eq!(&payload[payload.len() - 8..payload.len() - 6], &[8, 0]);
+    }
+
+    #[test]
+    fn apply_orientation_inserts_after_jfif() {
+        let mut segments = vec![
+            Segment {
+                marker: jpeg::APP0,
+                data: b"JFIF\0".to_vec(),
+            },
+            Segment {
+                marker: 0xDB,
+                data: vec![0; 5],
+            },
+        ];
+        apply_orientation(&mut segments, 3).unwrap();
+        assert_eq!(segments[1].marker, jpeg::APP1);
+        assert_eq!(segments[1].data, orientation_payload(3));
+
+        // A second call patches the block instead of adding another.
+        apply_orientation(&mut segments, 5).unwrap();
+        assert_eq!(segments.len(), 3);
+        assert_eq!(segments[1].data, orientation_payload(5));
+    }
+
+    #[test]
+    fn orientation_range_is_checked() {
+        assert!(check_orientation(0).is_err());
+        assert!(check_orientation(9).is_err());
+        assert!((1..=8).all(|o| check_orientation(o).is_ok()));
+    }
+
+    #[test]
+    fn strip_exif_keeps_gainmap_reachable() {
+        let exif = Segment {
+            marker: jpeg::APP1,
+            data: orientation_payload(6),
+        };
+        let (stream, secondary) = ultrahdr_like_with(&[exif], &[]);
+        let out = strip_exif(&stream).unwrap();
+        assert!(jpeg::extract_app_segments(&out, jpeg::APP1).is_empty());
+        assert_eq!(secondary_via_mpf(&out), secondary);
+        assert!(out.len() < stream.len());
+
+        let (plain, _) = ultrahdr_like(&[]);
+        assert_eq!(strip_exif(&plain).unwrap(), plain);
+    }
+
+    #[test]
+    fn strip_exif_ignores_mpf_signature_inside_other_segments() {
+        // An `MPF\0` byte run ahead of the real index must not be taken as its TIFF base.
+        let mut exif = orientation_payload(1);
+        exif.extend_from_slice(b"MPF\0MM\0*");
+        let mut xmp = b"http://ns.adobe.com/xap/1.0/\0".to_vec();
+        xmp.extend_from_slice(b"<x:comment>MPF\0</x:comment>");
+        let extra = [
+            Segment {
+                marker: jpeg::APP1,
+                data: exif,
+            },
+            Segment {
+                marker: jpeg::APP1,
+                data: xmp,
+            },
+        ];
+        let (stream, secondary) = ultrahdr_like_with(&extra, &[]);
+        let out = strip_exif(&stream).unwrap();
+        assert_eq!(jpeg::extract_app_segments(&out, jpeg::APP1).len(), 1);
+        assert_eq!(secondary_via_mpf(&out), secondary);
+    }
+}
@@ -0,0 +1,384 @@
+//! Gain-map editing and container assembly for UltraHDR JPEGs, done on the Rust side where
+//! libultrahdr has no entry point.
+
+use crate::error::{Error, Result};
+use crate::jpeg::{self, JpegInfo};
+use crate::metadata;
+use crate::sys;
+use crate::types::{
+    ChromaSubsampling, DecodedPacked, GainMapMetadata, RawImage, SDR_WHITE_NITS, copy_raw_packed,
+};
+use jpeg_encoder::{ColorType, SamplingFactor};
+
+/// libultrahdr's default SDR and HDR offsets, 1/64 of SDR white.
+pub(crate) const DEFAULT_OFFSET: f32 = 1.0 / 64.0;
+
+/// Replace the gain map of an UltraHDR JPEG with edited pixels.
+///
+/// Together with [`Decoder::decode_gainmap_view`](crate::Decoder::decode_gainmap_view) this
+/// completes an extract, edit, re-insert round trip.
+///
+/// `edited_gm` is compressed at `gm_quality` (1..=100) and must have the dimensions of the
+/// gain map it replaces; use `UHDR_IMG_FMT_8bppYCbCr400` for a single-channel map or
+/// `UHDR_IMG_FMT_32bppRGBA8888` (alpha ignored) for a multi-channel one. The base image is
+/// kept verbatim. The gain-map image carries `meta` in whichever of ISO 21496-1 and XMP the
+/// original used, and the MPF index plus the primary XMP `Container` length are updated.
+///
+/// libultrahdr only compresses gain maps it computes itself, so the edited map is coded as
+/// a baseline JPEG on the Rust side, without chroma subsampling.
+pub fn replace_gainmap(
+    uhdr: &[u8],
+    edited_gm: &mut RawImage<'_>,
+    meta: &GainMapMetadata,
+    gm_quality: i32,
+) -> Result<Vec<u8>> {
+    meta.validate()?;
+    check_pixels(&edited_gm.inner)?;
+    let original = jpeg::secondary_image(uhdr, 1)?;
+    let expected = JpegInfo::parse(original)?;
+    let (width, height) = (edited_gm.width(), edited_gm.height());
+    if (width, height) != (expected.width, expected.height) {
+        return Err(Error::invalid_param(format!(
+            "edited gain map is {width}x{height}, expected {}x{}",
+            expected.width, expected.height
+        )));
+    }
+
+    let (mut meta_segments, _) = jpeg::parse_header(original)?;
+    meta_segments.retain(|s| metadata::is_iso_segment(s) || metadata::is_xmp_segment(s));
+    if !metadata::regenerate(&mut meta_segments, meta) {
+        return Err(Error::invalid_param("gain map image carries no metadata"));
+    }
+
+    let compressed = compress(&edited_gm.inner, gm_quality, ChromaSubsampling::Yuv444)?;
+    let (mut segments, scan_at) = jpeg::parse_header(&compressed)?;
+    for seg in meta_segments {
+        if metadata::is_xmp_segment(&seg) {
+            metadata::insert_xmp(&mut segments, seg.data);
+        } else {
+            jpeg::insert_app_segment(&mut segments, seg.marker, seg.data);
+        }
+    }
+    let image = jpeg::serialize(&segments, &compressed[scan_at..])?;
+    metadata::splice_gainmap(uhdr, image)
+}
+
+/// Reject gain-map pixels [`compress`] cannot code.
+pub(crate) fn check_pixels(img: &sys::uhdr_raw_image) -> Result<()> {
+    match img.fmt {
+        sys::uhdr_img_fmt::UHDR_IMG_FMT_8bppYCbCr400
+        | sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA8888 => {}
+        _ => {
+            return Err(Error::invalid_param(
+                "gain map must be 8bppYCbCr400 or 32bppRGBA8888",
+            ));
+        }
+    }
+    if img.w > u32::from(u16::MAX) || img.h > u32::from(u16::MAX) {
+        return Err(Error::invalid_param("gain map exceeds JPEG dimensions"));
+    }
+    Ok(())
+}
+
+/// Code gain-map pixels as a baseline JPEG. `subsampling` only affects multi-channel maps.
+pub(crate) fn compress(
+    img: &sys::uhdr_raw_image,
+    quality: i32,
+    subsampling: ChromaSubsampling,
+) -> Result<Vec<u8>> {
+    check_pixels(img)?;
+    let quality = u8::try_from(quality)
+        .ok()
+        .filter(|q| (1..=100).contains(q))
+        .ok_or_else(|| Error::invalid_param("gain map quality must be in 1..=100"))?;
+    let color = if img.fmt == sys::uhdr_img_fmt::UHDR_IMG_FMT_8bppYCbCr400 {
+        ColorType::Luma
+    } else {
+        ColorType::Rgba
+    };
+    let pixels = copy_raw_packed(img)?;
+    let mut compressed = Vec::new();
+    let mut encoder = jpeg_encoder::Encoder::new(&mut compressed, quality);
+    encoder.set_sampling_factor(match subsampling {
+        ChromaSubsampling::Yuv444 => SamplingFactor::F_1_1,
+        ChromaSubsampling::Yuv422 => SamplingFactor::F_2_1,
+        ChromaSubsampling::Yuv420 => SamplingFactor::F_2_2,
+    });
+    // check_pixels bounds both dimensions to u16.
+    encoder
+        .encode(&pixels, img.w as u16, img.h as u16, color)
+        .map_err(|e| Error::invalid_param(format!("gain map compression failed: {e}")))?;
+    Ok(compressed)
+}
+
+/// Compute a single-channel gain map from the HDR and SDR renditions of one image.
+///
+/// Each gain is `log2` of the HDR-to-SDR luminance ratio, both taken relative to SDR white
+/// and lifted by `offsets` (`(sdr, hdr)`). The gains are normalized to the `(min, max)`
+/// content boost in `content_boost`, or to the range found in the content when `None`, and
+/// quantized to 8 bits with gamma 1. With `base_rendition_is_hdr` the stored map is
+/// inverted, so it encodes the HDR-to-SDR gain over the negated range that
+/// [`iso_payload`](metadata::iso_payload) writes for an HDR base. Both images must have the
+/// same dimensions and a format/transfer pair accepted by
+/// [`luminance_stats`](DecodedPacked::luminance_stats).
+pub(crate) fn compute(
+    hdr: &DecodedPacked,
+    sdr: &DecodedPacked,
+    content_boost: Option<(f32, f32)>,
+    offsets: (f32, f32),
+    base_rendition_is_hdr: bool,
+) -> Result<(Vec<u8>, GainMapMetadata)> {
+    if (hdr.width, hdr.height) != (sdr.width, sdr.height) {
+        return Err(Error::invalid_param(format!(
+            "HDR image is {}x{} but SDR image is {}x{}",
+            hdr.width, hdr.height, sdr.width, sdr.height
+        )));
+    }
+    let (offset_sdr, offset_hdr) = offsets;
+    let gains: Vec<f32> = hdr
+        .pixel_nits()?
+        .zip(sdr.pixel_nits()?)
+        .map(|((hdr, _), (sdr, _))| {
+            let hdr = hdr / SDR_WHITE_NITS + offset_hdr;
+            let sdr = sdr / SDR_WHITE_NITS + offset_sdr;
+            (hdr.max(f32::MIN_POSITIVE) / sdr.max(f32::MIN_POSITIVE)).log2()
+        })
+        .collect();
+
+    let (min_log, max_log) = match content_boost {
+        Some((min, max)) => (min.log2(), max.log2()),
+        None => gains
+            .iter()
+            .fold((0f32, 0f32), |(lo, hi), &g| (lo.min(g), hi.max(g))),
+    };
+    // A flat map still needs a non-empty range to normalize against.
+    let max_log = max_log.max(min_log + 1.0 / 256.0);
+    let range = max_log - min_log;
+    let pixels = gains
+        .iter()
+        .map(|&g| {
+            let t = if base_rendition_is_hdr {
+                max_log - g
+            } else {
+                g - min_log
+            };
+            ((t / range).clamp(0.0, 1.0) * 255.0).round() as u8
+        })
+        .collect();
+
+    let meta = GainMapMetadata {
+        max_content_boost: [max_log.exp2(); 3],
+        min_content_boost: [min_log.exp2(); 3],
+        gamma: [1.0; 3],
+        offset_sdr: [offset_sdr; 3],
+        offset_hdr: [offset_hdr; 3],
+        hdr_capacity_min: 1.0,
+        hdr_capacity_max: max_log.exp2().max(1.0),
+        use_base_cg: true,
+        base_rendition_is_hdr,
+    };
+    meta.validate()?;
+    Ok((pixels, meta))
+}
+
+/// Assemble a gain-map JPEG from an already compressed base image and gain-map image.
+///
+/// This is the route for HDR-base files (`meta.base_rendition_is_hdr`), where `base` is the
+/// HDR rendition and the gain map derives the SDR one. libultrahdr has no entry point for
+/// that layout: its encoder only computes SDR-to-HDR gain maps and its C metadata struct
+/// has no direction flag. [`Encoder::set_hdr_base`](crate::Encoder::set_hdr_base) computes
+/// the gain map from the two renditions and assembles the file through this function;
+/// call it directly when both images are already compressed. SDR-base metadata is
+/// accepted as well.
+///
+/// Any gain-map segments already present are replaced. The gain-map image gets `meta` as
+/// both ISO 21496-1 (with the backward-direction flag for an HDR base) and `hdrgm` XMP
+/// (`BaseRenditionIsHDR`); the base gets the ISO version block, the XMP `Container`
+/// directory and an MPF index. Other segments and all entropy-coded data are kept as is.
+/// libultrahdr itself decodes only SDR-base files.
+pub fn assemble_gainmap_jpeg(
+    base: &[u8],
+    gainmap: &[u8],
+    meta: &GainMapMetadata,
+) -> Result<Vec<u8>> {
+    meta.validate()?;
+    let (mut gm_segments, gm_scan_at) = jpeg::parse_header(gainmap)?;
+    gm_segments.retain(|s| !metadata::is_iso_segment(s) && !metadata::is_xmp_segment(s));
+    metadata::insert_xmp(&mut gm_segments, metadata::xmp_payload(meta));
+    jpeg::insert_app_segment(&mut gm_segments, jpeg::APP2, metadata::iso_payload(meta));
+    let gainmap = jpeg::serialize(&gm_segments, &gainmap[gm_scan_at..])?;
+
+    let (mut segments, scan_at) = jpeg::parse_header(base)?;
+    metadata::strip_gainmap_segments(&mut segments);
+    metadata::insert_xmp(&mut segments, metadata::primary_xmp_payload(gainmap.len()));
+    jpeg::insert_app_segment(&mut segments, jpeg::APP2, metadata::iso_version_payload());
+    jpeg::with_secondary(&segments, &base[scan_at..], &gainmap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jpeg::Segment;
+    use crate::jpeg::tests::{baseline_sof, secondary_via_mpf, ultrahdr_like_with};
+    use crate::types::tests::sample_metadata;
+
+    fn stream() -> (Vec<u8>, Vec<u8>) {
+        let iso = Segment {
+            marker: jpeg::APP2,
+            data: metadata::iso_payload(&sample_metadata(2.0)),
+        };
+        ultrahdr_like_with(&[], &[iso, baseline_sof(8, 4, 1)])
+    }
+
+    fn luma(pixels: &mut [u8], width: u32, height: u32) -> RawImage<'_> {
+        RawImage::packed(
+            sys::uhdr_img_fmt::UHDR_IMG_FMT_8bppYCbCr400,
+            width,
+            height,
+            pixels,
+            sys::uhdr_color_gamut::UHDR_CG_UNSPECIFIED,
+            sys::uhdr_color_transfer::UHDR_CT_UNSPECIFIED,
+            sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn replaced_gainmap_is_reachable_with_new_metadata() {
+        let (stream, secondary) = stream();
+        let mut pixels: Vec<u8> = (0..32).map(|i| i * 8).collect();
+        let out = replace_gainmap(
+            &stream,
+            &mut luma(&mut pixels, 8, 4),
+            &sample_metadata(4.0),
+            90,
+        )
+        .unwrap();
+
+        // Only the MPF entry sizes change in the primary image.
+        let primary = jpeg::primary_image(&out).unwrap();
+        assert_eq!(primary.len(), stream.len() - secondary.len());
+        assert!(primary.ends_with(&[1, 2, 3, 4, 0xFF, jpeg::EOI]));
+        let gainmap = secondary_via_mpf(&out);
+        let info = JpegInfo::parse(gainmap).unwrap();
+        assert_eq!((info.width, info.height, info.components), (8, 4, 1));
+        let (segments, _) = jpeg::parse_header(gainmap).unwrap();
+        let iso: Vec<_> = segments
+            .iter()
+            .filter(|s| metadata::is_iso_segment(s))
+            .collect();
+        assert_eq!(iso.len(), 1);
+        assert_eq!(iso[0].data, metadata::iso_payload(&sample_metadata(4.0)));
+        assert!(!segments.iter().any(metadata::is_xmp_segment));
+    }
+
+    #[test]
+    fn assembles_hdr_base_with_backward_metadata() {
+        let base = jpeg::tests::jpeg(&[baseline_sof(8, 4, 1)], &[1, 2, 3]);
+        let gainmap = jpeg::tests::jpeg(&[baseline_sof(4, 2, 1)], &[7, 7]);
+        let mut hdr_base = sample_metadata(4.0);
+        hdr_base.base_rendition_is_hdr = true;
+        hdr_base.offset_hdr = [1.0 / 32.0; 3];
+        let out = assemble_gainmap_jpeg(&base, &gainmap, &hdr_base).unwrap();
+
+        let (segments, _) = jpeg::parse_header(secondary_via_mpf(&out)).unwrap();
+        let iso = segments
+            .iter()
+            .find(|s| metadata::is_iso_segment(s))
+            .unwrap();
+        assert_eq!(iso.data, metadata::iso_payload(&hdr_base));
+
+        // Fractions follow the namespace, both versions and the flags byte. Every field is
+        // relative to the HDR base: headrooms and offsets swap, gains negate and swap.
+        let fields = &iso.data[metadata::ISO_NAMESPACE.len() + 5..];
+        let field = |i: usize| {
+            let at = &fields[i * 8..];
+            let n = i32::from_be_bytes(at[..4].try_into().unwrap());
+            let d = u32::from_be_bytes(at[4..8].try_into().unwrap());
+            n as f32 / d as f32
+        };
+        let decoded: Vec<f32> = (0..7).map(field).collect();
+        // base/alternate headroom, gain min/max, gamma, base/alternate offset
+        assert_eq!(decoded, [2.0, 0.0, -2.0, 0.0, 1.0, 1.0 / 32.0, 1.0 / 64.0]);
+        assert_eq!(fields.len(), 7 * 8);
+        let report = crate::check_conformance(&out);
+        for name in ["mpf", "mpf-offsets", "gainmap-image", "metadata-carriage"] {
+            let check = report.check(name).unwrap();
+            assert_eq!(check.status, crate::CheckStatus::Pass, "{check:?}");
+        }
+    }
+
+    #[test]
+    fn computed_gains_span_the_content_boost_range() {
+        // Linear half-float at SDR white and at 4x SDR white, alpha 1.0.
+        let half = |v: u16| [v, v, v, 0x3C00].map(u16::to_le_bytes).concat();
+        let hdr = DecodedPacked {
+            fmt: sys::uhdr_img_fmt::UHDR_IMG_FMT_64bppRGBAHalfFloat,
+            cg: sys::uhdr_color_gamut::UHDR_CG_BT_709,
+            ct: sys::uhdr_color_transfer::UHDR_CT_LINEAR,
+            range: sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+            width: 2,
+            height: 1,
+            data: [half(0x3C00), half(0x4400)].concat(),
+        };
+        let sdr = DecodedPacked {
+            fmt: sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA8888,
+            ct: sys::uhdr_color_transfer::UHDR_CT_SRGB,
+            data: vec![255; 8],
+            ..hdr.clone()
+        };
+
+        let (pixels, meta) = compute(&hdr, &sdr, None, (0.0, 0.0), true).unwrap();
+        assert_eq!(pixels, [255, 0]);
+        assert!(meta.base_rendition_is_hdr);
+        assert_eq!(meta.min_content_boost, [1.0; 3]);
+        assert!((meta.max_content_boost[0] - 4.0).abs() < 1e-3);
+        assert_eq!(meta.hdr_capacity_max, meta.max_content_boost[0]);
+
+        let (pixels, meta) = compute(&hdr, &sdr, Some((1.0, 2.0)), (0.0, 0.0), false).unwrap();
+        assert_eq!(pixels, [0, 255]);
+        assert_eq!(meta.max_content_boost, [2.0; 3]);
+
+        let narrow = DecodedPacked { width: 1, ..sdr };
+        assert!(compute(&hdr, &narrow, None, (0.0, 0.0), true).is_err());
+    }
+
+    #[test]
+    fn mismatched_dimensions_are_rejected() {
+        let (stream, _) = stream();
+        let mut pixels = vec![0; 16];
+        let err = replace_gainmap(
+            &stream,
+            &mut luma(&mut pixels, 4, 4),
+            &sample_metadata(4.0),
+            90,
+        )
+        .unwrap_err();
+        assert_eq!(err.code, sys::uhdr_codec_err_t::UHDR_CODEC_INVALID_PARAM);
+        assert!(err.to_string().contains("expected 8x4"), "{err}");
+    }
+
+    #[test]
+    fn compress_applies_chroma_subsampling_to_rgba_maps() {
+        let mut pixels = vec![200u8; 16 * 16 * 4];
+        let rgba = RawImage::rgba8888(
+            16,
+            16,
+            &mut pixels,
+            sys::uhdr_color_gamut::UHDR_CG_UNSPECIFIED,
+            sys::uhdr_color_transfer::UHDR_CT_UNSPECIFIED,
+            sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+        )
+        .unwrap();
+        let luma_sampling = |subsampling| {
+            let out = compress(&rgba.inner, 90, subsampling).unwrap();
+            let (segments, _) = jpeg::parse_header(&out).unwrap();
+            let sof = segments.iter().find(|s| jpeg::is_sof(s.marker)).unwrap();
+            assert_eq!(sof.data[5], 3);
+            sof.data[7]
+        };
+        assert_eq!(luma_sampling(ChromaSubsampling::Yuv444), 0x11);
+        assert_eq!(luma_sampling(ChromaSubsampling::Yuv422), 0x21);
+        assert_eq!(luma_sampling(ChromaSubsampling::Yuv420), 0x22);
+    }
+}
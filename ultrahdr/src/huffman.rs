@@ -0,0 +1,769 @@
+//! Lossless Huffman-table optimization for baseline JPEGs.
+//!
+//! libultrahdr compresses through libjpeg with the standard Annex K tables and offers no
+//! switch for `optimize_coding`. [`optimize`] entropy-decodes a scan into its Huffman
+//! symbols, builds optimal tables from their frequencies (the libjpeg algorithm) and
+//! re-encodes. DCT coefficients are never touched, so pixels decode identically.
+//!
+//! Only single-scan, 8-bit sequential Huffman frames (SOF0/SOF1) are handled; anything
+//! else is reported as `None` and left to the caller to keep as is.
+
+use crate::error::{Error, Result};
+use crate::jpeg::{self, DRI, EOI, SOS, Segment};
+use crate::metadata;
+
+const DHT: u8 = 0xC4;
+/// Table slots: DC tables 0-3 followed by AC tables 0-3.
+const SLOTS: usize = 8;
+
+/// Optimize the primary image and gain map of an (UltraHDR) JPEG stream.
+///
+/// Returns `None` when no image could be made smaller. The MPF index and the primary XMP
+/// `Container` length of the gain map are kept in sync.
+pub(crate) fn optimize_stream(stream: &[u8]) -> Result<Option<Vec<u8>>> {
+    let mut current = None;
+    if let Ok(gainmap) = jpeg::secondary_image(stream, 1)
+        && let Some(image) = optimize(gainmap)?
+    {
+        let image_len = image.len();
+        current = Some(jpeg::replace_secondary(stream, 1, image, |primary| {
+            for seg in primary.iter_mut() {
+                if seg.marker == jpeg::APP1 && seg.data.starts_with(metadata::XMP_NAMESPACE) {
+                    metadata::set_container_item_length(&mut seg.data, "GainMap", image_len);
+                }
+            }
+            Ok(())
+        })?);
+    }
+    let src = current.as_deref().unwrap_or(stream);
+    if let Some(image) = optimize(jpeg::primary_image(src)?)? {
+        current = Some(jpeg::replace_primary(src, image)?);
+    }
+    Ok(current)
+}
+
+/// Re-encode a single JPEG with optimal Huffman tables.
+///
+/// Returns `None` for layouts this module does not handle or when the result would not be
+/// smaller than the input.
+pub(crate) fn optimize(image: &[u8]) -> Result<Option<Vec<u8>>> {
+    let (mut segments, scan_at) = jpeg::parse_header(image)?;
+    let Some(frame) = Frame::parse(&segments) else {
+        return Ok(None);
+    };
+    let Some((sos, scan)) = Scan::parse(image, scan_at, &frame, &segments)? else {
+        return Ok(None);
+    };
+
+    let mut tables: [Option<DecodeTable>; SLOTS] = Default::default();
+    for seg in segments.iter().filter(|s| s.marker == DHT) {
+        for (slot, table) in parse_dht(&seg.data)? {
+            tables[slot] = Some(DecodeTable::new(&table)?);
+        }
+    }
+    let data_at = scan_at + sos.len();
+
+    let mut freq = [[0u32; 256]; SLOTS];
+    let consumed = walk_scan(&image[data_at..], &scan, &tables, |event| {
+        if let Event::Symbol { slot, symbol, .. } = event {
+            freq[slot][symbol as usize] += 1;
+        }
+    })?;
+    if image.get(data_at + consumed..data_at + consumed + 2) != Some(&[0xFF, EOI]) {
+        // Multiple scans or trailing data: not a layout we rewrite.
+        return Ok(None);
+    }
+
+    let mut optimal: [Option<HuffTable>; SLOTS] = Default::default();
+    let mut dht = Vec::new();
+    for (slot, counts) in freq.iter().enumerate() {
+        if counts.iter().all(|&c| c == 0) {
+            continue;
+        }
+        let table = HuffTable::optimal(counts)?;
+        dht.push((((slot / 4) << 4) | (slot % 4)) as u8);
+        dht.extend_from_slice(&table.bits[1..]);
+        dht.extend_from_slice(&table.vals);
+        optimal[slot] = Some(table);
+    }
+    let codes: [Option<EncodeTable>; SLOTS] =
+        std::array::from_fn(|i| optimal[i].as_ref().map(EncodeTable::new));
+
+    let mut writer = BitWriter::with_capacity(consumed);
+    walk_scan(&image[data_at..], &scan, &tables, |event| match event {
+        Event::Symbol {
+            slot,
+            symbol,
+            extra,
+            extra_len,
+        } => {
+            // Every decoded symbol was counted above, so its table and code exist.
+            if let Some(table) = &codes[slot] {
+                let (code, len) = table.codes[symbol as usize];
+                writer.put(u32::from(code), u32::from(len));
+            }
+            writer.put(u32::from(extra), u32::from(extra_len));
+        }
+        Event::Restart(n) => writer.restart(n),
+    })?;
+    let mut tail = sos.to_vec();
+    tail.extend_from_slice(&writer.finish());
+    tail.extend_from_slice(&[0xFF, EOI]);
+
+    let at = segments
+        .iter()
+        .position(|s| s.marker == DHT)
+        .unwrap_or(segments.len());
+    segments.retain(|s| s.marker != DHT);
+    segments.insert(
+        at,
+        Segment {
+            marker: DHT,
+            data: dht,
+        },
+    );
+    let out = jpeg::serialize(&segments, &tail)?;
+    Ok((out.len() < image.len()).then_some(out))
+}
+
+/// Sampling layout of the frame.
+struct Frame {
+    width: usize,
+    height: usize,
+    /// (component id, horizontal, vertical sampling factor).
+    components: Vec<(u8, usize, usize)>,
+    restart_interval: usize,
+}
+
+impl Frame {
+    fn parse(segments: &[Segment]) -> Option<Self> {
+        let sof = segments.iter().find(|s| jpeg::is_sof(s.marker))?;
+        let data = &sof.data;
+        if !matches!(sof.marker, 0xC0 | 0xC1) || data.len() < 6 || data[0] != 8 {
+            return None;
+        }
+        let count = data[5] as usize;
+        let components = data
+            .get(6..6 + 3 * count)?
+            .chunks_exact(3)
+            .map(|c| (c[0], (c[1] >> 4) as usize, (c[1] & 0xF) as usize))
+            .collect::<Vec<_>>();
+        if components.is_empty()
+            || components
+                .iter()
+                .any(|&(_, h, v)| !(1..=4).contains(&h) || !(1..=4).contains(&v))
+        {
+            return None;
+        }
+        let restart_interval = segments
+            .iter()
+            .rfind(|s| s.marker == DRI && s.data.len() >= 2)
+            .map_or(0, |s| u16::from_be_bytes([s.data[0], s.data[1]]) as usize);
+        Some(Self {
+            height: u16::from_be_bytes([data[1], data[2]]) as usize,
+            width: u16::from_be_bytes([data[3], data[4]]) as usize,
+            components,
+            restart_interval,
+        })
+    }
+}
+
+/// Component order and MCU count of the (only) scan.
+struct Scan {
+    /// (blocks per MCU, DC slot, AC slot) for each scan component.
+    components: Vec<(usize, usize, usize)>,
+    mcus: usize,
+    restart_interval: usize,
+}
+
+impl Scan {
+    /// Parse the SOS segment at `at`, returning its raw bytes and the scan layout.
+    fn parse<'a>(
+        image: &'a [u8],
+        at: usize,
+        frame: &Frame,
+        segments: &[Segment],
+    ) -> Result<Option<(&'a [u8], Self)>> {
+        if image.get(at + 1) != Some(&SOS) || frame.width == 0 || frame.height == 0 {
+            return Ok(None);
+        }
+        let len = image
+            .get(at + 2..at + 4)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]) as usize)
+            .ok_or_else(|| Error::invalid_param("truncated SOS segment"))?;
+        let sos = image
+            .get(at..at + 2 + len)
+            .ok_or_else(|| Error::invalid_param("truncated SOS segment"))?;
+        let data = &sos[4..];
+        let count = data.first().copied().unwrap_or(0) as usize;
+        let Some(params) = data.get(1 + 2 * count..4 + 2 * count) else {
+            return Ok(None);
+        };
+        // Sequential scans cover the whole spectrum without successive approximation.
+        if params != [0, 63, 0] || count == 0 {
+            return Ok(None);
+        }
+        if !segments.iter().any(|s| s.marker == DHT) {
+            // Motion-JPEG style streams relying on implicit default tables.
+            return Ok(None);
+        }
+
+        let h_max = frame.components.iter().map(|c| c.1).max().unwrap_or(1);
+        let v_max = frame.components.iter().map(|c| c.2).max().unwrap_or(1);
+        let mut components = Vec::with_capacity(count);
+        let mut mcus = 0;
+        for sel in data[1..1 + 2 * count].chunks_exact(2) {
+            let Some(&(_, h, v)) = frame.components.iter().find(|c| c.0 == sel[0]) else {
+                return Ok(None);
+            };
+            let (dc, ac) = ((sel[1] >> 4) as usize, (sel[1] & 0xF) as usize);
+            if dc > 3 || ac > 3 {
+                return Ok(None);
+            }
+            if count == 1 {
+                // Non-interleaved: one block per MCU over the component's own grid.
+                let w = (frame.width * h).div_ceil(h_max).div_ceil(8);
+                let hgt = (frame.height * v).div_ceil(v_max).div_ceil(8);
+                components.push((1, dc, 4 + ac));
+                mcus = w * hgt;
+            } else {
+                components.push((h * v, dc, 4 + ac));
+                mcus = frame.width.div_ceil(8 * h_max) * frame.height.div_ceil(8 * v_max);
+            }
+        }
+        Ok(Some((
+            sos,
+            Self {
+                components,
+                mcus,
+                restart_interval: frame.restart_interval,
+            },
+        )))
+    }
+}
+
+enum Event {
+    Symbol {
+        slot: usize,
+        symbol: u8,
+        extra: u16,
+        extra_len: u8,
+    },
+    Restart(u8),
+}
+
+/// Walk the entropy-coded data of `scan`, reporting each Huffman symbol (with its
+/// appended magnitude bits) and restart marker. Returns the number of bytes consumed.
+fn walk_scan(
+    data: &[u8],
+    scan: &Scan,
+    tables: &[Option<DecodeTable>; SLOTS],
+    mut emit: impl FnMut(Event),
+) -> Result<usize> {
+    let table = |slot: usize| {
+        tables[slot]
+            .as_ref()
+            .ok_or_else(|| Error::invalid_param("scan references an undefined Huffman table"))
+    };
+    let mut reader = BitReader::new(data);
+    for mcu in 0..scan.mcus {
+        if scan.restart_interval > 0 && mcu > 0 && mcu % scan.restart_interval == 0 {
+            emit(Event::Restart(reader.restart()?));
+        }
+        for &(blocks, dc, ac) in &scan.components {
+            let (dc_table, ac_table) = (table(dc)?, table(ac)?);
+            for _ in 0..blocks {
+                let symbol = reader.decode(dc_table)?;
+                if symbol > 11 {
+                    return Err(Error::invalid_param("invalid DC magnitude"));
+                }
+                emit(Event::Symbol {
+                    slot: dc,
+                    symbol,
+                    extra: reader.bits(symbol),
+                    extra_len: symbol,
+                });
+                let mut k = 1;
+                while k < 64 {
+                    let symbol = reader.decode(ac_table)?;
+                    let (run, size) = (symbol >> 4, symbol & 0xF);
+                    if size > 10 {
+                        return Err(Error::invalid_param("invalid AC magnitude"));
+                    }
+                    emit(Event::Symbol {
+                        slot: ac,
+                        symbol,
+                        extra: reader.bits(size),
+                        extra_len: size,
+                    });
+                    match (run, size) {
+                        (0, 0) => break,
+                        (15, 0) => k += 16,
+                        _ => k += run as usize + 1,
+                    }
+                }
+                if k > 64 {
+                    return Err(Error::invalid_param("AC run past end of block"));
+                }
+            }
+        }
+    }
+    Ok(reader.finish())
+}
+
+/// Code-length counts (`bits[1..=16]`) and symbols of a Huffman table, as stored in DHT.
+struct HuffTable {
+    bits: [u8; 17],
+    vals: Vec<u8>,
+}
+
+impl HuffTable {
+    /// Build a length-limited optimal table (ITU T.81 Annex K.2, as in libjpeg).
+    fn optimal(counts: &[u32; 256]) -> Result<Self> {
+        // Slot 256 is a reserved pseudo-symbol so no real code is all ones.
+        let mut freq = [0u64; 257];
+        for (f, &c) in freq.iter_mut().zip(counts) {
+            *f = u64::from(c);
+        }
+        freq[256] = 1;
+        let mut code_size = [0usize; 257];
+        let mut others = [usize::MAX; 257];
+        let smallest = |freq: &[u64; 257], skip: usize| {
+            let mut best = None;
+            let mut v = u64::MAX;
+            for (i, &f) in freq.iter().enumerate() {
+                if f != 0 && f <= v && i != skip {
+                    v = f;
+                    best = Some(i);
+                }
+            }
+            best
+        };
+        while let Some(mut c1) = smallest(&freq, usize::MAX) {
+            let Some(mut c2) = smallest(&freq, c1) else {
+                break;
+            };
+            freq[c1] += freq[c2];
+            freq[c2] = 0;
+            code_size[c1] += 1;
+            while others[c1] != usize::MAX {
+                c1 = others[c1];
+                code_size[c1] += 1;
+            }
+            others[c1] = c2;
+            code_size[c2] += 1;
+            while others[c2] != usize::MAX {
+                c2 = others[c2];
+                code_size[c2] += 1;
+            }
+        }
+
+        let mut bits = [0usize; 33];
+        for &size in code_size.iter().filter(|&&s| s > 0) {
+            if size > 32 {
+                return Err(Error::invalid_param("Huffman code length overflow"));
+            }
+            bits[size] += 1;
+        }
+        // Shorten codes longer than 16 bits, keeping the tree complete.
+        for i in (17..=32).rev() {
+            while bits[i] > 0 {
+                let mut j = i - 2;
+                while bits[j] == 0 {
+                    j -= 1;
+                }
+                bits[i] -= 2;
+                bits[i - 1] += 1;
+                bits[j + 1] += 2;
+                bits[j] -= 1;
+            }
+        }
+        // Drop the reserved symbol from the longest length.
+        let mut longest = 16;
+        while bits[longest] == 0 {
+            longest -= 1;
+        }
+        bits[longest] -= 1;
+
+        let mut vals = Vec::new();
+        for size in 1..=32 {
+            for (symbol, &s) in code_size[..256].iter().enumerate() {
+                if s == size {
+                    vals.push(symbol as u8);
+                }
+            }
+        }
+        let mut out = [0u8; 17];
+        for (o, &b) in out.iter_mut().zip(&bits[..17]) {
+            *o = b as u8;
+        }
+        Ok(Self { bits: out, vals })
+    }
+
+    /// (code, length) of every symbol in table order (ITU T.81 Annex C).
+    fn codes(&self) -> impl Iterator<Item = (u8, u16, u8)> + '_ {
+        let mut code = 0u16;
+        let mut vals = self.vals.iter();
+        (1..=16u8).flat_map(move |len| {
+            let run: Vec<_> = (0..self.bits[len as usize])
+                .filter_map(|_| {
+                    let symbol = *vals.next()?;
+                    let c = code;
+                    code = code.wrapping_add(1);
+                    Some((symbol, c, len))
+                })
+                .collect();
+            code = code.wrapping_shl(1);
+            run
+        })
+    }
+}
+
+fn parse_dht(data: &[u8]) -> Result<Vec<(usize, HuffTable)>> {
+    let mut tables = Vec::new();
+    let mut rest = data;
+    while let Some((&class_id, tail)) = rest.split_first() {
+        let (class, id) = ((class_id >> 4) as usize, (class_id & 0xF) as usize);
+        if class > 1 || id > 3 || tail.len() < 16 {
+            return Err(Error::invalid_param("malformed DHT segment"));
+        }
+        let mut bits = [0u8; 17];
+        bits[1..].copy_from_slice(&tail[..16]);
+        let count = bits.iter().map(|&b| b as usize).sum::<usize>();
+        let vals = tail
+            .get(16..16 + count)
+            .ok_or_else(|| Error::invalid_param("truncated DHT segment"))?
+            .to_vec();
+        tables.push((class * 4 + id, HuffTable { bits, vals }));
+        rest = &tail[16 + count..];
+    }
+    Ok(tables)
+}
+
+/// Canonical decoding tables (ITU T.81 F.2.2.3).
+struct DecodeTable {
+    max_code: [i32; 17],
+    min_code: [i32; 17],
+    val_ptr: [usize; 17],
+    vals: Vec<u8>,
+}
+
+impl DecodeTable {
+    fn new(table: &HuffTable) -> Result<Self> {
+        let mut max_code = [-1i32; 17];
+        let mut min_code = [0i32; 17];
+        let mut val_ptr = [0usize; 17];
+        let mut code = 0i32;
+        let mut k = 0usize;
+        for len in 1..=16 {
+            let n = table.bits[len] as usize;
+            if n > 0 {
+                val_ptr[len] = k;
+                min_code[len] = code;
+                code += n as i32;
+                max_code[len] = code - 1;
+                k += n;
+            }
+            if code > 1 << len {
+                return Err(Error::invalid_param("oversubscribed Huffman table"));
+            }
+            code <<= 1;
+        }
+        Ok(Self {
+            max_code,
+            min_code,
+            val_ptr,
+            vals: table.vals.clone(),
+        })
+    }
+}
+
+struct EncodeTable {
+    /// (code, length) indexed by symbol.
+    codes: [(u16, u8); 256],
+}
+
+impl EncodeTable {
+    fn new(table: &HuffTable) -> Self {
+        let mut codes = [(0, 0); 256];
+        for (symbol, code, len) in table.codes() {
+            codes[symbol as usize] = (code, len);
+        }
+        Self { codes }
+    }
+}
+
+/// MSB-first reader over entropy-coded data that undoes `FF 00` byte stuffing.
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    acc: u32,
+    len: u32,
+    /// Set once a marker is reached; further reads yield zero bits, as in libjpeg.
+    at_marker: bool,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            acc: 0,
+            len: 0,
+            at_marker: false,
+        }
+    }
+
+    fn next_byte(&mut self) -> u8 {
+        if self.at_marker {
+            return 0;
+        }
+        let Some(&byte) = self.data.get(self.pos) else {
+            self.at_marker = true;
+            return 0;
+        };
+        if byte == 0xFF {
+            if self.data.get(self.pos + 1) == Some(&0) {
+                self.pos += 2;
+                return 0xFF;
+            }
+            self.at_marker = true;
+            return 0;
+        }
+        self.pos += 1;
+        byte
+    }
+
+    fn bit(&mut self) -> u32 {
+        if self.len == 0 {
+            self.acc = u32::from(self.next_byte());
+            self.len = 8;
+        }
+        self.len -= 1;
+        (self.acc >> self.len) & 1
+    }
+
+    fn bits(&mut self, n: u8) -> u16 {
+        let mut v = 0u32;
+        for _ in 0..n {
+            v = (v << 1) | self.bit();
+        }
+        v as u16
+    }
+
+    fn decode(&mut self, table: &DecodeTable) -> Result<u8> {
+        let mut code = 0i32;
+        for len in 1..=16 {
+            code = (code << 1) | self.bit() as i32;
+            if code <= table.max_code[len] {
+                let idx = table.val_ptr[len] + (code - table.min_code[len]) as usize;
+                return table
+                    .vals
+                    .get(idx)
+                    .copied()
+                    .ok_or_else(|| Error::invalid_param("corrupt Huffman table"));
+            }
+        }
+        Err(Error::invalid_param("corrupt Huffman code"))
+    }
+
+    /// Drop the partial byte and consume an `RSTn` marker, returning `n`.
+    fn restart(&mut self) -> Result<u8> {
+        self.len = 0;
+        match self.data.get(self.pos..self.pos + 2) {
+            Some(&[0xFF, m]) if (0xD0..=0xD7).contains(&m) => {
+                self.pos += 2;
+                self.at_marker = false;
+                Ok(m - 0xD0)
+            }
+            _ => Err(Error::invalid_param("missing restart marker")),
+        }
+    }
+
+    fn finish(self) -> usize {
+        self.pos
+    }
+}
+
+/// MSB-first writer that applies `FF 00` byte stuffing and one-bit padding.
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u32,
+    len: u32,
+}
+
+impl BitWriter {
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            out: Vec::with_capacity(capacity),
+            acc: 0,
+            len: 0,
+        }
+    }
+
+    fn put(&mut self, code: u32, len: u32) {
+        self.acc = (self.acc << len) | code;
+        self.len += len;
+        while self.len >= 8 {
+            let byte = (self.acc >> (self.len - 8)) as u8;
+            self.out.push(byte);
+            if byte == 0xFF {
+                self.out.push(0);
+            }
+            self.len -= 8;
+        }
+        self.acc &= (1 << self.len) - 1;
+    }
+
+    fn flush(&mut self) {
+        if self.len > 0 {
+            let pad = 8 - self.len;
+            self.put((1 << pad) - 1, pad);
+        }
+    }
+
+    fn restart(&mut self, n: u8) {
+        self.flush();
+        self.out.extend_from_slice(&[0xFF, 0xD0 + n]);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.flush();
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fixed-length 8-bit codes for every symbol in `symbols`: valid but far from optimal.
+    fn flat_table(slot: u8, symbols: &[u8]) -> Vec<u8> {
+        let mut data = vec![slot];
+        let mut bits = [0u8; 16];
+        bits[7] = symbols.len() as u8;
+        data.extend_from_slice(&bits);
+        data.extend_from_slice(symbols);
+        data
+    }
+
+    /// 16x8 grayscale baseline JPEG (two blocks) encoded with flat tables.
+    fn flat_jpeg(restart: bool) -> Vec<u8> {
+        let dc_symbols: Vec<u8> = (0..=11).collect();
+        let ac_symbols = [0x00, 0x01, 0x02, 0x11, 0xF0];
+        let mut dht = flat_table(0x00, &dc_symbols);
+        dht.extend(flat_table(0x10, &ac_symbols));
+        let mut segments = vec![
+            Segment {
+                marker: 0xC0,
+                data: vec![8, 0, 8, 0, 16, 1, 1, 0x11, 0],
+            },
+            Segment {
+                marker: DHT,
+                data: dht,
+            },
+        ];
+        if restart {
+            segments.push(Segment {
+                marker: DRI,
+                data: 1u16.to_be_bytes().to_vec(),
+            });
+        }
+        // Block: DC size 2 (bits 11), AC: 0x01 x10, ZRL, 0x02 (bits 10), EOB.
+        let tables = parse_dht(&segments[1].data).unwrap();
+        let dc = EncodeTable::new(&tables[0].1);
+        let ac = EncodeTable::new(&tables[1].1);
+        let mut w = BitWriter::with_capacity(64);
+        for block in 0..2 {
+            if restart && block == 1 {
+                w.restart(0);
+            }
+            let (c, l) = dc.codes[2];
+            w.put(c.into(), l.into());
+            w.put(0b11, 2);
+            for _ in 0..10 {
+                let (c, l) = ac.codes[0x01];
+                w.put(c.into(), l.into());
+                w.put(1, 1);
+            }
+            let (c, l) = ac.codes[0xF0];
+            w.put(c.into(), l.into());
+            let (c, l) = ac.codes[0x02];
+            w.put(c.into(), l.into());
+            w.put(0b10, 2);
+            let (c, l) = ac.codes[0x00];
+            w.put(c.into(), l.into());
+        }
+        let mut tail = vec![0xFF, SOS, 0, 8, 1, 1, 0x00, 0, 63, 0];
+        tail.extend(w.finish());
+        tail.extend_from_slice(&[0xFF, EOI]);
+        jpeg::serialize(&segments, &tail).unwrap()
+    }
+
+    /// Decode every symbol of the single scan of `image` with its own tables.
+    fn symbols(image: &[u8]) -> Vec<(usize, u8, u16)> {
+        let (segments, scan_at) = jpeg::parse_header(image).unwrap();
+        let frame = Frame::parse(&segments).unwrap();
+        let (sos, scan) = Scan::parse(image, scan_at, &frame, &segments)
+            .unwrap()
+            .unwrap();
+        let mut tables: [Option<DecodeTable>; SLOTS] = Default::default();
+        for seg in segments.iter().filter(|s| s.marker == DHT) {
+            for (slot, table) in parse_dht(&seg.data).unwrap() {
+                tables[slot] = Some(DecodeTable::new(&table).unwrap());
+            }
+        }
+        let mut out = Vec::new();
+        walk_scan(&image[scan_at + sos.len()..], &scan, &tables, |e| {
+            if let Event::Symbol {
+                slot,
+                symbol,
+                extra,
+                ..
+            } = e
+            {
+                out.push((slot, symbol, extra));
+            }
+        })
+        .unwrap();
+        out
+    }
+
+    #[test]
+    fn optimized_tables_shrink_the_scan_and_keep_every_symbol() {
+        for restart in [false, true] {
+            let image = flat_jpeg(restart);
+            let optimized = optimize(&image).unwrap().expect("smaller output");
+            assert!(optimized.len() < image.len());
+            assert_eq!(symbols(&optimized), symbols(&image));
+            assert_eq!(symbols(&image).len(), 2 * 14);
+        }
+    }
+
+    #[test]
+    fn optimal_table_limits_code_length_to_16() {
+        // Fibonacci-like frequencies force an unbounded tree deeper than 16.
+        let mut counts = [0u32; 256];
+        let (mut a, mut b) = (1u32, 1u32);
+        for c in counts.iter_mut().take(30) {
+            *c = a;
+            (a, b) = (b, a.saturating_add(b));
+        }
+        let table = HuffTable::optimal(&counts).unwrap();
+        assert_eq!(table.vals.len(), 30);
+        assert!(table.bits[1..].iter().map(|&b| b as usize).sum::<usize>() == 30);
+        // Kraft inequality with room for the reserved all-ones code.
+        let kraft: f64 = (1..=16)
+            .map(|l| table.bits[l] as f64 / f64::from(1 << l))
+            .sum();
+        assert!(kraft < 1.0);
+    }
+
+    #[test]
+    fn progressive_frames_are_left_alone() {
+        let mut image = flat_jpeg(false);
+        let sof_at = image.windows(2).position(|w| w == [0xFF, 0xC0]).unwrap();
+        image[sof_at + 1] = 0xC2;
+        assert!(optimize(&image).unwrap().is_none());
+    }
+}
@@ -0,0 +1,443 @@
+//! Built-in ICC profiles for the gamuts libultrahdr knows about.
+//!
+//! JPEG carries no gamut outside an embedded ICC profile, so an UltraHDR file baked from
+//! raw buffers is treated as sRGB by color-managed viewers unless a profile is attached
+//! with [`Encoder::set_icc_profile`](crate::Encoder::set_icc_profile):
+//!
+//! ```no_run
+//! # fn main() -> ultrahdr::Result<()> {
+//! use ultrahdr::{Encoder, ImgLabel, icc};
+//!
+//! let mut enc = Encoder::new()?;
+//! enc.set_icc_profile(icc::display_p3(), ImgLabel::UHDR_BASE_IMG)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The profiles describe the SDR base image: the gamut's primaries with a D65 white point,
+//! and the sRGB transfer curve. They are ICC v2.4 display profiles with colorants adapted to
+//! the D50 connection space (Bradford), which every color-managed viewer understands.
+//!
+//! In the other direction, [`detect_color_gamut`] maps an embedded profile, e.g. from
+//! [`Decoder::icc_profile`](crate::Decoder::icc_profile), back to a [`ColorGamut`].
+
+use crate::error::{Error, Result};
+use crate::sys;
+use crate::types::ColorGamut;
+use std::sync::OnceLock;
+
+/// Size of the fixed ICC profile header.
+const HEADER_LEN: usize = 128;
+/// `acsp`, the profile file signature at offset 36.
+const FILE_SIGNATURE: &[u8; 4] = b"acsp";
+/// Entries in the sampled transfer curves.
+const CURVE_POINTS: usize = 1024;
+
+const D65_XY: [f64; 2] = [0.3127, 0.3290];
+const D50_XYZ: [f64; 3] = [0.9642, 1.0, 0.8249];
+
+const PRIMARIES_BT709: [[f64; 2]; 3] = [[0.640, 0.330], [0.300, 0.600], [0.150, 0.060]];
+const PRIMARIES_DISPLAY_P3: [[f64; 2]; 3] = [[0.680, 0.320], [0.265, 0.690], [0.150, 0.060]];
+const PRIMARIES_BT2100: [[f64; 2]; 3] = [[0.708, 0.292], [0.170, 0.797], [0.131, 0.046]];
+/// Largest per-coordinate xy difference for primaries to count as a known gamut.
+const MATCH_TOLERANCE: f64 = 0.005;
+
+/// sRGB (BT.709 primaries) profile.
+pub fn srgb() -> &'static [u8] {
+    static PROFILE: OnceLock<Vec<u8>> = OnceLock::new();
+    PROFILE.get_or_init(|| build_profile("sRGB IEC61966-2.1", &PRIMARIES_BT709))
+}
+
+/// Display P3 profile.
+pub fn display_p3() -> &'static [u8] {
+    static PROFILE: OnceLock<Vec<u8>> = OnceLock::new();
+    PROFILE.get_or_init(|| build_profile("Display P3", &PRIMARIES_DISPLAY_P3))
+}
+
+/// BT.2100 (BT.2020 primaries) profile with the sRGB transfer curve, for SDR bases graded in
+/// the wide gamut.
+pub fn bt2100() -> &'static [u8] {
+    static PROFILE: OnceLock<Vec<u8>> = OnceLock::new();
+    PROFILE.get_or_init(|| build_profile("Rec. 2100 (sRGB transfer)", &PRIMARIES_BT2100))
+}
+
+/// Built-in profile for `cg`, or `None` for `UHDR_CG_UNSPECIFIED`.
+pub fn for_gamut(cg: ColorGamut) -> Option<&'static [u8]> {
+    match cg {
+        sys::uhdr_color_gamut::UHDR_CG_BT_709 => Some(srgb()),
+        sys::uhdr_color_gamut::UHDR_CG_DISPLAY_P3 => Some(display_p3()),
+        sys::uhdr_color_gamut::UHDR_CG_BT_2100 => Some(bt2100()),
+        sys::uhdr_color_gamut::UHDR_CG_UNSPECIFIED => None,
+    }
+}
+
+/// Guess the gamut of a raw ICC profile (not a JPEG; see
+/// [`Decoder::icc_profile`](crate::Decoder::icc_profile)).
+///
+/// Matches the [`parse_primaries`] result against BT.709, Display P3 and BT.2100, and
+/// falls back to hints in a v2 `desc` tag ("P3", "2020", "sRGB", ...) when the colorants
+/// are missing or match none of them. Returns `None` if neither works.
+pub fn detect_color_gamut(icc: &[u8]) -> Option<ColorGamut> {
+    parse_primaries(icc)
+        .and_then(|p| match_primaries(&p))
+        .or_else(|| {
+            profile_description(icc)
+                .as_deref()
+                .and_then(match_desc_hint)
+        })
+}
+
+/// Read the red, green and blue primaries of an RGB profile as CIE xy chromaticities.
+///
+/// Profiles store their colorants (`rXYZ`/`gXYZ`/`bXYZ`) adapted to the D50 connection
+/// space; this undoes a Bradford adaptation from D65, the convention of display profiles,
+/// so the result is comparable with published primaries. Returns `None` when a colorant tag
+/// is missing or malformed.
+pub fn parse_primaries(icc: &[u8]) -> Option<[[f32; 2]; 3]> {
+    let [r, g, b] = [b"rXYZ", b"gXYZ", b"bXYZ"].map(|sig| parse_xyz_tag(icc, sig));
+    let (r, g, b) = (r?, g?, b?);
+    let d50 = [[r[0], g[0], b[0]], [r[1], g[1], b[1]], [r[2], g[2], b[2]]];
+    let m = mul(&invert(&bradford_d65_to_d50()), &d50);
+    let xy = |i: usize| {
+        let sum = m[0][i] + m[1][i] + m[2][i];
+        (sum > f64::EPSILON).then(|| [(m[0][i] / sum) as f32, (m[1][i] / sum) as f32])
+    };
+    Some([xy(0)?, xy(1)?, xy(2)?])
+}
+
+fn match_primaries(primaries: &[[f32; 2]; 3]) -> Option<ColorGamut> {
+    let close = |known: &[[f64; 2]; 3]| {
+        primaries.iter().zip(known).all(|(p, k)| {
+            (p[0] as f64 - k[0]).abs() <= MATCH_TOLERANCE
+                && (p[1] as f64 - k[1]).abs() <= MATCH_TOLERANCE
+        })
+    };
+    if close(&PRIMARIES_DISPLAY_P3) {
+        Some(sys::uhdr_color_gamut::UHDR_CG_DISPLAY_P3)
+    } else if close(&PRIMARIES_BT2100) {
+        Some(sys::uhdr_color_gamut::UHDR_CG_BT_2100)
+    } else if close(&PRIMARIES_BT709) {
+        Some(sys::uhdr_color_gamut::UHDR_CG_BT_709)
+    } else {
+        None
+    }
+}
+
+fn match_desc_hint(desc: &str) -> Option<ColorGamut> {
+    let lower = desc.to_ascii_lowercase();
+    if lower.contains("p3") {
+        Some(sys::uhdr_color_gamut::UHDR_CG_DISPLAY_P3)
+    } else if lower.contains("2020") || lower.contains("2100") {
+        Some(sys::uhdr_color_gamut::UHDR_CG_BT_2100)
+    } else if lower.contains("srgb") || lower.contains("709") {
+        Some(sys::uhdr_color_gamut::UHDR_CG_BT_709)
+    } else {
+        None
+    }
+}
+
+/// ASCII text of a v2 `textDescriptionType` `desc` tag.
+fn profile_description(icc: &[u8]) -> Option<String> {
+    let tag = tag_data(icc, b"desc")?;
+    if tag.len() < 12 || &tag[..4] != b"desc" {
+        return None;
+    }
+    let len = u32::from_be_bytes(tag[8..12].try_into().ok()?) as usize;
+    let text = tag
+        .get(12..12usize.checked_add(len)?)
+        .filter(|t| !t.is_empty())?;
+    String::from_utf8(text.to_vec())
+        .ok()
+        .map(|s| s.trim_matches('\0').to_string())
+}
+
+fn parse_xyz_tag(icc: &[u8], sig: &[u8; 4]) -> Option<[f64; 3]> {
+    let data = tag_data(icc, sig)?;
+    if data.len() < 20 || &data[..4] != b"XYZ " {
+        return None;
+    }
+    Some(std::array::from_fn(|i| {
+        read_s15_fixed16(&data[8 + 4 * i..12 + 4 * i])
+    }))
+}
+
+/// Bytes of the tag with signature `sig`, if the tag table lists it within bounds.
+fn tag_data<'a>(icc: &'a [u8], sig: &[u8; 4]) -> Option<&'a [u8]> {
+    let count = u32::from_be_bytes(icc.get(HEADER_LEN..HEADER_LEN + 4)?.try_into().ok()?);
+    let table = icc.get(HEADER_LEN + 4..)?;
+    let entry = table
+        .chunks_exact(12)
+        .take(count as usize)
+        .find(|e| &e[..4] == sig)?;
+    let offset = u32::from_be_bytes(entry[4..8].try_into().ok()?) as usize;
+    let size = u32::from_be_bytes(entry[8..12].try_into().ok()?) as usize;
+    icc.get(offset..offset.checked_add(size)?)
+}
+
+/// Check that `bytes` starts with a well-formed ICC header: at least 128 bytes, a declared
+/// size equal to the buffer length and the `acsp` signature.
+pub(crate) fn validate(bytes: &[u8]) -> Result<()> {
+    if bytes.len() < HEADER_LEN {
+        return Err(Error::invalid_param("ICC profile shorter than its header"));
+    }
+    let declared = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    if declared as usize != bytes.len() {
+        return Err(Error::invalid_param(
+            "ICC profile size field does not match its length",
+        ));
+    }
+    if &bytes[36..40] != FILE_SIGNATURE {
+        return Err(Error::invalid_param(
+            "ICC profile lacks the 'acsp' signature",
+        ));
+    }
+    Ok(())
+}
+
+fn build_profile(description: &str, primaries: &[[f64; 2]; 3]) -> Vec<u8> {
+    let colorants = adapt_to_d50(rgb_to_xyz(primaries));
+    let column = |i: usize| [colorants[0][i], colorants[1][i], colorants[2][i]];
+    let curve = srgb_curve();
+    // Tags sharing data (the three TRCs) point at the same bytes.
+    let tags: [(&[u8; 4], usize); 9] = [
+        (b"desc", 0),
+        (b"cprt", 1),
+        (b"wtpt", 2),
+        (b"rXYZ", 3),
+        (b"gXYZ", 4),
+        (b"bXYZ", 5),
+        (b"rTRC", 6),
+        (b"gTRC", 6),
+        (b"bTRC", 6),
+    ];
+    let data = [
+        text_description(description),
+        text("No copyright, use freely"),
+        xyz(D50_XYZ),
+        xyz(column(0)),
+        xyz(column(1)),
+        xyz(column(2)),
+        curve,
+    ];
+
+    let table_len = 4 + 12 * tags.len();
+    let mut offsets = Vec::with_capacity(data.len());
+    let mut body = Vec::new();
+    for block in &data {
+        offsets.push(HEADER_LEN + table_len + body.len());
+        body.extend_from_slice(block);
+        body.resize(body.len().next_multiple_of(4), 0);
+    }
+
+    let mut out = header();
+    out.extend_from_slice(&(tags.len() as u32).to_be_bytes());
+    for (sig, idx) in tags {
+        out.extend_from_slice(sig);
+        out.extend_from_slice(&(offsets[idx] as u32).to_be_bytes());
+        out.extend_from_slice(&(data[idx].len() as u32).to_be_bytes());
+    }
+    out.extend_from_slice(&body);
+    let size = out.len() as u32;
+    out[..4].copy_from_slice(&size.to_be_bytes());
+    out
+}
+
+fn header() -> Vec<u8> {
+    let mut h = vec![0u8; HEADER_LEN];
+    h[8..12].copy_from_slice(&[0x02, 0x40, 0x00, 0x00]);
+    h[12..16].copy_from_slice(b"mntr");
+    h[16..20].copy_from_slice(b"RGB ");
+    h[20..24].copy_from_slice(b"XYZ ");
+    for (i, v) in [2024u16, 1, 1, 0, 0, 0].into_iter().enumerate() {
+        h[24 + 2 * i..26 + 2 * i].copy_from_slice(&v.to_be_bytes());
+    }
+    h[36..40].copy_from_slice(FILE_SIGNATURE);
+    for (i, v) in D50_XYZ.into_iter().enumerate() {
+        h[68 + 4 * i..72 + 4 * i].copy_from_slice(&s15_fixed16(v));
+    }
+    h
+}
+
+fn text_description(s: &str) -> Vec<u8> {
+    let mut out = b"desc\0\0\0\0".to_vec();
+    out.extend_from_slice(&(s.len() as u32 + 1).to_be_bytes());
+    out.extend_from_slice(s.as_bytes());
+    out.push(0);
+    // Empty Unicode and ScriptCode descriptions.
+    out.extend_from_slice(&[0; 4 + 4 + 2 + 1 + 67]);
+    out
+}
+
+fn text(s: &str) -> Vec<u8> {
+    let mut out = b"text\0\0\0\0".to_vec();
+    out.extend_from_slice(s.as_bytes());
+    out.push(0);
+    out
+}
+
+fn xyz(v: [f64; 3]) -> Vec<u8> {
+    let mut out = b"XYZ \0\0\0\0".to_vec();
+    for c in v {
+        out.extend_from_slice(&s15_fixed16(c));
+    }
+    out
+}
+
+fn srgb_curve() -> Vec<u8> {
+    let mut out = b"curv\0\0\0\0".to_vec();
+    out.extend_from_slice(&(CURVE_POINTS as u32).to_be_bytes());
+    for i in 0..CURVE_POINTS {
+        let v = i as f64 / (CURVE_POINTS - 1) as f64;
+        let linear = if v <= 0.04045 {
+            v / 12.92
+        } else {
+            ((v + 0.055) / 1.055).powf(2.4)
+        };
+        out.extend_from_slice(&((linear * 65535.0).round() as u16).to_be_bytes());
+    }
+    out
+}
+
+fn s15_fixed16(v: f64) -> [u8; 4] {
+    ((v * 65536.0).round() as i32).to_be_bytes()
+}
+
+fn read_s15_fixed16(bytes: &[u8]) -> f64 {
+    i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64 / 65536.0
+}
+
+type Mat3 = [[f64; 3]; 3];
+
+fn xy_to_xyz([x, y]: [f64; 2]) -> [f64; 3] {
+    [x / y, 1.0, (1.0 - x - y) / y]
+}
+
+/// RGB to XYZ matrix for `primaries` with a D65 white point.
+fn rgb_to_xyz(primaries: &[[f64; 2]; 3]) -> Mat3 {
+    let p = primaries.map(xy_to_xyz);
+    let m = [
+        [p[0][0], p[1][0], p[2][0]],
+        [p[0][1], p[1][1], p[2][1]],
+        [p[0][2], p[1][2], p[2][2]],
+    ];
+    let s = mul_vec(&invert(&m), xy_to_xyz(D65_XY));
+    m.map(|row| [row[0] * s[0], row[1] * s[1], row[2] * s[2]])
+}
+
+/// Bradford chromatic adaptation of an RGB to XYZ matrix from D65 to D50.
+fn adapt_to_d50(m: Mat3) -> Mat3 {
+    mul(&bradford_d65_to_d50(), &m)
+}
+
+/// XYZ to XYZ Bradford adaptation matrix from D65 to D50.
+fn bradford_d65_to_d50() -> Mat3 {
+    const BRADFORD: Mat3 = [
+        [0.8951, 0.2664, -0.1614],
+        [-0.7502, 1.7135, 0.0367],
+        [0.0389, -0.0685, 1.0296],
+    ];
+    let src = mul_vec(&BRADFORD, xy_to_xyz(D65_XY));
+    let dst = mul_vec(&BRADFORD, D50_XYZ);
+    // Scale the cone responses, i.e. diag(dst / src) * BRADFORD.
+    let cones: Mat3 = std::array::from_fn(|i| BRADFORD[i].map(|v| v * dst[i] / src[i]));
+    mul(&invert(&BRADFORD), &cones)
+}
+
+fn mul(a: &Mat3, b: &Mat3) -> Mat3 {
+    std::array::from_fn(|i| std::array::from_fn(|j| (0..3).map(|k| a[i][k] * b[k][j]).sum()))
+}
+
+fn mul_vec(m: &Mat3, v: [f64; 3]) -> [f64; 3] {
+    m.map(|row| row[0] * v[0] + row[1] * v[1] + row[2] * v[2])
+}
+
+fn invert(m: &Mat3) -> Mat3 {
+    let cof = |r: usize, c: usize| {
+        let (r1, r2) = ((r + 1) % 3, (r + 2) % 3);
+        let (c1, c2) = ((c + 1) % 3, (c + 2) % 3);
+        m[r1][c1] * m[r2][c2] - m[r1][c2] * m[r2][c1]
+    };
+    let det = (0..3).map(|c| m[0][c] * cof(0, c)).sum::<f64>();
+    std::array::from_fn(|i| std::array::from_fn(|j| cof(j, i) / det))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag<'a>(icc: &'a [u8], sig: &[u8; 4]) -> &'a [u8] {
+        tag_data(icc, sig).unwrap()
+    }
+
+    #[test]
+    fn built_in_profiles_are_valid_and_balanced_to_d50() {
+        for (cg, name) in [
+            (sys::uhdr_color_gamut::UHDR_CG_BT_709, "sRGB"),
+            (sys::uhdr_color_gamut::UHDR_CG_DISPLAY_P3, "Display P3"),
+            (sys::uhdr_color_gamut::UHDR_CG_BT_2100, "Rec. 2100"),
+        ] {
+            let icc = for_gamut(cg).unwrap();
+            validate(icc).unwrap();
+            assert_eq!(icc.len() % 4, 0);
+            let desc = tag(icc, b"desc");
+            assert!(desc[12..].starts_with(name.as_bytes()));
+            // RGB(1, 1, 1) must map to the D50 connection-space white.
+            let [r, g, b] = [b"rXYZ", b"gXYZ", b"bXYZ"].map(|s| parse_xyz_tag(icc, s).unwrap());
+            for c in 0..3 {
+                assert!((r[c] + g[c] + b[c] - D50_XYZ[c]).abs() < 1e-3, "{name}");
+            }
+            assert_eq!(tag(icc, b"rTRC").len(), 12 + 2 * CURVE_POINTS);
+        }
+        assert!(for_gamut(sys::uhdr_color_gamut::UHDR_CG_UNSPECIFIED).is_none());
+        assert_ne!(srgb(), display_p3());
+    }
+
+    #[test]
+    fn validate_rejects_malformed_headers() {
+        assert!(validate(&[0; 64]).is_err());
+        let mut icc = srgb().to_vec();
+        icc.push(0);
+        assert!(validate(&icc).is_err());
+        let mut icc = srgb().to_vec();
+        icc[36] = b'x';
+        assert!(validate(&icc).is_err());
+    }
+
+    #[test]
+    fn detects_gamut_of_built_in_profiles() {
+        for (cg, known) in [
+            (sys::uhdr_color_gamut::UHDR_CG_BT_709, PRIMARIES_BT709),
+            (
+                sys::uhdr_color_gamut::UHDR_CG_DISPLAY_P3,
+                PRIMARIES_DISPLAY_P3,
+            ),
+            (sys::uhdr_color_gamut::UHDR_CG_BT_2100, PRIMARIES_BT2100),
+        ] {
+            let icc = for_gamut(cg).unwrap();
+            let primaries = parse_primaries(icc).unwrap();
+            for (p, k) in primaries.iter().zip(&known) {
+                assert!((p[0] as f64 - k[0]).abs() < 1e-3 && (p[1] as f64 - k[1]).abs() < 1e-3);
+            }
+            assert_eq!(detect_color_gamut(icc), Some(cg));
+        }
+    }
+
+    #[test]
+    fn detection_falls_back_to_description() {
+        let mut icc = display_p3().to_vec();
+        // Rename the colorant tags so only the description is left to go by.
+        let table = HEADER_LEN + 4;
+        for entry in icc[table..table + 12 * 9].chunks_exact_mut(12) {
+            if entry[1..4] == *b"XYZ" {
+                entry[0] = b'x';
+            }
+        }
+        assert!(parse_primaries(&icc).is_none());
+        assert_eq!(
+            detect_color_gamut(&icc),
+            Some(sys::uhdr_color_gamut::UHDR_CG_DISPLAY_P3)
+        );
+        assert_eq!(detect_color_gamut(&[0; 200]), None);
+        assert_eq!(detect_color_gamut(b"not a profile"), None);
+    }
+}
@@ -0,0 +1,731 @@
+//! Lightweight JPEG marker walking used to post-process encoded UltraHDR streams.
+//!
+//! Only the header of the primary image (everything before SOS) is split into segments;
+//! entropy-coded data and any trailing secondary images (the gain map) are carried
+//! through verbatim, with the MPF index patched so the gain map stays reachable.
+
+use crate::error::{Error, Result};
+#[cfg(any(feature = "std", test))]
+use crate::mpf;
+#[cfg(feature = "std")]
+use crate::mpf::MpEntry;
+use crate::mpf::{MPF_SIGNATURE, MpfIndex, TIFF_HEADER_OFFSET};
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+
+pub(crate) const SOI: u8 = 0xD8;
+pub(crate) const EOI: u8 = 0xD9;
+pub(crate) const SOS: u8 = 0xDA;
+#[cfg(any(feature = "std", test))]
+pub(crate) const APP0: u8 = 0xE0;
+pub(crate) const APP1: u8 = 0xE1;
+pub(crate) const APP2: u8 = 0xE2;
+#[cfg(any(feature = "std", test))]
+pub(crate) const APP15: u8 = 0xEF;
+
+/// Prefix of the APP2 payload carrying an ICC profile chunk.
+#[cfg(any(feature = "std", test))]
+pub(crate) const ICC_SIGNATURE: &[u8] = b"ICC_PROFILE\0";
+
+/// Largest payload a length-prefixed segment can carry (the length field counts itself).
+pub(crate) const MAX_SEGMENT_PAYLOAD: usize = u16::MAX as usize - 2;
+
+/// A marker segment from the JPEG header. `data` excludes the marker and length bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Segment {
+    pub marker: u8,
+    pub data: Vec<u8>,
+}
+
+impl Segment {
+    #[cfg(any(feature = "std", test))]
+    pub fn is_app(&self) -> bool {
+        (APP0..=APP15).contains(&self.marker)
+    }
+
+    fn is_mpf(&self) -> bool {
+        self.marker == APP2 && self.data.starts_with(MPF_SIGNATURE)
+    }
+}
+
+fn is_standalone(marker: u8) -> bool {
+    matches!(marker, 0x01 | 0xD0..=0xD7)
+}
+
+/// Split the header of the first JPEG in `bytes` into segments.
+///
+/// Returns the segments preceding the first SOS (or EOI) marker, plus the offset of that
+/// marker so callers can carry the remaining bytes through untouched.
+pub(crate) fn parse_header(bytes: &[u8]) -> Result<(Vec<Segment>, usize)> {
+    if bytes.len() < 4 || bytes[0] != 0xFF || bytes[1] != SOI {
+        return Err(Error::invalid_param("input is not a JPEG stream"));
+    }
+    let mut segments = Vec::new();
+    let mut pos = 2;
+    loop {
+        let marker_at = pos;
+        if bytes.get(pos) != Some(&0xFF) {
+            return Err(Error::invalid_param("malformed JPEG marker"));
+        }
+        // Skip optional fill bytes before the marker code.
+        while bytes.get(pos) == Some(&0xFF) {
+            pos += 1;
+        }
+        let marker = *bytes
+            .get(pos)
+            .ok_or_else(|| Error::invalid_param("truncated JPEG header"))?;
+        pos += 1;
+        if marker == SOS || marker == EOI {
+            return Ok((segments, marker_at));
+        }
+        if is_standalone(marker) {
+            segments.push(Segment {
+                marker,
+                data: Vec::new(),
+            });
+            continue;
+        }
+        let len = bytes
+            .get(pos..pos + 2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]) as usize)
+            .ok_or_else(|| Error::invalid_param("truncated JPEG segment length"))?;
+        if len < 2 {
+            return Err(Error::invalid_param("invalid JPEG segment length"));
+        }
+        let data = bytes
+            .get(pos + 2..pos + len)
+            .ok_or_else(|| Error::invalid_param("truncated JPEG segment"))?;
+        segments.push(Segment {
+            marker,
+            data: data.to_vec(),
+        });
+        pos += len;
+    }
+}
+
+/// Serialize header segments followed by `tail` (SOS onwards) into a JPEG stream.
+pub(crate) fn serialize(segments: &[Segment], tail: &[u8]) -> Result<Vec<u8>> {
+    let header_len: usize = segments.iter().map(|s| 4 + s.data.len()).sum();
+    let mut out = Vec::with_capacity(2 + header_len + tail.len());
+    out.extend_from_slice(&[0xFF, SOI]);
+    for seg in segments {
+        out.extend_from_slice(&[0xFF, seg.marker]);
+        if is_standalone(seg.marker) {
+            continue;
+        }
+        if seg.data.len() > MAX_SEGMENT_PAYLOAD {
+            return Err(Error::invalid_param("JPEG segment exceeds 64KB limit"));
+        }
+        out.extend_from_slice(&((seg.data.len() + 2) as u16).to_be_bytes());
+        out.extend_from_slice(&seg.data);
+    }
+    out.extend_from_slice(tail);
+    Ok(out)
+}
+
+/// Offset of the payload of `segments[idx]` within the serialized stream.
+fn payload_offset(segments: &[Segment], idx: usize) -> usize {
+    2 + segments[..idx]
+        .iter()
+        .map(|s| {
+            if is_standalone(s.marker) {
+                2
+            } else {
+                4 + s.data.len()
+            }
+        })
+        .sum::<usize>()
+        + 4
+}
+
+fn find_mpf(segments: &[Segment]) -> Option<(usize, MpfIndex)> {
+    segments
+        .iter()
+        .enumerate()
+        .filter(|(_, s)| s.is_mpf())
+        .find_map(|(i, s)| MpfIndex::parse(&s.data).map(|index| (i, index)))
+}
+
+/// Edit the header segments of the primary image in an (UltraHDR) JPEG stream.
+///
+/// Secondary images referenced by MPF are preserved byte-for-byte and the MPF entry
+/// table is updated to the new primary length/offsets in the writer's byte order.
+pub(crate) fn rewrite_primary(
+    stream: &[u8],
+    edit: impl FnOnce(&mut Vec<Segment>) -> Result<()>,
+) -> Result<Vec<u8>> {
+    rewrite_stream(stream, edit, None)
+}
+
+/// Replace MPF image `idx` (1 = gain map) with `image`, then edit the primary header.
+///
+/// Other images are preserved byte-for-byte; every MPF size/offset is updated.
+#[cfg(any(feature = "std", test))]
+pub(crate) fn replace_secondary(
+    stream: &[u8],
+    idx: usize,
+    image: Vec<u8>,
+    edit: impl FnOnce(&mut Vec<Segment>) -> Result<()>,
+) -> Result<Vec<u8>> {
+    if idx == 0 {
+        return Err(Error::invalid_param("MPF image 0 is the primary image"));
+    }
+    rewrite_stream(stream, edit, Some((idx, image)))
+}
+
+/// Borrow MPF image `idx` (1 = gain map) from an UltraHDR stream.
+#[cfg(any(feature = "std", test))]
+pub(crate) fn secondary_image(stream: &[u8], idx: usize) -> Result<&[u8]> {
+    let (segments, _) = parse_header(stream)?;
+    let (mpf_idx, index) =
+        find_mpf(&segments).ok_or_else(|| Error::invalid_param("stream has no MPF index"))?;
+    let payload = &segments[mpf_idx].data;
+    let entry = index
+        .entry(payload, idx)
+        .filter(|e| idx > 0 && e.offset != 0)
+        .ok_or_else(|| Error::invalid_param("MPF has no such secondary image"))?;
+    let start = payload_offset(&segments, mpf_idx) + TIFF_HEADER_OFFSET + entry.offset as usize;
+    stream
+        .get(start..start + entry.size as usize)
+        .ok_or_else(|| Error::invalid_param("MPF entry points outside the stream"))
+}
+
+/// Serialize a primary image with a two-image MPF index followed by `secondary`.
+///
+/// An MPF segment is inserted after the leading APP segments (replacing any existing
+/// one), and both entries are filled in for the final layout.
+#[cfg(feature = "std")]
+pub(crate) fn with_secondary(
+    segments: &[Segment],
+    tail: &[u8],
+    secondary: &[u8],
+) -> Result<Vec<u8>> {
+    let mut segments: Vec<Segment> = segments.iter().filter(|s| !s.is_mpf()).cloned().collect();
+    insert_app_segment(&mut segments, APP2, mpf::payload(0, 0, 0));
+    let mut out = serialize(&segments, tail)?;
+    let (idx, index) =
+        find_mpf(&segments).ok_or_else(|| Error::invalid_param("failed to build MPF index"))?;
+    let payload_at = payload_offset(&segments, idx);
+    let too_large = || Error::invalid_param("image too large for MPF");
+    let primary_size = u32::try_from(out.len()).map_err(|_| too_large())?;
+    let secondary_size = u32::try_from(secondary.len()).map_err(|_| too_large())?;
+    let offset = primary_size - (payload_at + TIFF_HEADER_OFFSET) as u32;
+    let payload = &mut out[payload_at..payload_at + segments[idx].data.len()];
+    if !(index.set_entry(payload, 0, primary_size, 0)
+        && index.set_entry(payload, 1, secondary_size, offset))
+    {
+        return Err(Error::invalid_param("failed to fill MPF entries"));
+    }
+    out.extend_from_slice(secondary);
+    Ok(out)
+}
+
+/// Every MP entry of the primary header paired with the absolute stream offset it points
+/// at (0 for the primary image). `None` when the header has no parseable MPF index.
+#[cfg(feature = "std")]
+pub(crate) fn mpf_entries(stream: &[u8]) -> Result<Option<Vec<(MpEntry, usize)>>> {
+    let (segments, _) = parse_header(stream)?;
+    let Some((idx, index)) = find_mpf(&segments) else {
+        return Ok(None);
+    };
+    let payload = &segments[idx].data;
+    let tiff_base = payload_offset(&segments, idx) + TIFF_HEADER_OFFSET;
+    Ok(Some(
+        (0..index.count)
+            .filter_map(|i| index.entry(payload, i))
+            .map(|e| {
+                let start = if e.offset == 0 {
+                    0
+                } else {
+                    tiff_base + e.offset as usize
+                };
+                (e, start)
+            })
+            .collect(),
+    ))
+}
+
+/// Length of the primary image and the (MPF index, absolute start, size) of each
+/// secondary image it references.
+fn layout(
+    stream: &[u8],
+    segments: &[Segment],
+    scan_at: usize,
+) -> (usize, Vec<(usize, usize, usize)>) {
+    let mut primary_len = stream.len();
+    let mut secondaries = Vec::new();
+    if let Some((idx, index)) = find_mpf(segments) {
+        let payload = &segments[idx].data;
+        if let Some(primary) = index.entry(payload, 0) {
+            let size = primary.size as usize;
+            if size > scan_at && size <= stream.len() {
+                primary_len = size;
+            }
+        }
+        let tiff_base = payload_offset(segments, idx) + TIFF_HEADER_OFFSET;
+        for i in 1..index.count {
+            if let Some(entry) = index.entry(payload, i)
+                && entry.offset != 0
+            {
+                secondaries.push((i, tiff_base + entry.offset as usize, entry.size as usize));
+            }
+        }
+    }
+    (primary_len, secondaries)
+}
+
+/// Borrow the primary image (MPF image 0, or the whole stream without MPF).
+#[cfg(any(feature = "std", test))]
+pub(crate) fn primary_image(stream: &[u8]) -> Result<&[u8]> {
+    let (segments, scan_at) = parse_header(stream)?;
+    let (primary_len, _) = layout(stream, &segments, scan_at);
+    Ok(&stream[..primary_len])
+}
+
+/// Replace the primary image with `image`, a complete JPEG whose header carries the MPF
+/// segment. Secondary images are preserved byte-for-byte and their offsets updated.
+#[cfg(any(feature = "std", test))]
+pub(crate) fn replace_primary(stream: &[u8], image: Vec<u8>) -> Result<Vec<u8>> {
+    rewrite_stream(stream, |_| Ok(()), Some((0, image)))
+}
+
+fn rewrite_stream(
+    stream: &[u8],
+    edit: impl FnOnce(&mut Vec<Segment>) -> Result<()>,
+    replacement: Option<(usize, Vec<u8>)>,
+) -> Result<Vec<u8>> {
+    let (mut segments, scan_at) = parse_header(stream)?;
+    let (primary_len, secondaries) = layout(stream, &segments, scan_at);
+    let mut primary_tail = Cow::Borrowed(&stream[scan_at..primary_len]);
+
+    // Splice the replacement into the bytes following the primary image.
+    let mut rest = stream[primary_len..].to_vec();
+    let mut replaced = None;
+    if let Some((0, mut image)) = replacement {
+        let (image_segments, image_scan_at) = parse_header(&image)?;
+        segments = image_segments;
+        image.drain(..image_scan_at);
+        primary_tail = Cow::Owned(image);
+    } else if let Some((idx, image)) = replacement {
+        let &(_, start, size) = secondaries
+            .iter()
+            .find(|(i, _, _)| *i == idx)
+            .ok_or_else(|| Error::invalid_param("MPF has no such secondary image"))?;
+        let rel = start
+            .checked_sub(primary_len)
+            .filter(|rel| rel + size <= rest.len())
+            .ok_or_else(|| Error::invalid_param("MPF entry points outside the stream"))?;
+        let new_size = image.len();
+        rest.splice(rel..rel + size, image);
+        replaced = Some((idx, start, size, new_size));
+    }
+
+    edit(&mut segments)?;
+    let mut out = serialize(&segments, &primary_tail)?;
+    let new_primary_len = out.len();
+
+    if let Some((idx, index)) = find_mpf(&segments) {
+        let payload_at = payload_offset(&segments, idx);
+        let tiff_base = payload_at + TIFF_HEADER_OFFSET;
+        let payload = &mut out[payload_at..payload_at + segments[idx].data.len()];
+        let primary_size = u32::try_from(new_primary_len)
+            .map_err(|_| Error::invalid_param("primary image too large for MPF"))?;
+        if !index.set_entry(payload, 0, primary_size, 0) {
+            return Err(Error::invalid_param("failed to update MPF primary entry"));
+        }
+        for (i, old_abs, old_size) in secondaries {
+            let mut size = old_size;
+            let mut shifted = old_abs;
+            if let Some((r_idx, r_start, r_size, r_new)) = replaced {
+                if i == r_idx {
+                    size = r_new;
+                } else if old_abs > r_start {
+                    shifted = (old_abs + r_new)
+                        .checked_sub(r_size)
+                        .ok_or_else(|| Error::invalid_param("MPF offset out of range"))?;
+                }
+            }
+            let offset = shifted
+                .checked_sub(primary_len)
+                .and_then(|rel| rel.checked_add(new_primary_len))
+                .and_then(|abs| abs.checked_sub(tiff_base))
+                .and_then(|off| u32::try_from(off).ok())
+                .ok_or_else(|| Error::invalid_param("MPF offset out of range"))?;
+            let size =
+                u32::try_from(size).map_err(|_| Error::invalid_param("image too large for MPF"))?;
+            if !index.set_entry(payload, i, size, offset) {
+                return Err(Error::invalid_param("failed to update MPF secondary entry"));
+            }
+        }
+    }
+
+    out.extend_from_slice(&rest);
+    Ok(out)
+}
+
+/// Collect the payloads of every `marker` segment in the primary JPEG header.
+///
+/// `marker` is the full marker byte (e.g. `0xEB` for APP11, where C2PA manifests live).
+/// Payloads are returned in file order without the marker/length prefix. Inputs that are
+/// not JPEG streams yield an empty list.
+///
+/// ```
+/// let jpeg = [0xFF, 0xD8, 0xFF, 0xE5, 0x00, 0x05, b'a', b'b', b'c', 0xFF, 0xD9];
+/// assert_eq!(ultrahdr::extract_app_segments(&jpeg, 0xE5), vec![b"abc".to_vec()]);
+/// ```
+pub fn extract_app_segments(bytes: &[u8], marker: u8) -> Vec<Vec<u8>> {
+    parse_header(bytes)
+        .map(|(segments, _)| {
+            segments
+                .into_iter()
+                .filter(|s| s.marker == marker)
+                .map(|s| s.data)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub(crate) const DRI: u8 = 0xDD;
+
+/// Frame properties of the primary image, read from its header without decoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JpegInfo {
+    /// Image width in pixels.
+    pub width: u32,
+    /// Image height in pixels.
+    pub height: u32,
+    /// Sample precision in bits (8 for baseline JPEG).
+    pub precision: u8,
+    /// Number of color components.
+    pub components: u8,
+    /// Whether the frame is progressive (SOF2/6/10/14) rather than sequential.
+    pub progressive: bool,
+    /// Restart interval in MCUs from a DRI marker before the first scan, if any.
+    pub restart_interval: Option<u16>,
+}
+
+impl JpegInfo {
+    /// Parse the header of the first (primary) JPEG in `bytes`.
+    ///
+    /// Only markers before the first SOS are considered; a DRI placed between the scans of
+    /// a progressive image is not reported.
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let (segments, _) = parse_header(bytes)?;
+        let frame = segments
+            .iter()
+            .find(|s| is_sof(s.marker))
+            .ok_or_else(|| Error::invalid_param("JPEG has no SOF marker"))?;
+        let data = &frame.data;
+        if data.len() < 6 {
+            return Err(Error::invalid_param("truncated SOF segment"));
+        }
+        let restart_interval = segments
+            .iter()
+            .rfind(|s| s.marker == DRI && s.data.len() >= 2)
+            .map(|s| u16::from_be_bytes([s.data[0], s.data[1]]))
+            .filter(|&interval| interval != 0);
+        Ok(Self {
+            precision: data[0],
+            height: u16::from_be_bytes([data[1], data[2]]) as u32,
+            width: u16::from_be_bytes([data[3], data[4]]) as u32,
+            components: data[5],
+            progressive: matches!(frame.marker, 0xC2 | 0xC6 | 0xCA | 0xCE),
+            restart_interval,
+        })
+    }
+}
+
+/// Start-of-frame markers: 0xC0..=0xCF except DHT (C4), JPG (C8) and DAC (CC).
+pub(crate) fn is_sof(marker: u8) -> bool {
+    (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC)
+}
+
+/// Insert an application segment after the leading APPn block of the header.
+#[cfg(any(feature = "std", test))]
+pub(crate) fn insert_app_segment(segments: &mut Vec<Segment>, marker: u8, data: Vec<u8>) {
+    let at = segments
+        .iter()
+        .position(|s| !s.is_app())
+        .unwrap_or(segments.len());
+    segments.insert(at, Segment { marker, data });
+}
+
+#[cfg(any(feature = "std", test))]
+pub(crate) fn is_icc_segment(seg: &Segment) -> bool {
+    seg.marker == APP2 && seg.data.starts_with(ICC_SIGNATURE)
+}
+
+/// Replace any ICC profile in the header with `profile`, split into APP2 chunks.
+///
+/// The chunks go after the leading APP0/APP1 (JFIF, EXIF, XMP) segments, where encoders
+/// conventionally put them.
+#[cfg(any(feature = "std", test))]
+pub(crate) fn set_icc_profile(segments: &mut Vec<Segment>, profile: &[u8]) -> Result<()> {
+    let chunks: Vec<&[u8]> = profile
+        .chunks(MAX_SEGMENT_PAYLOAD - ICC_SIGNATURE.len() - 2)
+        .collect();
+    let count = u8::try_from(chunks.len())
+        .map_err(|_| Error::invalid_param("ICC profile needs more than 255 APP2 chunks"))?;
+    segments.retain(|s| !is_icc_segment(s));
+    let at = segments
+        .iter()
+        .position(|s| s.marker != APP0 && s.marker != APP1)
+        .unwrap_or(segments.len());
+    let chunks = chunks.into_iter().enumerate().map(|(i, chunk)| {
+        let mut data = ICC_SIGNATURE.to_vec();
+        // Sequence numbers are 1-based.
+        data.extend_from_slice(&[i as u8 + 1, count]);
+        data.extend_from_slice(chunk);
+        Segment { marker: APP2, data }
+    });
+    segments.splice(at..at, chunks);
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A tiny JPEG-shaped stream: SOI, header segments, fake scan, EOI.
+    pub(crate) fn jpeg(segments: &[Segment], scan: &[u8]) -> Vec<u8> {
+        let mut tail = vec![0xFF, SOS, 0x00, 0x02];
+        tail.extend_from_slice(scan);
+        tail.extend_from_slice(&[0xFF, EOI]);
+        serialize(segments, &tail).unwrap()
+    }
+
+    /// Baseline SOF0 frame header with `components` 8-bit components, all unsubsampled.
+    #[cfg(feature = "std")]
+    pub(crate) fn baseline_sof(width: u16, height: u16, components: u8) -> Segment {
+        let mut data = vec![8];
+        data.extend_from_slice(&height.to_be_bytes());
+        data.extend_from_slice(&width.to_be_bytes());
+        data.push(components);
+        for id in 1..=components {
+            data.extend_from_slice(&[id, 0x11, 0]);
+        }
+        Segment { marker: 0xC0, data }
+    }
+
+    /// Primary JPEG with a valid MPF index followed by a secondary JPEG.
+    pub(crate) fn ultrahdr_like(extra: &[Segment]) -> (Vec<u8>, Vec<u8>) {
+        ultrahdr_like_with(extra, &[])
+    }
+
+    /// Like [`ultrahdr_like`], with header segments in the secondary image too.
+    pub(crate) fn ultrahdr_like_with(
+        extra: &[Segment],
+        secondary_segments: &[Segment],
+    ) -> (Vec<u8>, Vec<u8>) {
+        let secondary = jpeg(secondary_segments, &[9, 9, 9]);
+        let mut segments = extra.to_vec();
+        segments.push(Segment {
+            marker: APP2,
+            data: mpf::payload(0, secondary.len() as u32, 0),
+        });
+        segments.push(Segment {
+            marker: 0xDB,
+            data: vec![0; 5],
+        });
+        let mut primary = jpeg(&segments, &[1, 2, 3, 4]);
+        let mpf_idx = segments.iter().position(|s| s.is_mpf()).unwrap();
+        let payload_at = payload_offset(&segments, mpf_idx);
+        let index = MpfIndex::parse(&segments[mpf_idx].data).unwrap();
+        let len = primary.len();
+        let offset = (len - payload_at - TIFF_HEADER_OFFSET) as u32;
+        let payload = &mut primary[payload_at..payload_at + segments[mpf_idx].data.len()];
+        assert!(index.set_entry(payload, 0, len as u32, 0));
+        assert!(index.set_entry(payload, 1, secondary.len() as u32, offset));
+        let mut stream = primary;
+        stream.extend_from_slice(&secondary);
+        (stream, secondary)
+    }
+
+    /// Resolve the secondary image through the MPF index of `stream`.
+    pub(crate) fn secondary_via_mpf(stream: &[u8]) -> &[u8] {
+        let (segments, _) = parse_header(stream).unwrap();
+        let (idx, index) = find_mpf(&segments).unwrap();
+        let payload_at = payload_offset(&segments, idx);
+        let payload = &stream[payload_at..payload_at + segments[idx].data.len()];
+        let primary = index.entry(payload, 0).unwrap();
+        let entry = index.entry(payload, 1).unwrap();
+        let start = payload_at + TIFF_HEADER_OFFSET + entry.offset as usize;
+        assert_eq!(start, primary.size as usize);
+        &stream[start..start + entry.size as usize]
+    }
+
+    #[test]
+    fn rewrite_primary_round_trips_unchanged() {
+        let (stream, _) = ultrahdr_like(&[]);
+        let out = rewrite_primary(&stream, |_| Ok(())).unwrap();
+        assert_eq!(out, stream);
+    }
+
+    #[test]
+    fn inserted_app_segment_keeps_gainmap_reachable() {
+        let app0 = Segment {
+            marker: APP0,
+            data: b"JFIF\0".to_vec(),
+        };
+        let (stream, secondary) = ultrahdr_like(std::slice::from_ref(&app0));
+        let out = rewrite_primary(&stream, |segments| {
+            insert_app_segment(segments, 0xEB, b"c2pa".to_vec());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(out.len(), stream.len() + 4 + 4);
+        let (segments, _) = parse_header(&out).unwrap();
+        let markers: Vec<u8> = segments.iter().map(|s| s.marker).collect();
+        assert_eq!(markers, vec![APP0, APP2, 0xEB, 0xDB]);
+        assert_eq!(secondary_via_mpf(&out), &secondary[..]);
+    }
+
+    #[test]
+    fn replaced_secondary_is_reachable_through_mpf() {
+        let (stream, secondary) = ultrahdr_like(&[]);
+        assert_eq!(secondary_image(&stream, 1).unwrap(), &secondary[..]);
+
+        let bigger = jpeg(
+            &[Segment {
+                marker: 0xE5,
+                data: b"grown".to_vec(),
+            }],
+            &[9, 9, 9],
+        );
+        let out = replace_secondary(&stream, 1, bigger.clone(), |_| Ok(())).unwrap();
+        assert_eq!(out.len(), stream.len() + 4 + 5);
+        assert_eq!(secondary_via_mpf(&out), &bigger[..]);
+        assert!(replace_secondary(&stream, 2, bigger, |_| Ok(())).is_err());
+    }
+
+    #[test]
+    fn replaced_primary_keeps_gainmap_reachable() {
+        let (stream, secondary) = ultrahdr_like(&[]);
+        let primary = primary_image(&stream).unwrap();
+        assert_eq!(primary.len(), stream.len() - secondary.len());
+
+        // Shrink the primary's scan; the MPF segment travels with the new header.
+        let (segments, _) = parse_header(primary).unwrap();
+        let smaller = jpeg(&segments, &[1]);
+        let out = replace_primary(&stream, smaller.clone()).unwrap();
+        assert_eq!(out.len(), stream.len() - 3);
+        // Same image, with the MPF entries patched to the new sizes/offsets.
+        let primary = primary_image(&out).unwrap();
+        assert_eq!(primary.len(), smaller.len());
+        assert!(primary.ends_with(&[0xFF, SOS, 0x00, 0x02, 1, 0xFF, EOI]));
+        assert_eq!(secondary_via_mpf(&out), &secondary[..]);
+
+        // A new header whose MPF lists only the primary cannot carry the gain-map entry.
+        let mut one_image = segments;
+        let mpf = one_image.iter_mut().find(|s| s.is_mpf()).unwrap();
+        mpf.data[22..26].copy_from_slice(&1u32.to_be_bytes()); // NumberOfImages
+        let err = replace_primary(&stream, jpeg(&one_image, &[1])).unwrap_err();
+        assert!(err.to_string().contains("secondary entry"), "{err}");
+    }
+
+    #[test]
+    fn extract_app_segments_returns_matching_payloads_in_order() {
+        let (stream, _) = ultrahdr_like(&[
+            Segment {
+                marker: 0xEB,
+                data: b"first".to_vec(),
+            },
+            Segment {
+                marker: 0xE5,
+                data: b"vendor".to_vec(),
+            },
+            Segment {
+                marker: 0xEB,
+                data: b"second".to_vec(),
+            },
+        ]);
+        assert_eq!(
+            extract_app_segments(&stream, 0xEB),
+            vec![b"first".to_vec(), b"second".to_vec()]
+        );
+        assert_eq!(
+            extract_app_segments(&stream, 0xE5),
+            vec![b"vendor".to_vec()]
+        );
+        assert!(extract_app_segments(&stream, 0xEC).is_empty());
+        assert!(extract_app_segments(b"not a jpeg", 0xEB).is_empty());
+    }
+
+    fn sof(marker: u8) -> Segment {
+        Segment {
+            marker,
+            data: vec![
+                8, 0x01, 0x20, 0x02, 0x80, 3, 1, 0x22, 0, 2, 0x11, 1, 3, 0x11, 1,
+            ],
+        }
+    }
+
+    #[test]
+    fn jpeg_info_reads_baseline_frame() {
+        let stream = jpeg(&[sof(0xC0)], &[1, 2]);
+        let info = JpegInfo::parse(&stream).unwrap();
+        assert_eq!((info.width, info.height), (640, 288));
+        assert_eq!((info.precision, info.components), (8, 3));
+        assert!(!info.progressive);
+        assert_eq!(info.restart_interval, None);
+    }
+
+    #[test]
+    fn jpeg_info_reads_progressive_and_restart_interval() {
+        let dht = Segment {
+            marker: 0xC4,
+            data: vec![0; 17],
+        };
+        let dri = Segment {
+            marker: DRI,
+            data: 16u16.to_be_bytes().to_vec(),
+        };
+        let stream = jpeg(&[dht, sof(0xC2), dri], &[1, 2]);
+        let info = JpegInfo::parse(&stream).unwrap();
+        assert!(info.progressive);
+        assert_eq!(info.restart_interval, Some(16));
+
+        assert!(JpegInfo::parse(&jpeg(&[], &[])).is_err());
+    }
+
+    #[test]
+    fn oversized_segment_is_rejected() {
+        let seg = Segment {
+            marker: 0xE5,
+            data: vec![0; MAX_SEGMENT_PAYLOAD + 1],
+        };
+        assert!(serialize(&[seg], &[]).is_err());
+    }
+
+    #[test]
+    fn icc_profile_replaces_existing_chunks_and_splits_large_profiles() {
+        let app1 = Segment {
+            marker: APP1,
+            data: b"Exif\0\0".to_vec(),
+        };
+        let mut old = ICC_SIGNATURE.to_vec();
+        old.extend_from_slice(&[1, 1, 0xAA]);
+        let mut segments = vec![
+            app1.clone(),
+            Segment {
+                marker: APP2,
+                data: old,
+            },
+        ];
+        let profile = vec![7; MAX_SEGMENT_PAYLOAD];
+        set_icc_profile(&mut segments, &profile).unwrap();
+
+        assert_eq!(segments[0], app1);
+        let chunks: Vec<_> = segments[1..].iter().map(|s| &s.data).collect();
+        assert_eq!(chunks.len(), 2);
+        assert!(segments[1..].iter().all(is_icc_segment));
+        assert_eq!(&chunks[0][ICC_SIGNATURE.len()..][..2], &[1, 2]);
+        assert_eq!(&chunks[1][ICC_SIGNATURE.len()..][..2], &[2, 2]);
+        let joined: Vec<u8> = chunks
+            .iter()
+            .flat_map(|c| c[ICC_SIGNATURE.len() + 2..].iter().copied())
+            .collect();
+        assert_eq!(joined, profile);
+        assert!(serialize(&segments, &[]).is_ok());
+    }
+}
@@ -8,21 +8,128 @@
 //! - [`Encoder`] writes UltraHDR or plain JPEGs from packed pixel buffers or compressed
 //!   inputs.
 //! - [`RawImage`], [`CompressedImage`], and [`DecodedPackedView`] describe image buffers
-//!   without requiring you to depend on [`sys`] directly.
+//!   without requiring you to depend on [`sys`] directly; [`OwnedPackedImage`] and
+//!   [`OwnedCompressedImage`] own theirs.
+//!
+//! # Concurrency
+//!
+//! `libultrahdr` has no process-wide state to initialize: every [`Encoder`] and [`Decoder`]
+//! owns its own codec context (including its libjpeg-turbo instances), so creating them
+//! from several threads at once needs no synchronization. A single instance is not
+//! internally synchronized and must only be used by one thread at a time.
+//!
+//! Both types are `Send`, so an instance can be moved into a worker thread (e.g. one per
+//! rayon task). The context has no thread affinity: libultrahdr keeps no thread-local
+//! state, and the only thread-bound resource it can hold, an EGL context for GPU
+//! acceleration, is never enabled by this crate (with or without the `gles` feature).
+//! Every access goes through `&mut self`, so the `Send` impls rely on exactly this; a
+//! handle adopted with `from_raw` must not have GPU acceleration enabled either.
+//!
+//! Neither type is `Sync`, and that is deliberate rather than an omission. Every
+//! `libultrahdr` entry point takes a mutable `uhdr_codec_private_t*`, including the ones
+//! that look read-only: `uhdr_dec_probe` caches parsed headers and gain-map state in the
+//! context, and the metadata/dimension getters read that cache. Two threads probing through
+//! a shared reference would race on it, so all wrapper methods take `&mut self` and there
+//! is no thread-safe subset to carve out into a separate shareable handle.
+//!
+//! For read-only inspection from many threads, use the pure-Rust helpers instead:
+//! [`JpegInfo::parse`] and [`extract_app_segments`] only borrow the input bytes and can be
+//! called concurrently on the same buffer.
+//!
+//! ```compile_fail
+//! fn assert_sync<T: Sync>() {}
+//! assert_sync::<ultrahdr::Decoder>();
+//! ```
+//!
+//! ```
+//! fn assert_send<T: Send>() {}
+//! assert_send::<ultrahdr::Decoder>();
+//! assert_send::<ultrahdr::Encoder>();
+//! ```
+//!
+//! `use ultrahdr::prelude::*;` brings the common types and enum aliases into scope.
 //!
 //! For a higher-level walkthrough, see `examples/ultrahdr_app.rs` in this crate and the
-//! CLI in the companion `ultrahdr-bake` package.
+//! CLI in the companion `ultrahdr-bake` package. Its Motion Photo muxer is available as
+//! `motion::build_motion_photo` behind the `motion` feature.
+//!
+//! # `no_std`
+//!
+//! The codec handles need `std` and are behind the default `std` feature. With
+//! `default-features = false` the crate is `#![no_std]` and needs only `alloc`; what
+//! remains is the pure-Rust core:
+//! - [`Error`] and [`Result`] ([`Error::io`] needs `std`);
+//! - the buffer descriptors and owned images of the types module: [`RawImage`],
+//!   [`CompressedImage`], [`OwnedCompressedImage`], [`OwnedPackedImage`], [`DecodedPacked`],
+//!   [`DecodedPackedView`], [`EncodedImage`] and [`EncodedView`];
+//! - the layout helpers [`bytes_per_pixel`], [`plane_layout`] and the RGBA1010102 packers;
+//! - [`GainMapMetadata`], [`ProbeInfo`] and the other plain data types and enums;
+//! - JPEG inspection with [`JpegInfo::parse`] and [`extract_app_segments`].
+//!
+//! [`sys`] still comes from `ultrahdr-sys`, which builds and links libultrahdr. On targets
+//! that cannot build it, set `ULTRAHDR_BINDINGS_ONLY=1` to generate the type definitions
+//! alone; the codec entry points are then left unresolved, so this only links without
+//! `std`. The `image` feature needs `std`.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
 
 /// Low-level bindings to `libultrahdr`. Most users should favor the safe wrappers
 /// re-exported from this crate.
 pub use ultrahdr_sys as sys;
 
+#[cfg(feature = "std")]
+mod conformance;
+#[cfg(feature = "std")]
 mod decoder;
+#[cfg(feature = "std")]
+mod dump;
+#[cfg(feature = "std")]
+mod effects;
+#[cfg(feature = "std")]
 mod encoder;
 mod error;
+mod exif;
+#[cfg(feature = "std")]
+mod gainmap;
+#[cfg(feature = "std")]
+mod huffman;
+#[cfg(feature = "std")]
+pub mod icc;
+mod jpeg;
+#[cfg(feature = "std")]
+mod merge;
+#[cfg(feature = "std")]
+mod metadata;
+#[cfg(feature = "motion")]
+pub mod motion;
+mod mpf;
+#[cfg(feature = "std")]
+pub mod prelude;
+#[cfg(feature = "std")]
+mod source;
+#[cfg(feature = "std")]
+mod stats;
 mod types;
 
+#[cfg(feature = "std")]
+pub use conformance::{CheckStatus, ConformanceCheck, ConformanceReport, check_conformance};
+#[cfg(feature = "std")]
 pub use decoder::Decoder;
-pub use encoder::Encoder;
-pub use error::{Error, Result};
+#[cfg(feature = "std")]
+pub use dump::{RAW_DUMP_HEADER_LEN, RAW_DUMP_MAGIC};
+#[cfg(feature = "std")]
+pub use encoder::{Encoder, EncoderBuilder};
+pub use error::{Error, ErrorKind, Result};
+pub use exif::strip_exif;
+#[cfg(feature = "std")]
+pub use gainmap::{assemble_gainmap_jpeg, replace_gainmap};
+pub use jpeg::{JpegInfo, extract_app_segments};
+#[cfg(feature = "std")]
+pub use merge::{HdrIntent, MergeOptions, merge_decoded, merge_hdr_sdr, rebake_gamma};
+#[cfg(feature = "std")]
+pub use source::{GainMapSource, JpegSource};
+#[cfg(feature = "std")]
+pub use stats::LumStats;
 pub use types::*;
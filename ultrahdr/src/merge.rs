@@ -0,0 +1,238 @@
+//! Combining the HDR intent of one UltraHDR JPEG with a separately graded SDR base, and
+//! re-baking an UltraHDR JPEG's gain map against its own base.
+
+use crate::decoder::Decoder;
+use crate::encoder::Encoder;
+use crate::error::{Error, Result};
+use crate::jpeg::JpegInfo;
+use crate::source::{GainMapSource, JpegSource};
+use crate::sys;
+use crate::types::{ColorGamut, CompressedImage, DecodedPacked, EncodedImage, ImgLabel};
+
+/// Target peak brightness used when neither the options nor the HDR input provide one.
+const DEFAULT_TARGET_PEAK_NITS: f32 = 1600.0;
+
+/// Settings for [`merge_hdr_sdr`].
+///
+/// The defaults match the `ultrahdr-bake` command line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MergeOptions {
+    /// JPEG quality of the SDR base image (1-100).
+    pub base_quality: i32,
+    /// JPEG quality of the gain map (1-100).
+    pub gainmap_quality: i32,
+    /// Gain map downscale factor relative to the base image.
+    pub gainmap_scale: i32,
+    /// Compute one gain map channel per color channel instead of a luma map.
+    pub multichannel_gainmap: bool,
+    /// Target display peak in nits. `None` uses the HDR input's gain-map metadata, or
+    /// 1600 nits when it has none.
+    pub target_peak_nits: Option<f32>,
+    /// Gamut of the HDR intent when the decoder cannot tell (e.g. from an ICC profile).
+    /// Display P3 is assumed if this is `None` too.
+    pub hdr_gamut: Option<ColorGamut>,
+    /// Gamut of the SDR base; Display P3 when `None`.
+    pub sdr_gamut: Option<ColorGamut>,
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        MergeOptions {
+            base_quality: 95,
+            gainmap_quality: 95,
+            gainmap_scale: 1,
+            multichannel_gainmap: false,
+            target_peak_nits: None,
+            hdr_gamut: None,
+            sdr_gamut: None,
+        }
+    }
+}
+
+/// Bake a new UltraHDR JPEG from the HDR intent of `hdr` and the SDR JPEG `sdr`.
+///
+/// `hdr` is decoded with [`HdrIntent::decode`]. The gain map is then computed against
+/// `sdr`, which is embedded as the base image without recompression, at gain-map gamma 1.0
+/// with the best-quality preset. To bake several variants from one HDR input, decode it
+/// once and call [`merge_decoded`] instead.
+pub fn merge_hdr_sdr(hdr: &mut [u8], sdr: &mut [u8], opts: &MergeOptions) -> Result<EncodedImage> {
+    check_target_peak(opts)?;
+    let mut intent = HdrIntent::decode(hdr, opts.hdr_gamut)?;
+    merge_decoded(&mut intent, sdr, opts)
+}
+
+/// [`merge_hdr_sdr`] with an HDR intent decoded beforehand. `opts.hdr_gamut` is ignored,
+/// as the gamut was resolved by [`HdrIntent::decode`].
+pub fn merge_decoded(
+    hdr: &mut HdrIntent,
+    sdr: &mut [u8],
+    opts: &MergeOptions,
+) -> Result<EncodedImage> {
+    let sdr_gamut = opts
+        .sdr_gamut
+        .unwrap_or(sys::uhdr_color_gamut::UHDR_CG_DISPLAY_P3);
+    bake(hdr, sdr, opts, 1.0, sdr_gamut)
+}
+
+/// HDR intent of an UltraHDR JPEG, decoded once for [`merge_decoded`].
+#[derive(Debug, Clone)]
+pub struct HdrIntent {
+    pixels: DecodedPacked,
+    source_peak_nits: Option<f32>,
+}
+
+impl HdrIntent {
+    /// Decode `hdr` to 10-bit PQ RGBA1010102.
+    ///
+    /// Gamut, transfer and range the decoder leaves unspecified are filled from `hdr_gamut`
+    /// (falling back to Display P3), PQ and full range.
+    pub fn decode(hdr: &mut [u8], hdr_gamut: Option<ColorGamut>) -> Result<Self> {
+        let mut dec = Decoder::new()?;
+        let mut comp = CompressedImage::from_bytes(
+            hdr,
+            hdr_gamut.unwrap_or(sys::uhdr_color_gamut::UHDR_CG_UNSPECIFIED),
+            sys::uhdr_color_transfer::UHDR_CT_UNSPECIFIED,
+            sys::uhdr_color_range::UHDR_CR_UNSPECIFIED,
+        )?;
+        dec.set_image(&mut comp)?;
+        let source_peak_nits = dec
+            .gainmap_metadata()?
+            .map(|meta| meta.target_display_peak_nits());
+        let mut pixels = dec.decode_to_owned(
+            sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA1010102,
+            sys::uhdr_color_transfer::UHDR_CT_PQ,
+        )?;
+        if pixels.cg == sys::uhdr_color_gamut::UHDR_CG_UNSPECIFIED {
+            pixels.cg = hdr_gamut.unwrap_or(sys::uhdr_color_gamut::UHDR_CG_DISPLAY_P3);
+        }
+        if pixels.ct == sys::uhdr_color_transfer::UHDR_CT_UNSPECIFIED {
+            pixels.ct = sys::uhdr_color_transfer::UHDR_CT_PQ;
+        }
+        if pixels.range == sys::uhdr_color_range::UHDR_CR_UNSPECIFIED {
+            pixels.range = sys::uhdr_color_range::UHDR_CR_FULL_RANGE;
+        }
+        Ok(Self {
+            pixels,
+            source_peak_nits,
+        })
+    }
+
+    /// The decoded pixels, e.g. as the reference when measuring a merged file.
+    pub fn pixels(&self) -> &DecodedPacked {
+        &self.pixels
+    }
+
+    /// Target display peak from the input's gain-map metadata, if it has any.
+    pub fn source_peak_nits(&self) -> Option<f32> {
+        self.source_peak_nits
+    }
+}
+
+/// Re-bake an UltraHDR JPEG with a different gain-map gamma, keeping its base image.
+///
+/// The HDR intent is decoded from `uhdr` and a new gain map is computed against the
+/// original base JPEG, which is embedded again without recompression. The gain-map scale
+/// factor and channel count are taken from the existing gain map and the target peak from
+/// its metadata; the gain map itself is re-encoded at quality 95. Fails if `uhdr` has no
+/// gain map or `new_gamma` is not a finite value greater than zero.
+pub fn rebake_gamma(uhdr: &[u8], new_gamma: f32) -> Result<Vec<u8>> {
+    if !(new_gamma.is_finite() && new_gamma > 0.0) {
+        return Err(Error::invalid_param(
+            "gain-map gamma must be finite and > 0",
+        ));
+    }
+    let src = JpegSource::new(uhdr);
+    let gainmap = src
+        .gainmap_image()?
+        .ok_or_else(|| Error::invalid_param("input has no gain map"))?;
+    let base_info = JpegInfo::parse(uhdr)?;
+    let gainmap_info = JpegInfo::parse(&gainmap.data)?;
+    let scale = base_info
+        .width
+        .checked_div(gainmap_info.width)
+        .ok_or_else(|| Error::invalid_param("gain map has zero width"))?
+        .max(1);
+    let opts = MergeOptions {
+        gainmap_scale: i32::try_from(scale)
+            .map_err(|_| Error::invalid_param("gain-map scale factor out of range"))?,
+        multichannel_gainmap: gainmap_info.components >= 3,
+        ..MergeOptions::default()
+    };
+    let mut base = src.base_image()?.data;
+    let mut intent = HdrIntent::decode(&mut uhdr.to_vec(), None)?;
+    // The base is the SDR rendition of the same photo, so it shares the HDR intent's gamut.
+    let sdr_gamut = intent.pixels.cg;
+    Ok(bake(&mut intent, &mut base, &opts, new_gamma, sdr_gamut)?.data)
+}
+
+fn check_target_peak(opts: &MergeOptions) -> Result<()> {
+    if opts.target_peak_nits.is_none_or(|peak| peak > 0.0) {
+        Ok(())
+    } else {
+        Err(Error::invalid_param(
+            "target peak brightness must be greater than zero nits",
+        ))
+    }
+}
+
+/// Shared encode step of [`merge_decoded`] and [`rebake_gamma`].
+fn bake(
+    hdr: &mut HdrIntent,
+    sdr: &mut [u8],
+    opts: &MergeOptions,
+    gamma: f32,
+    sdr_gamut: ColorGamut,
+) -> Result<EncodedImage> {
+    check_target_peak(opts)?;
+    let target_peak = opts
+        .target_peak_nits
+        .or(hdr.source_peak_nits)
+        .unwrap_or(DEFAULT_TARGET_PEAK_NITS);
+
+    let mut enc = Encoder::builder()
+        .base_quality(opts.base_quality)
+        .gainmap_quality(opts.gainmap_quality)
+        .gainmap_scale(opts.gainmap_scale)
+        .multichannel(opts.multichannel_gainmap)
+        .gamma(gamma)
+        .target_peak_nits(target_peak)
+        .output_format(sys::uhdr_codec::UHDR_CODEC_JPG)
+        .preset(sys::uhdr_enc_preset::UHDR_USAGE_BEST_QUALITY)
+        .build()?;
+    enc.set_decoded_packed(&mut hdr.pixels, ImgLabel::UHDR_HDR_IMG)?;
+    let mut sdr_comp = CompressedImage::from_bytes(
+        sdr,
+        sdr_gamut,
+        sys::uhdr_color_transfer::UHDR_CT_SRGB,
+        sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+    )?;
+    enc.set_compressed_image(&mut sdr_comp, ImgLabel::UHDR_SDR_IMG)?;
+    enc.encode_to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn non_positive_target_peak_is_rejected_before_decoding() {
+        let opts = MergeOptions {
+            target_peak_nits: Some(0.0),
+            ..MergeOptions::default()
+        };
+        let err = merge_hdr_sdr(&mut [0xFF], &mut [0xFF], &opts).unwrap_err();
+        assert_eq!(err.code, sys::uhdr_codec_err_t::UHDR_CODEC_INVALID_PARAM);
+        assert!(err.to_string().contains("zero nits"), "{err}");
+    }
+
+    #[test]
+    fn rebake_rejects_bad_gamma_and_plain_jpegs() {
+        for gamma in [0.0, -1.0, f32::INFINITY, f32::NAN] {
+            let err = rebake_gamma(&[0xFF, 0xD8], gamma).unwrap_err();
+            assert!(err.to_string().contains("gamma"), "{err}");
+        }
+        let plain = crate::jpeg::tests::jpeg(&[], &[1, 2, 3]);
+        let err = rebake_gamma(&plain, 2.0).unwrap_err();
+        assert!(err.to_string().contains("no gain map"), "{err}");
+    }
+}
@@ -0,0 +1,459 @@
+//! Serializers for the gain-map metadata carried by UltraHDR JPEGs: the ISO 21496-1
+//! binary block (APP2) and the Adobe `hdrgm` XMP packet (APP1) of the gain-map image,
+//! plus the post-processing that adds or drops either format in an encoded stream.
+
+use crate::decoder::Decoder;
+use crate::error::{Error, Result};
+use crate::jpeg::{self, Segment};
+use crate::mpf::MPF_SIGNATURE;
+use crate::sys;
+use crate::types::{CompressedImage, GainMapMetadata};
+
+/// Prefix of the APP1 payload carrying an XMP packet.
+pub(crate) const XMP_NAMESPACE: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+/// Prefix of the APP2 payload carrying ISO 21496-1 gain-map metadata.
+pub(crate) const ISO_NAMESPACE: &[u8] = b"urn:iso:std:iso:ts:21496:-1\0";
+
+const HDRGM_NAMESPACE: &[u8] = b"http://ns.adobe.com/hdr-gain-map/1.0/";
+
+const FLAG_MULTI_CHANNEL: u8 = 1 << 7;
+const FLAG_USE_BASE_COLOR_SPACE: u8 = 1 << 6;
+const FLAG_BACKWARD_DIRECTION: u8 = 1 << 2;
+
+/// Denominator used for every ISO fraction; values are scaled down by 10 until they fit.
+const DENOMINATOR: u32 = 1_000_000;
+
+/// Whether an APP2 payload is an ISO 21496-1 block.
+pub(crate) fn is_iso(payload: &[u8]) -> bool {
+    payload.starts_with(ISO_NAMESPACE)
+}
+
+/// Whether an APP1 payload is an XMP packet using the `hdrgm` namespace.
+pub(crate) fn is_hdrgm_xmp(payload: &[u8]) -> bool {
+    payload.starts_with(XMP_NAMESPACE) && find(payload, HDRGM_NAMESPACE).is_some()
+}
+
+/// Full APP2 payload (namespace included) describing `meta` for the gain-map image.
+pub(crate) fn iso_payload(meta: &GainMapMetadata) -> Vec<u8> {
+    let channels = if is_multi_channel(meta) { 3 } else { 1 };
+    let mut flags = 0;
+    if channels == 3 {
+        flags |= FLAG_MULTI_CHANNEL;
+    }
+    if meta.use_base_cg {
+        flags |= FLAG_USE_BASE_COLOR_SPACE;
+    }
+    if meta.base_rendition_is_hdr {
+        flags |= FLAG_BACKWARD_DIRECTION;
+    }
+
+    let mut out = ISO_NAMESPACE.to_vec();
+    out.extend_from_slice(&0u16.to_be_bytes()); // minimum_version
+    out.extend_from_slice(&0u16.to_be_bytes()); // writer_version
+    out.push(flags);
+    // ISO 21496-1 fields describe the base-to-alternate mapping, while `meta` keeps
+    // libultrahdr's SDR-to-HDR convention; an HDR base swaps every pair and negates the gains.
+    let backward = meta.base_rendition_is_hdr;
+    let (base_headroom, alternate_headroom) = if backward {
+        (meta.hdr_capacity_max, meta.hdr_capacity_min)
+    } else {
+        (meta.hdr_capacity_min, meta.hdr_capacity_max)
+    };
+    push_unsigned(&mut out, base_headroom.log2());
+    push_unsigned(&mut out, alternate_headroom.log2());
+    for c in 0..channels {
+        let (min, max) = (
+            meta.min_content_boost[c].log2(),
+            meta.max_content_boost[c].log2(),
+        );
+        let (gain_min, gain_max) = if backward { (-max, -min) } else { (min, max) };
+        let (base_offset, alternate_offset) = if backward {
+            (meta.offset_hdr[c], meta.offset_sdr[c])
+        } else {
+            (meta.offset_sdr[c], meta.offset_hdr[c])
+        };
+        push_signed(&mut out, gain_min);
+        push_signed(&mut out, gain_max);
+        push_unsigned(&mut out, meta.gamma[c]);
+        push_signed(&mut out, base_offset);
+        push_signed(&mut out, alternate_offset);
+    }
+    out
+}
+
+/// Full APP1 payload (namespace included) with the `hdrgm` description of `meta`.
+///
+/// Like libultrahdr, only the first channel is written; the ISO block carries the rest.
+pub(crate) fn xmp_payload(meta: &GainMapMetadata) -> Vec<u8> {
+    let xmp = format!(
+        concat!(
+            r#"<x:xmpmeta xmlns:x="adobe:ns:meta/" x:xmptk="Adobe XMP Core 5.1.2">"#,
+            r#"<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">"#,
+            r#"<rdf:Description rdf:about="" xmlns:hdrgm="http://ns.adobe.com/hdr-gain-map/1.0/""#,
+            r#" hdrgm:Version="1.0" hdrgm:GainMapMin="{}" hdrgm:GainMapMax="{}""#,
+            r#" hdrgm:Gamma="{}" hdrgm:OffsetSDR="{}" hdrgm:OffsetHDR="{}""#,
+            r#" hdrgm:HDRCapacityMin="{}" hdrgm:HDRCapacityMax="{}""#,
+            r#" hdrgm:BaseRenditionIsHDR="{}"/>"#,
+            r#"</rdf:RDF></x:xmpmeta>"#
+        ),
+        meta.min_content_boost[0].log2(),
+        meta.max_content_boost[0].log2(),
+        meta.gamma[0],
+        meta.offset_sdr[0],
+        meta.offset_hdr[0],
+        meta.hdr_capacity_min.log2(),
+        meta.hdr_capacity_max.log2(),
+        if meta.base_rendition_is_hdr {
+            "True"
+        } else {
+            "False"
+        },
+    );
+    let mut out = XMP_NAMESPACE.to_vec();
+    out.extend_from_slice(xmp.as_bytes());
+    out
+}
+
+/// APP1 payload for the primary image: `hdrgm:Version` plus the Container directory that
+/// points legacy XMP readers at the gain map.
+pub(crate) fn primary_xmp_payload(gainmap_len: usize) -> Vec<u8> {
+    let xmp = format!(
+        concat!(
+            r#"<x:xmpmeta xmlns:x="adobe:ns:meta/" x:xmptk="Adobe XMP Core 5.1.2">"#,
+            r#"<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">"#,
+            r#"<rdf:Description rdf:about="""#,
+            r#" xmlns:Container="http://ns.google.com/photos/1.0/container/""#,
+            r#" xmlns:Item="http://ns.google.com/photos/1.0/container/item/""#,
+            r#" xmlns:hdrgm="http://ns.adobe.com/hdr-gain-map/1.0/" hdrgm:Version="1.0">"#,
+            r#"<Container:Directory><rdf:Seq>"#,
+            r#"<rdf:li rdf:parseType="Resource">"#,
+            r#"<Container:Item Item:Semantic="Primary" Item:Mime="image/jpeg"/></rdf:li>"#,
+            r#"<rdf:li rdf:parseType="Resource">"#,
+            r#"<Container:Item Item:Semantic="GainMap" Item:Mime="image/jpeg" Item:Length="{}"/>"#,
+            r#"</rdf:li></rdf:Seq></Container:Directory>"#,
+            r#"</rdf:Description></rdf:RDF></x:xmpmeta>"#
+        ),
+        gainmap_len
+    );
+    let mut out = XMP_NAMESPACE.to_vec();
+    out.extend_from_slice(xmp.as_bytes());
+    out
+}
+
+/// APP2 payload for the primary image: the ISO 21496-1 version-only block.
+pub(crate) fn iso_version_payload() -> Vec<u8> {
+    let mut out = ISO_NAMESPACE.to_vec();
+    out.extend_from_slice(&0u16.to_be_bytes()); // minimum_version
+    out.extend_from_slice(&0u16.to_be_bytes()); // writer_version
+    out
+}
+
+/// Make an UltraHDR stream carry exactly the requested gain-map metadata formats.
+///
+/// Missing formats are generated from the metadata libultrahdr reads back from `stream`;
+/// unwanted ones are dropped from both images. Streams without a gain map are returned
+/// unchanged.
+pub(crate) fn apply_formats(stream: &[u8], iso: bool, xmp: bool) -> Result<Vec<u8>> {
+    let Ok(gainmap) = jpeg::secondary_image(stream, 1) else {
+        return Ok(stream.to_vec());
+    };
+    let (mut segments, scan_at) = jpeg::parse_header(gainmap)?;
+    let has_iso = segments.iter().any(is_iso_segment);
+    let has_xmp = segments.iter().any(is_xmp_segment);
+
+    let injected = (iso && !has_iso) || (xmp && !has_xmp);
+    if injected {
+        let meta = probe_metadata(stream)?;
+        if iso && !has_iso {
+            jpeg::insert_app_segment(&mut segments, jpeg::APP2, iso_payload(&meta));
+        }
+        if xmp && !has_xmp {
+            insert_xmp(&mut segments, xmp_payload(&meta));
+        }
+    }
+    segments.retain(|s| (iso || !is_iso_segment(s)) && (xmp || !is_xmp_segment(s)));
+    let image = jpeg::serialize(&segments, &gainmap[scan_at..])?;
+    let image_len = image.len();
+
+    let out = jpeg::replace_secondary(stream, 1, image, |primary| {
+        primary.retain(|s| (iso || !is_iso_segment(s)) && (xmp || !is_xmp_segment(s)));
+        if iso && !primary.iter().any(is_iso_segment) {
+            jpeg::insert_app_segment(primary, jpeg::APP2, iso_version_payload());
+        }
+        if xmp {
+            let existing = primary
+                .iter_mut()
+                .find(|s| s.marker == jpeg::APP1 && s.data.starts_with(XMP_NAMESPACE));
+            match existing {
+                Some(seg) => {
+                    set_container_item_length(&mut seg.data, "GainMap", image_len);
+                }
+                None => insert_xmp(primary, primary_xmp_payload(image_len)),
+            }
+        }
+        Ok(())
+    })?;
+
+    if injected {
+        // Make sure the reassembled file still reads back as UltraHDR.
+        probe_metadata(&out)?;
+    }
+    Ok(out)
+}
+
+/// Regenerate every ISO 21496-1 block and `hdrgm` XMP packet in a gain-map image header
+/// from `meta`. Returns `false` when the header carries neither.
+pub(crate) fn regenerate(segments: &mut [Segment], meta: &GainMapMetadata) -> bool {
+    let mut replaced = false;
+    for seg in segments {
+        if is_iso_segment(seg) {
+            seg.data = iso_payload(meta);
+            replaced = true;
+        } else if is_xmp_segment(seg) {
+            seg.data = xmp_payload(meta);
+            replaced = true;
+        }
+    }
+    replaced
+}
+
+/// Drop the container-level gain-map segments (MPF index, ISO 21496-1 block, `hdrgm` XMP)
+/// from a primary image header.
+pub(crate) fn strip_gainmap_segments(segments: &mut Vec<Segment>) {
+    segments.retain(|s| {
+        let gainmap_app2 =
+            s.marker == jpeg::APP2 && (s.data.starts_with(MPF_SIGNATURE) || is_iso(&s.data));
+        let gainmap_xmp = s.marker == jpeg::APP1 && is_hdrgm_xmp(&s.data);
+        !gainmap_app2 && !gainmap_xmp
+    });
+}
+
+/// Swap the gain-map image of `stream` for `image`, keeping the primary image verbatim.
+///
+/// The MPF index and the primary XMP `Container` length are updated for the new size.
+pub(crate) fn splice_gainmap(stream: &[u8], image: Vec<u8>) -> Result<Vec<u8>> {
+    let image_len = image.len();
+    jpeg::replace_secondary(stream, 1, image, |primary| {
+        for seg in primary.iter_mut() {
+            if seg.marker == jpeg::APP1 && seg.data.starts_with(XMP_NAMESPACE) {
+                set_container_item_length(&mut seg.data, "GainMap", image_len);
+            }
+        }
+        Ok(())
+    })
+}
+
+pub(crate) fn is_iso_segment(seg: &Segment) -> bool {
+    seg.marker == jpeg::APP2 && is_iso(&seg.data)
+}
+
+pub(crate) fn is_xmp_segment(seg: &Segment) -> bool {
+    seg.marker == jpeg::APP1 && is_hdrgm_xmp(&seg.data)
+}
+
+/// Whether the gain-map image's metadata marks the base image as the HDR rendition.
+///
+/// The ISO backward-direction flag wins when present; otherwise the `hdrgm` XMP
+/// `BaseRenditionIsHDR` property is consulted, in attribute or element form.
+pub(crate) fn base_rendition_is_hdr(gainmap: &[u8]) -> bool {
+    let Ok((segments, _)) = jpeg::parse_header(gainmap) else {
+        return false;
+    };
+    if let Some(iso) = segments.iter().find(|s| is_iso_segment(s)) {
+        return iso
+            .data
+            .get(ISO_NAMESPACE.len() + 4)
+            .is_some_and(|flags| flags & FLAG_BACKWARD_DIRECTION != 0);
+    }
+    segments.iter().filter(|s| is_xmp_segment(s)).any(|s| {
+        find(&s.data, br#"hdrgm:BaseRenditionIsHDR="True""#).is_some()
+            || find(&s.data, b"<hdrgm:BaseRenditionIsHDR>True<").is_some()
+    })
+}
+
+/// Insert an XMP packet after the leading APP0/APP1 segments.
+pub(crate) fn insert_xmp(segments: &mut Vec<Segment>, data: Vec<u8>) {
+    let at = segments
+        .iter()
+        .position(|s| s.marker != jpeg::APP0 && s.marker != jpeg::APP1)
+        .unwrap_or(segments.len());
+    segments.insert(
+        at,
+        Segment {
+            marker: jpeg::APP1,
+            data,
+        },
+    );
+}
+
+pub(crate) fn probe_metadata(stream: &[u8]) -> Result<GainMapMetadata> {
+    let mut comp = CompressedImage::from_slice(
+        stream,
+        sys::uhdr_color_gamut::UHDR_CG_UNSPECIFIED,
+        sys::uhdr_color_transfer::UHDR_CT_UNSPECIFIED,
+        sys::uhdr_color_range::UHDR_CR_UNSPECIFIED,
+    )?;
+    let mut dec = Decoder::new()?;
+    dec.set_image(&mut comp)?;
+    dec.gainmap_metadata()?
+        .ok_or_else(|| Error::invalid_param("stream does not probe as UltraHDR"))
+}
+
+/// Rewrite `Item:Length` of the Container item with `Item:Semantic="<semantic>"`.
+///
+/// Returns `false` when the packet has no such item or the item carries no length.
+pub(crate) fn set_container_item_length(xmp: &mut Vec<u8>, semantic: &str, len: usize) -> bool {
+    let needle = format!(r#"Item:Semantic="{semantic}""#);
+    let Some(at) = find(xmp, needle.as_bytes()) else {
+        return false;
+    };
+    let Some(start) = xmp[..at].iter().rposition(|&b| b == b'<') else {
+        return false;
+    };
+    let Some(end) = xmp[at..].iter().position(|&b| b == b'>').map(|e| at + e) else {
+        return false;
+    };
+    const ATTR: &[u8] = br#"Item:Length=""#;
+    let Some(value_at) = find(&xmp[start..end], ATTR).map(|p| start + p + ATTR.len()) else {
+        return false;
+    };
+    let Some(value_end) = xmp[value_at..end]
+        .iter()
+        .position(|&b| b == b'"')
+        .map(|p| value_at + p)
+    else {
+        return false;
+    };
+    xmp.splice(value_at..value_end, len.to_string().into_bytes());
+    true
+}
+
+fn is_multi_channel(meta: &GainMapMetadata) -> bool {
+    let differs = |v: &[f32; 3]| v[1] != v[0] || v[2] != v[0];
+    differs(&meta.max_content_boost)
+        || differs(&meta.min_content_boost)
+        || differs(&meta.gamma)
+        || differs(&meta.offset_sdr)
+        || differs(&meta.offset_hdr)
+}
+
+fn push_signed(out: &mut Vec<u8>, v: f32) {
+    let mut d = DENOMINATOR;
+    while d > 1 && (f64::from(v) * f64::from(d)).abs() > f64::from(i32::MAX) {
+        d /= 10;
+    }
+    let n = (f64::from(v) * f64::from(d)).round() as i32;
+    out.extend_from_slice(&n.to_be_bytes());
+    out.extend_from_slice(&d.to_be_bytes());
+}
+
+fn push_unsigned(out: &mut Vec<u8>, v: f32) {
+    let mut d = DENOMINATOR;
+    while d > 1 && f64::from(v) * f64::from(d) > f64::from(u32::MAX) {
+        d /= 10;
+    }
+    let n = (f64::from(v.max(0.0)) * f64::from(d)).round() as u32;
+    out.extend_from_slice(&n.to_be_bytes());
+    out.extend_from_slice(&d.to_be_bytes());
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jpeg::tests::{secondary_via_mpf, ultrahdr_like_with};
+    use crate::types::tests::sample_metadata;
+
+    #[test]
+    fn apply_formats_drops_unwanted_formats() {
+        let (stream, _) = ultrahdr_like_with(
+            &[
+                Segment {
+                    marker: jpeg::APP1,
+                    data: primary_xmp_payload(0),
+                },
+                Segment {
+                    marker: jpeg::APP2,
+                    data: iso_version_payload(),
+                },
+            ],
+            &[
+                Segment {
+                    marker: jpeg::APP1,
+                    data: xmp_payload(&sample_metadata(4.0)),
+                },
+                Segment {
+                    marker: jpeg::APP2,
+                    data: iso_payload(&sample_metadata(4.0)),
+                },
+            ],
+        );
+
+        let iso_only = apply_formats(&stream, true, false).unwrap();
+        let gainmap = secondary_via_mpf(&iso_only);
+        assert!(jpeg::extract_app_segments(gainmap, jpeg::APP1).is_empty());
+        assert_eq!(
+            jpeg::extract_app_segments(gainmap, jpeg::APP2),
+            vec![iso_payload(&sample_metadata(4.0))]
+        );
+        assert!(jpeg::extract_app_segments(&iso_only, jpeg::APP1).is_empty());
+
+        let xmp_only = apply_formats(&stream, false, true).unwrap();
+        let gainmap = secondary_via_mpf(&xmp_only);
+        assert!(jpeg::extract_app_segments(gainmap, jpeg::APP2).is_empty());
+        assert_eq!(
+            jpeg::extract_app_segments(&xmp_only, jpeg::APP1),
+            vec![primary_xmp_payload(gainmap.len())]
+        );
+
+        let both = apply_formats(&stream, true, true).unwrap();
+        assert_eq!(secondary_via_mpf(&both), secondary_via_mpf(&stream));
+    }
+
+    #[test]
+    fn base_rendition_is_hdr_sets_backward_direction() {
+        let flags_at = ISO_NAMESPACE.len() + 4;
+        assert_eq!(
+            iso_payload(&sample_metadata(4.0))[flags_at] & FLAG_BACKWARD_DIRECTION,
+            0
+        );
+
+        let mut meta = sample_metadata(4.0);
+        meta.base_rendition_is_hdr = true;
+        assert_ne!(iso_payload(&meta)[flags_at] & FLAG_BACKWARD_DIRECTION, 0);
+        assert!(find(&xmp_payload(&meta), br#"hdrgm:BaseRenditionIsHDR="True""#).is_some());
+    }
+
+    #[test]
+    fn base_rendition_is_hdr_reads_iso_then_xmp() {
+        let mut hdr = sample_metadata(4.0);
+        hdr.base_rendition_is_hdr = true;
+        let gainmap = |segments: &[Segment]| {
+            let (stream, _) = ultrahdr_like_with(&[], segments);
+            secondary_via_mpf(&stream).to_vec()
+        };
+        let seg = |marker, data| Segment { marker, data };
+
+        assert!(!base_rendition_is_hdr(&gainmap(&[])));
+        assert!(base_rendition_is_hdr(&gainmap(&[seg(
+            jpeg::APP2,
+            iso_payload(&hdr)
+        )])));
+        assert!(base_rendition_is_hdr(&gainmap(&[seg(
+            jpeg::APP1,
+            xmp_payload(&hdr)
+        )])));
+        assert!(!base_rendition_is_hdr(&gainmap(&[seg(
+            jpeg::APP1,
+            xmp_payload(&sample_metadata(4.0))
+        )])));
+        // The ISO flags take precedence over a disagreeing XMP packet.
+        assert!(!base_rendition_is_hdr(&gainmap(&[
+            seg(jpeg::APP1, xmp_payload(&hdr)),
+            seg(jpeg::APP2, iso_payload(&sample_metadata(4.0))),
+        ])));
+    }
+}
@@ -0,0 +1,854 @@
+//! Motion Photo muxing: a still JPEG (optionally UltraHDR) followed by an MP4 clip.
+//!
+//! [`build_motion_photo`] writes the Google Camera Motion Photo v1 XMP (`GCamera:*` plus a
+//! `Container:Directory` listing every item) into the primary image and appends the video
+//! and any extra [`SecondaryItem`]s. For UltraHDR inputs the gain map stays in place right
+//! after the primary image and the MPF index is updated so it remains reachable.
+//!
+//! ```no_run
+//! # fn main() -> ultrahdr::Result<()> {
+//! use ultrahdr::motion::{MotionOptions, build_motion_photo};
+//!
+//! let still = std::fs::read("still.jpg").map_err(ultrahdr::Error::io)?;
+//! let clip = std::fs::read("clip.mp4").map_err(ultrahdr::Error::io)?;
+//! let opts = MotionOptions {
+//!     presentation_timestamp_us: 1_500_000,
+//!     ..Default::default()
+//! };
+//! let photo = build_motion_photo(&still, &clip, &opts)?;
+//! std::fs::write("motion.jpg", &photo.data).map_err(ultrahdr::Error::io)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::{Error, Result};
+use crate::jpeg::{self, APP1, Segment};
+use crate::metadata::{self, XMP_NAMESPACE};
+use quick_xml::{
+    Reader, Writer,
+    events::{BytesEnd, BytesStart, Event},
+};
+
+/// Rebuilds allowed for the primary length recorded in the XMP to settle.
+const MAX_LAYOUT_PASSES: usize = 4;
+
+/// A media item stored after the primary image (and its gain map) in the container.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecondaryItem {
+    /// MIME type recorded as `Item:Mime`, e.g. `image/jpeg`.
+    pub mime: String,
+    /// Role recorded as `Item:Semantic`, e.g. `Depth`.
+    pub semantic: String,
+    /// Item bytes, appended verbatim.
+    pub data: Vec<u8>,
+    /// Zero bytes written after `data`, recorded as the item's `Item:Padding`.
+    pub padding: usize,
+}
+
+impl SecondaryItem {
+    /// The Motion Photo video item (`video/mp4`, semantic `MotionPhoto`).
+    pub fn video(data: Vec<u8>) -> Self {
+        Self {
+            mime: "video/mp4".to_string(),
+            semantic: "MotionPhoto".to_string(),
+            data,
+            padding: 0,
+        }
+    }
+}
+
+/// Settings for [`build_motion_photo`].
+#[derive(Debug, Clone, Default)]
+pub struct MotionOptions {
+    /// Frame of the video the still corresponds to, written as
+    /// `GCamera:MotionPhotoPresentationTimestampUs`.
+    pub presentation_timestamp_us: u64,
+    /// Extra items (depth maps, alternate renditions, ...) stored alongside the video, in
+    /// file order.
+    pub items: Vec<SecondaryItem>,
+    /// Position of the video among [`items`](Self::items); `None` appends it last.
+    pub video_index: Option<usize>,
+}
+
+/// A muxed Motion Photo and where each part of it landed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MotionPhoto {
+    /// The complete file.
+    pub data: Vec<u8>,
+    /// Length of the primary JPEG, Motion Photo XMP included.
+    pub primary_len: usize,
+    /// Length of the gain-map JPEG stored right after the primary image, if any.
+    pub gainmap_len: Option<usize>,
+    /// Every `Container:Directory` item after the primary image, in file order.
+    pub items: Vec<ItemLayout>,
+}
+
+/// Position of one container item in a [`MotionPhoto`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ItemLayout {
+    /// `Item:Mime` of the item.
+    pub mime: String,
+    /// `Item:Semantic` of the item.
+    pub semantic: String,
+    /// Byte offset of the item from the start of the file.
+    pub offset: usize,
+    /// Item length, padding excluded.
+    pub len: usize,
+    /// Zero bytes following the item.
+    pub padding: usize,
+}
+
+/// Mux `primary_jpeg` and the MP4 `video` into a Motion Photo.
+///
+/// Existing XMP in the primary image is kept and only its Motion Photo properties and
+/// container directory are rewritten; legacy `GCamera:MicroVideo*` properties, when
+/// present, are rewritten to point at the new video. MPF secondary images are kept
+/// directly after the primary image and listed in the directory: image 1 as the
+/// `GainMap` item when it carries `hdrgm` XMP or ISO 21496-1 metadata, and any other
+/// image (e.g. a camera preview) as a `Secondary` item.
+///
+/// Fails if `video` is not a complete MP4 (see [`check_mp4_structure`]), if
+/// [`MotionOptions::video_index`] is past the end of the items, or if `primary_jpeg` is not
+/// a JPEG stream.
+pub fn build_motion_photo(
+    primary_jpeg: &[u8],
+    video: &[u8],
+    opts: &MotionOptions,
+) -> Result<MotionPhoto> {
+    check_mp4_structure(video)
+        .map_err(|e| Error::invalid_param(format!("video is not a complete MP4: {e}")))?;
+    let video_index = opts.video_index.unwrap_or(opts.items.len());
+    if video_index > opts.items.len() {
+        return Err(Error::invalid_param(format!(
+            "video index {video_index} is past the {} extra items",
+            opts.items.len()
+        )));
+    }
+    let mut items = opts.items.clone();
+    items.insert(video_index, SecondaryItem::video(video.to_vec()));
+    let micro_video_offset = items[video_index..]
+        .iter()
+        .map(|item| item.data.len() + item.padding)
+        .sum();
+
+    let mpf_images = mpf_images(primary_jpeg)?;
+    let primary = mux_primary(
+        primary_jpeg,
+        &mpf_images,
+        &items,
+        opts.presentation_timestamp_us,
+        micro_video_offset,
+    )?;
+    let primary_len = jpeg::primary_image(&primary)?.len();
+
+    let mut layout = Vec::with_capacity(mpf_images.len() + items.len());
+    let mut offset = primary_len;
+    for &(semantic, len) in &mpf_images {
+        layout.push(ItemLayout {
+            mime: "image/jpeg".to_string(),
+            semantic: semantic.to_string(),
+            offset,
+            len,
+            padding: 0,
+        });
+        offset += len;
+    }
+    for item in &items {
+        layout.push(ItemLayout {
+            mime: item.mime.clone(),
+            semantic: item.semantic.clone(),
+            offset,
+            len: item.data.len(),
+            padding: item.padding,
+        });
+        offset += item.data.len() + item.padding;
+    }
+    Ok(MotionPhoto {
+        data: concat_container(&primary, &items),
+        primary_len,
+        gainmap_len: mpf_images
+            .iter()
+            .find(|(semantic, _)| *semantic == GAINMAP_SEMANTIC)
+            .map(|&(_, len)| len),
+        items: layout,
+    })
+}
+
+const GAINMAP_SEMANTIC: &str = "GainMap";
+
+/// MPF images following the primary image, as `(semantic, length)` in file order.
+///
+/// Image 1 is the `GainMap` only when it carries gain-map metadata; other MPF images
+/// (previews, thumbnails) are listed as `Secondary` so the directory still covers them.
+fn mpf_images(primary_jpeg: &[u8]) -> Result<Vec<(&'static str, usize)>> {
+    let Some(entries) = jpeg::mpf_entries(primary_jpeg)? else {
+        return Ok(Vec::new());
+    };
+    let mut images: Vec<_> = entries
+        .iter()
+        .enumerate()
+        .skip(1)
+        .filter(|(_, (entry, start))| *start != 0 && entry.size != 0)
+        .map(|(i, &(entry, start))| {
+            let semantic = if i == 1 && has_gainmap_metadata(primary_jpeg) {
+                GAINMAP_SEMANTIC
+            } else {
+                "Secondary"
+            };
+            (start, semantic, entry.size as usize)
+        })
+        .collect();
+    images.sort_by_key(|&(start, _, _)| start);
+    Ok(images
+        .into_iter()
+        .map(|(_, semantic, len)| (semantic, len))
+        .collect())
+}
+
+/// Whether MPF image 1 carries an `hdrgm` XMP packet or an ISO 21496-1 block.
+fn has_gainmap_metadata(stream: &[u8]) -> bool {
+    jpeg::secondary_image(stream, 1)
+        .and_then(jpeg::parse_header)
+        .is_ok_and(|(segments, _)| {
+            segments
+                .iter()
+                .any(|s| metadata::is_iso_segment(s) || metadata::is_xmp_segment(s))
+        })
+}
+
+/// The primary JPEG (plus MPF images) carrying the Motion Photo XMP for `items`.
+fn mux_primary(
+    primary_jpeg: &[u8],
+    mpf_images: &[(&str, usize)],
+    items: &[SecondaryItem],
+    presentation_timestamp_us: u64,
+    micro_video_offset: usize,
+) -> Result<Vec<u8>> {
+    let (segments, _) = jpeg::parse_header(primary_jpeg)?;
+    let existing_xmp = segments
+        .iter()
+        .find(|s| is_xmp_segment(s))
+        .map(|s| s.data[XMP_NAMESPACE.len()..].to_vec());
+
+    // The XMP records the primary length, which depends on the XMP; rebuild until stable.
+    let mut meta = MotionMeta {
+        primary_len: jpeg::primary_image(primary_jpeg)?.len(),
+        mpf_images,
+        items,
+        presentation_timestamp_us,
+        micro_video_offset,
+    };
+    for _ in 0..MAX_LAYOUT_PASSES {
+        let mut payload = XMP_NAMESPACE.to_vec();
+        payload.extend_from_slice(&build_motion_xmp(existing_xmp.as_deref(), &meta));
+        let out = jpeg::rewrite_primary(primary_jpeg, |segments| {
+            segments.retain(|s| !is_xmp_segment(s));
+            metadata::insert_xmp(segments, payload);
+            Ok(())
+        })?;
+        let primary_len = jpeg::primary_image(&out)?.len();
+        if primary_len == meta.primary_len {
+            return Ok(out);
+        }
+        meta.primary_len = primary_len;
+    }
+    Err(Error::invalid_param(
+        "Motion Photo XMP did not settle on a primary length",
+    ))
+}
+
+fn is_xmp_segment(seg: &Segment) -> bool {
+    seg.marker == APP1 && seg.data.starts_with(XMP_NAMESPACE)
+}
+
+/// The primary JPEG followed by every secondary item and its padding, in order.
+fn concat_container(primary: &[u8], items: &[SecondaryItem]) -> Vec<u8> {
+    let total = primary.len()
+        + items
+            .iter()
+            .map(|item| item.data.len() + item.padding)
+            .sum::<usize>();
+    let mut out = Vec::with_capacity(total);
+    out.extend_from_slice(primary);
+    for item in items {
+        out.extend_from_slice(&item.data);
+        out.resize(out.len() + item.padding, 0);
+    }
+    out
+}
+
+/// Walk the top-level MP4 boxes and require a complete `ftyp` + `moov` + `mdat` file.
+///
+/// Catches truncated downloads and fragmented (`moof`-only) recordings, which Google Photos
+/// rejects without a message; the box contents are not parsed.
+pub fn check_mp4_structure(data: &[u8]) -> Result<()> {
+    let mut pos = 0usize;
+    let (mut ftyp, mut moov, mut mdat) = (false, false, false);
+    while pos < data.len() {
+        let header = data
+            .get(pos..pos + 8)
+            .ok_or_else(|| Error::invalid_param(format!("truncated box header at offset {pos}")))?;
+        let kind: [u8; 4] = header[4..8].try_into().expect("4-byte slice");
+        let (size, header_len) = match u32::from_be_bytes(header[..4].try_into().unwrap()) {
+            // 64-bit size follows the type.
+            1 => {
+                let large = data.get(pos + 8..pos + 16).ok_or_else(|| {
+                    Error::invalid_param(format!("truncated 64-bit box size at offset {pos}"))
+                })?;
+                let size = u64::from_be_bytes(large.try_into().unwrap());
+                (usize::try_from(size).unwrap_or(usize::MAX), 16)
+            }
+            // Box runs to the end of the file.
+            0 => (data.len() - pos, 8),
+            size => (size as usize, 8),
+        };
+        let kind_str = String::from_utf8_lossy(&kind);
+        if size < header_len {
+            return Err(Error::invalid_param(format!(
+                "box '{kind_str}' at offset {pos} has invalid size {size}"
+            )));
+        }
+        if size > data.len() - pos {
+            return Err(Error::invalid_param(format!(
+                "box '{kind_str}' at offset {pos} claims {size} bytes but only {} remain",
+                data.len() - pos
+            )));
+        }
+        if pos == 0 && &kind != b"ftyp" {
+            return Err(Error::invalid_param("file does not start with an ftyp box"));
+        }
+        match &kind {
+            b"ftyp" => ftyp = true,
+            b"moov" => moov = true,
+            b"mdat" => mdat = true,
+            _ => {}
+        }
+        pos += size;
+    }
+    if !ftyp {
+        return Err(Error::invalid_param("missing ftyp box"));
+    }
+    if !moov {
+        return Err(Error::invalid_param(
+            "missing moov box (truncated or fragmented MP4)",
+        ));
+    }
+    if !mdat {
+        return Err(Error::invalid_param("missing mdat box (no media data)"));
+    }
+    Ok(())
+}
+
+struct MotionMeta<'a> {
+    primary_len: usize,
+    /// `(semantic, length)` of the MPF images right after the primary image.
+    mpf_images: &'a [(&'a str, usize)],
+    /// Secondary items after the primary image (and MPF images), in file order.
+    items: &'a [SecondaryItem],
+    presentation_timestamp_us: u64,
+    /// Bytes from the start of the video to the end of the file.
+    micro_video_offset: usize,
+}
+
+fn build_motion_xmp(existing: Option<&[u8]>, meta: &MotionMeta<'_>) -> Vec<u8> {
+    existing
+        .and_then(|existing| merge_into_existing_xmp(existing, meta).ok().flatten())
+        .unwrap_or_else(|| build_fresh_xmp(meta))
+}
+
+/// Motion Photo properties rewritten on every merge, in attribute or element form.
+const MOTION_PROPERTIES: &[&[u8]] = &[
+    b"GCamera:MotionPhoto",
+    b"GCamera:MotionPhotoVersion",
+    b"GCamera:MotionPhotoPresentationTimestampUs",
+    b"GCamera:MicroVideo",
+    b"GCamera:MicroVideoVersion",
+    b"GCamera:MicroVideoOffset",
+    b"GCamera:MicroVideoPresentationTimestampUs",
+];
+
+/// Whether `name` is one of the legacy Motion Photo v0 (`MicroVideo*`) properties.
+fn is_micro_video(name: &[u8]) -> bool {
+    name.starts_with(b"GCamera:MicroVideo")
+}
+
+/// Rewrite the Motion Photo bits of an existing packet and keep everything else.
+///
+/// The old `Container:Directory` and [`MOTION_PROPERTIES`] are dropped wherever they sit,
+/// and a fresh description carrying them is injected before `</rdf:RDF>`; the legacy
+/// `MicroVideo*` properties are only written back if the packet had them, with
+/// `MicroVideoOffset` recomputed for the new video. Other attributes and elements (e.g.
+/// `hdrgm:*`, `xmpMM:*`) pass through. Returns `None` when the packet has no `rdf:RDF` to
+/// inject into.
+fn merge_into_existing_xmp(
+    existing: &[u8],
+    meta: &MotionMeta<'_>,
+) -> quick_xml::Result<Option<Vec<u8>>> {
+    let mut reader = Reader::from_reader(existing);
+    reader.config_mut().trim_text(false);
+    let mut writer = Writer::new(Vec::with_capacity(existing.len() + 512));
+    let mut buf = Vec::new();
+    let mut injected = false;
+    let mut micro_video = false;
+    let mut dropping = 0usize;
+    let rewritten =
+        |name: &[u8]| name == b"Container:Directory" || MOTION_PROPERTIES.contains(&name);
+
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(ref e) if dropping == 0 && rewritten(e.name().as_ref()) => {
+                micro_video |= is_micro_video(e.name().as_ref());
+                dropping = 1;
+            }
+            Event::Empty(ref e) if dropping == 0 && rewritten(e.name().as_ref()) => {
+                micro_video |= is_micro_video(e.name().as_ref());
+            }
+            Event::Start(_) if dropping > 0 => {
+                dropping += 1;
+            }
+            Event::End(_) if dropping > 0 => {
+                dropping -= 1;
+            }
+            Event::Eof => break,
+            _ if dropping > 0 => {}
+            Event::Start(ref e) if e.name().as_ref() == b"rdf:Description" => {
+                let (desc, had_micro_video) = without_motion_attributes(e);
+                micro_video |= had_micro_video;
+                writer.write_event(Event::Start(desc))?;
+            }
+            Event::Empty(ref e) if e.name().as_ref() == b"rdf:Description" => {
+                let (desc, had_micro_video) = without_motion_attributes(e);
+                micro_video |= had_micro_video;
+                writer.write_event(Event::Empty(desc))?;
+            }
+            Event::End(ref e) if e.name().as_ref() == b"rdf:RDF" => {
+                if !injected {
+                    write_motion_description(&mut writer, meta, micro_video)?;
+                    injected = true;
+                }
+                writer.write_event(Event::End(e.to_owned()))?;
+            }
+            ev => writer.write_event(ev.to_owned())?,
+        }
+        buf.clear();
+    }
+
+    Ok(injected.then(|| writer.into_inner()))
+}
+
+/// Copy of an `rdf:Description` tag without its [`MOTION_PROPERTIES`] attributes, plus
+/// whether any of the dropped ones was a `MicroVideo*` property.
+fn without_motion_attributes(e: &BytesStart<'_>) -> (BytesStart<'static>, bool) {
+    let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
+    let mut out = BytesStart::new(name);
+    let mut micro_video = false;
+    for attr in e.attributes().with_checks(false).flatten() {
+        if MOTION_PROPERTIES.contains(&attr.key.as_ref()) {
+            micro_video |= is_micro_video(attr.key.as_ref());
+        } else {
+            out.push_attribute(attr);
+        }
+    }
+    (out, micro_video)
+}
+
+fn build_fresh_xmp(meta: &MotionMeta<'_>) -> Vec<u8> {
+    let mut writer = Writer::new(Vec::with_capacity(512));
+    write_fresh_xmp(&mut writer, meta).expect("writing to a Vec cannot fail");
+    writer.into_inner()
+}
+
+fn write_fresh_xmp(writer: &mut Writer<Vec<u8>>, meta: &MotionMeta<'_>) -> quick_xml::Result<()> {
+    let mut xmp = BytesStart::new("x:xmpmeta");
+    xmp.push_attribute(("xmlns:x", "adobe:ns:meta/"));
+    writer.write_event(Event::Start(xmp))?;
+
+    let mut rdf = BytesStart::new("rdf:RDF");
+    rdf.push_attribute(("xmlns:rdf", "http://www.w3.org/1999/02/22-rdf-syntax-ns#"));
+    writer.write_event(Event::Start(rdf))?;
+
+    write_motion_description(writer, meta, false)?;
+
+    writer.write_event(Event::End(BytesEnd::new("rdf:RDF")))?;
+    writer.write_event(Event::End(BytesEnd::new("x:xmpmeta")))?;
+    Ok(())
+}
+
+/// Write the Motion Photo description; `micro_video` adds the legacy `MicroVideo*` set.
+fn write_motion_description(
+    writer: &mut Writer<Vec<u8>>,
+    meta: &MotionMeta<'_>,
+    micro_video: bool,
+) -> quick_xml::Result<()> {
+    let ts_str = meta.presentation_timestamp_us.to_string();
+    let offset_str = meta.micro_video_offset.to_string();
+
+    let mut desc = BytesStart::new("rdf:Description");
+    desc.push_attribute(("xmlns:GCamera", "http://ns.google.com/photos/1.0/camera/"));
+    desc.push_attribute((
+        "xmlns:Container",
+        "http://ns.google.com/photos/1.0/container/",
+    ));
+    desc.push_attribute((
+        "xmlns:Item",
+        "http://ns.google.com/photos/1.0/container/item/",
+    ));
+    desc.push_attribute(("GCamera:MotionPhoto", "1"));
+    desc.push_attribute(("GCamera:MotionPhotoVersion", "1"));
+    desc.push_attribute((
+        "GCamera:MotionPhotoPresentationTimestampUs",
+        ts_str.as_str(),
+    ));
+    if micro_video {
+        desc.push_attribute(("GCamera:MicroVideo", "1"));
+        desc.push_attribute(("GCamera:MicroVideoVersion", "1"));
+        desc.push_attribute(("GCamera:MicroVideoOffset", offset_str.as_str()));
+        desc.push_attribute(("GCamera:MicroVideoPresentationTimestampUs", ts_str.as_str()));
+    }
+    writer.write_event(Event::Start(desc))?;
+    write_container_directory(writer, meta)?;
+    writer.write_event(Event::End(BytesEnd::new("rdf:Description")))?;
+    Ok(())
+}
+
+fn write_container_directory(
+    writer: &mut Writer<Vec<u8>>,
+    meta: &MotionMeta<'_>,
+) -> quick_xml::Result<()> {
+    writer.write_event(Event::Start(BytesStart::new("Container:Directory")))?;
+    writer.write_event(Event::Start(BytesStart::new("rdf:Seq")))?;
+
+    write_container_item(writer, "image/jpeg", "Primary", meta.primary_len, 0)?;
+    for &(semantic, len) in meta.mpf_images {
+        write_container_item(writer, "image/jpeg", semantic, len, 0)?;
+    }
+    for item in meta.items {
+        write_container_item(
+            writer,
+            &item.mime,
+            &item.semantic,
+            item.data.len(),
+            item.padding,
+        )?;
+    }
+
+    writer.write_event(Event::End(BytesEnd::new("rdf:Seq")))?;
+    writer.write_event(Event::End(BytesEnd::new("Container:Directory")))?;
+    Ok(())
+}
+
+fn write_container_item(
+    writer: &mut Writer<Vec<u8>>,
+    mime: &str,
+    semantic: &str,
+    len: usize,
+    padding: usize,
+) -> quick_xml::Result<()> {
+    let len_str = len.to_string();
+    let padding_str = padding.to_string();
+    let mut li = BytesStart::new("rdf:li");
+    li.push_attribute(("rdf:parseType", "Resource"));
+    writer.write_event(Event::Start(li))?;
+    let mut item = BytesStart::new("Container:Item");
+    item.push_attribute(("Item:Mime", mime));
+    item.push_attribute(("Item:Semantic", semantic));
+    item.push_attribute(("Item:Length", len_str.as_str()));
+    item.push_attribute(("Item:Padding", padding_str.as_str()));
+    writer.write_event(Event::Empty(item))?;
+    writer.write_event(Event::End(BytesEnd::new("rdf:li")))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(mime: &str, semantic: &str, len: usize, padding: usize) -> SecondaryItem {
+        SecondaryItem {
+            mime: mime.to_string(),
+            semantic: semantic.to_string(),
+            data: vec![0xAB; len],
+            padding,
+        }
+    }
+
+    fn mp4_box(kind: &[u8; 4], payload_len: usize) -> Vec<u8> {
+        let mut out = ((payload_len + 8) as u32).to_be_bytes().to_vec();
+        out.extend_from_slice(kind);
+        out.resize(payload_len + 8, 0);
+        out
+    }
+
+    fn mp4() -> Vec<u8> {
+        [
+            mp4_box(b"ftyp", 8),
+            mp4_box(b"moov", 16),
+            mp4_box(b"mdat", 32),
+        ]
+        .concat()
+    }
+
+    /// SOI, APP0, a DQT stand-in, then SOS + scan data + EOI.
+    fn tiny_jpeg() -> Vec<u8> {
+        let header = [
+            Segment {
+                marker: jpeg::APP0,
+                data: b"JFIF\0\x01\x01\0\0\x01\0\x01\0\0".to_vec(),
+            },
+            Segment {
+                marker: 0xDB,
+                data: vec![0; 65],
+            },
+        ];
+        jpeg::serialize(&header, &[0xFF, 0xDA, 0x00, 0x02, 0x11, 0x22, 0xFF, 0xD9]).unwrap()
+    }
+
+    fn xmp_of(stream: &[u8]) -> String {
+        let (segments, _) = jpeg::parse_header(stream).unwrap();
+        let xmp: Vec<_> = segments.iter().filter(|s| is_xmp_segment(s)).collect();
+        assert_eq!(xmp.len(), 1);
+        String::from_utf8(xmp[0].data[XMP_NAMESPACE.len()..].to_vec()).unwrap()
+    }
+
+    #[test]
+    fn container_directory_lists_every_secondary_in_order() {
+        let items = [
+            item("image/jpeg", "Depth", 10, 2),
+            item("video/mp4", "MotionPhoto", 30, 0),
+        ];
+        let meta = MotionMeta {
+            primary_len: 100,
+            mpf_images: &[("GainMap", 20)],
+            items: &items,
+            presentation_timestamp_us: 0,
+            micro_video_offset: 30,
+        };
+        let xmp = String::from_utf8(build_fresh_xmp(&meta)).unwrap();
+        let semantics: Vec<_> = ["Primary", "GainMap", "Depth", "MotionPhoto"]
+            .iter()
+            .map(|s| xmp.find(&format!("Item:Semantic=\"{s}\"")).unwrap())
+            .collect();
+        assert!(semantics.is_sorted(), "{xmp}");
+        assert!(
+            xmp.contains("Item:Length=\"10\" Item:Padding=\"2\""),
+            "{xmp}"
+        );
+        assert!(
+            xmp.contains("Item:Length=\"30\" Item:Padding=\"0\""),
+            "{xmp}"
+        );
+    }
+
+    #[test]
+    fn mp4_check_requires_complete_top_level_boxes() {
+        let complete = mp4();
+        check_mp4_structure(&complete).unwrap();
+
+        let truncated = &complete[..complete.len() - 1];
+        let err = check_mp4_structure(truncated).unwrap_err();
+        assert!(err.to_string().contains("mdat"), "{err}");
+
+        let fragmented = [
+            mp4_box(b"ftyp", 8),
+            mp4_box(b"moof", 16),
+            mp4_box(b"mdat", 32),
+        ]
+        .concat();
+        let err = check_mp4_structure(&fragmented).unwrap_err();
+        assert!(err.to_string().contains("moov"), "{err}");
+
+        let mut open_ended = [mp4_box(b"ftyp", 8), mp4_box(b"moov", 16)].concat();
+        open_ended.extend_from_slice(&[0, 0, 0, 0]);
+        open_ended.extend_from_slice(b"mdat");
+        open_ended.extend_from_slice(&[1, 2, 3]);
+        check_mp4_structure(&open_ended).unwrap();
+
+        assert!(check_mp4_structure(&mp4_box(b"moov", 0)).is_err());
+    }
+
+    #[test]
+    fn merge_keeps_unrelated_xmp_and_rewrites_motion_bits() {
+        let existing = br#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+  <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+    <rdf:Description rdf:about=""
+        xmlns:hdrgm="http://ns.adobe.com/hdr-gain-map/1.0/"
+        xmlns:GCamera="http://ns.google.com/photos/1.0/camera/"
+        xmlns:Container="http://ns.google.com/photos/1.0/container/"
+        xmlns:Item="http://ns.google.com/photos/1.0/container/item/"
+        xmlns:xmpMM="http://ns.adobe.com/xap/1.0/mm/"
+        hdrgm:Version="1.0"
+        GCamera:MicroVideo="1"
+        GCamera:MicroVideoOffset="999999"
+        GCamera:MotionPhoto="1"
+        GCamera:MotionPhotoPresentationTimestampUs="123"
+        xmpMM:OriginalDocumentID="ABC123">
+      <GCamera:MotionPhotoVersion>1</GCamera:MotionPhotoVersion>
+      <Container:Directory>
+        <rdf:Seq>
+          <rdf:li rdf:parseType="Resource">
+            <Container:Item Item:Mime="video/mp4" Item:Semantic="MotionPhoto" Item:Length="999"/>
+          </rdf:li>
+        </rdf:Seq>
+      </Container:Directory>
+      <hdrgm:Hints>
+        <rdf:Bag><rdf:li>keep-me</rdf:li></rdf:Bag>
+      </hdrgm:Hints>
+    </rdf:Description>
+  </rdf:RDF>
+</x:xmpmeta>"#;
+        let items = [item("video/mp4", "MotionPhoto", 30, 0)];
+        let meta = MotionMeta {
+            primary_len: 100,
+            mpf_images: &[],
+            items: &items,
+            presentation_timestamp_us: 456,
+            micro_video_offset: 30,
+        };
+        let merged =
+            String::from_utf8(merge_into_existing_xmp(existing, &meta).unwrap().unwrap()).unwrap();
+
+        for kept in [
+            "hdrgm:Version=\"1.0\"",
+            "xmpMM:OriginalDocumentID=\"ABC123\"",
+            "<rdf:li>keep-me</rdf:li>",
+        ] {
+            assert!(merged.contains(kept), "lost {kept}: {merged}");
+        }
+        assert_eq!(
+            merged.matches("GCamera:MotionPhoto=\"1\"").count(),
+            1,
+            "{merged}"
+        );
+        assert_eq!(
+            merged.matches("Container:Directory>").count(),
+            2,
+            "{merged}"
+        );
+        assert!(!merged.contains("<GCamera:MotionPhotoVersion>"), "{merged}");
+        assert!(
+            !merged.contains("\"999\"") && !merged.contains("\"123\""),
+            "{merged}"
+        );
+        assert!(merged.contains("GCamera:MotionPhotoPresentationTimestampUs=\"456\""));
+        assert_eq!(merged.matches("GCamera:MicroVideo=\"1\"").count(), 1);
+        assert!(
+            merged.contains("GCamera:MicroVideoOffset=\"30\""),
+            "{merged}"
+        );
+        assert!(!merged.contains("999999"), "{merged}");
+        assert!(merged.contains("Item:Length=\"30\""), "{merged}");
+    }
+
+    #[test]
+    fn concat_container_appends_items_with_padding() {
+        let items = [
+            item("image/jpeg", "Depth", 3, 2),
+            item("video/mp4", "MotionPhoto", 4, 0),
+        ];
+        let out = concat_container(b"JPG", &items);
+        assert_eq!(out, b"JPG\xAB\xAB\xAB\0\0\xAB\xAB\xAB\xAB");
+    }
+
+    #[test]
+    fn motion_photo_records_settled_primary_length() {
+        let video = mp4();
+        let opts = MotionOptions {
+            presentation_timestamp_us: 42,
+            items: vec![item("image/jpeg", "Depth", 5, 1)],
+            video_index: Some(0),
+        };
+        let photo = build_motion_photo(&tiny_jpeg(), &video, &opts).unwrap();
+        let out = &photo.data;
+
+        let primary_len = out.len() - video.len() - 5 - 1;
+        assert_eq!(photo.primary_len, primary_len);
+        assert_eq!(photo.gainmap_len, None);
+        let offsets: Vec<_> = photo.items.iter().map(|i| (i.offset, i.len)).collect();
+        assert_eq!(
+            offsets,
+            [(primary_len, video.len()), (primary_len + video.len(), 5)]
+        );
+        assert_eq!(&out[primary_len - 2..primary_len], &[0xFF, 0xD9]);
+        assert_eq!(&out[primary_len..primary_len + video.len()], &video[..]);
+        let xmp = xmp_of(out);
+        assert!(
+            xmp.contains(&format!(
+                "Item:Semantic=\"Primary\" Item:Length=\"{primary_len}\""
+            )),
+            "{xmp}"
+        );
+        assert!(
+            xmp.find("\"MotionPhoto\"").unwrap() < xmp.find("\"Depth\"").unwrap(),
+            "{xmp}"
+        );
+        assert!(xmp.contains("PresentationTimestampUs=\"42\""), "{xmp}");
+    }
+
+    /// `tiny_jpeg` carrying `secondary` as MPF image 1.
+    fn with_secondary(secondary: &[u8]) -> Vec<u8> {
+        let primary = tiny_jpeg();
+        let (segments, scan_at) = jpeg::parse_header(&primary).unwrap();
+        jpeg::with_secondary(&segments, &primary[scan_at..], secondary).unwrap()
+    }
+
+    #[test]
+    fn motion_photo_keeps_gainmap_reachable() {
+        let iso = Segment {
+            marker: jpeg::APP2,
+            data: metadata::iso_version_payload(),
+        };
+        let gainmap = jpeg::serialize(&[iso], &tiny_jpeg()[2..]).unwrap();
+        let stream = with_secondary(&gainmap);
+
+        let photo = build_motion_photo(&stream, &mp4(), &MotionOptions::default()).unwrap();
+        let out = &photo.data;
+        assert_eq!(jpeg::secondary_image(out, 1).unwrap(), &gainmap[..]);
+        assert_eq!(photo.gainmap_len, Some(gainmap.len()));
+        let xmp = xmp_of(out);
+        assert!(
+            xmp.contains(&format!(
+                "Item:Semantic=\"GainMap\" Item:Length=\"{}\"",
+                gainmap.len()
+            )),
+            "{xmp}"
+        );
+        let primary_len = jpeg::primary_image(out).unwrap().len();
+        assert_eq!(photo.primary_len, primary_len);
+        assert_eq!(photo.items[1].offset, primary_len + gainmap.len());
+        assert!(out[primary_len + gainmap.len()..].starts_with(&mp4()));
+    }
+
+    #[test]
+    fn mpf_preview_is_not_listed_as_gainmap() {
+        let preview = tiny_jpeg();
+        let photo =
+            build_motion_photo(&with_secondary(&preview), &mp4(), &MotionOptions::default())
+                .unwrap();
+        assert_eq!(photo.gainmap_len, None);
+        assert_eq!(photo.items[0].semantic, "Secondary");
+        assert_eq!(photo.items[0].len, preview.len());
+        let xmp = xmp_of(&photo.data);
+        assert!(!xmp.contains("GainMap"), "{xmp}");
+        assert!(
+            xmp.contains(&format!(
+                "Item:Semantic=\"Secondary\" Item:Length=\"{}\"",
+                preview.len()
+            )),
+            "{xmp}"
+        );
+    }
+
+    #[test]
+    fn motion_photo_rejects_bad_video_index_and_video() {
+        let opts = MotionOptions {
+            video_index: Some(1),
+            ..Default::default()
+        };
+        let err = build_motion_photo(&tiny_jpeg(), &mp4(), &opts).unwrap_err();
+        assert!(err.is_invalid_param());
+
+        let err =
+            build_motion_photo(&tiny_jpeg(), b"not an mp4", &MotionOptions::default()).unwrap_err();
+        assert!(err.to_string().contains("MP4"), "{err}");
+    }
+}
@@ -0,0 +1,169 @@
+//! Minimal reader/patcher for the CIPA DC-007 Multi-Picture Format (MPF) index that
+//! UltraHDR JPEGs carry in APP2 to locate the gain-map image.
+
+#[cfg(any(feature = "std", test))]
+use alloc::vec::Vec;
+
+/// Signature that prefixes the MPF APP2 payload.
+pub(crate) const MPF_SIGNATURE: &[u8] = b"MPF\0";
+/// The TIFF header (and every MPF offset) starts right after the signature.
+pub(crate) const TIFF_HEADER_OFFSET: usize = 4;
+
+const TAG_NUMBER_OF_IMAGES: u16 = 0xB001;
+const TAG_MP_ENTRY: u16 = 0xB002;
+const TYPE_UNDEFINED: u16 = 0x7;
+const MP_ENTRY_SIZE: usize = 16;
+
+/// One row of the MP entry table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct MpEntry {
+    pub attribute: u32,
+    pub size: u32,
+    /// Offset relative to the TIFF header; zero for the primary image.
+    pub offset: u32,
+}
+
+/// Location of the MP entry table inside an MPF APP2 payload.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct MpfIndex {
+    pub big_endian: bool,
+    /// Byte offset of the first MP entry within the payload.
+    entries_at: usize,
+    /// Number of images described by the table.
+    pub count: usize,
+}
+
+impl MpfIndex {
+    /// Parse the MPF payload (including the `MPF\0` signature).
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        if !payload.starts_with(MPF_SIGNATURE) {
+            return None;
+        }
+        let big_endian = match payload.get(4..8)? {
+            [0x4D, 0x4D, 0x00, 0x2A] => true,
+            [0x49, 0x49, 0x2A, 0x00] => false,
+            _ => return None,
+        };
+        let ifd_pos = TIFF_HEADER_OFFSET.checked_add(read_u32(payload, 8, big_endian)? as usize)?;
+        let tag_count = read_u16(payload, ifd_pos, big_endian)? as usize;
+
+        let mut count = None;
+        let mut entries_at = None;
+        for i in 0..tag_count {
+            let base = ifd_pos.checked_add(2 + i * 12)?;
+            let tag = read_u16(payload, base, big_endian)?;
+            let typ = read_u16(payload, base + 2, big_endian)?;
+            let len = read_u32(payload, base + 4, big_endian)? as usize;
+            let value = read_u32(payload, base + 8, big_endian)? as usize;
+            match tag {
+                TAG_NUMBER_OF_IMAGES => count = Some(value),
+                TAG_MP_ENTRY if typ == TYPE_UNDEFINED => {
+                    entries_at = Some(if len > 4 {
+                        TIFF_HEADER_OFFSET.checked_add(value)?
+                    } else {
+                        base + 8
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        let count = count?;
+        let entries_at = entries_at?;
+        let table_end = count
+            .checked_mul(MP_ENTRY_SIZE)
+            .and_then(|n| n.checked_add(entries_at))?;
+        if table_end > payload.len() {
+            return None;
+        }
+        Some(Self {
+            big_endian,
+            entries_at,
+            count,
+        })
+    }
+
+    /// Read the entry at `idx`.
+    pub fn entry(&self, payload: &[u8], idx: usize) -> Option<MpEntry> {
+        if idx >= self.count {
+            return None;
+        }
+        let at = self.entries_at + idx * MP_ENTRY_SIZE;
+        Some(MpEntry {
+            attribute: read_u32(payload, at, self.big_endian)?,
+            size: read_u32(payload, at + 4, self.big_endian)?,
+            offset: read_u32(payload, at + 8, self.big_endian)?,
+        })
+    }
+
+    /// Overwrite the size and offset of the entry at `idx`, keeping the source byte order.
+    pub fn set_entry(&self, payload: &mut [u8], idx: usize, size: u32, offset: u32) -> bool {
+        if idx >= self.count {
+            return false;
+        }
+        let at = self.entries_at + idx * MP_ENTRY_SIZE;
+        write_u32(payload, at + 4, size, self.big_endian)
+            && write_u32(payload, at + 8, offset, self.big_endian)
+    }
+}
+
+/// Big-endian MPF payload (signature included) describing a baseline primary image and
+/// one secondary image at `offset` from the TIFF header.
+#[cfg(any(feature = "std", test))]
+pub(crate) fn payload(primary_size: u32, secondary_size: u32, offset: u32) -> Vec<u8> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(MPF_SIGNATURE);
+    buf.extend_from_slice(&[0x4D, 0x4D, 0x00, 0x2A]);
+    buf.extend_from_slice(&8u32.to_be_bytes());
+    buf.extend_from_slice(&2u16.to_be_bytes());
+    buf.extend_from_slice(&TAG_NUMBER_OF_IMAGES.to_be_bytes());
+    buf.extend_from_slice(&4u16.to_be_bytes());
+    buf.extend_from_slice(&1u32.to_be_bytes());
+    buf.extend_from_slice(&2u32.to_be_bytes());
+    buf.extend_from_slice(&TAG_MP_ENTRY.to_be_bytes());
+    buf.extend_from_slice(&TYPE_UNDEFINED.to_be_bytes());
+    buf.extend_from_slice(&((2 * MP_ENTRY_SIZE) as u32).to_be_bytes());
+    // Entries follow the IFD (2 tags) and the next-IFD pointer.
+    buf.extend_from_slice(&(8 + 2 + 2 * 12 + 4u32).to_be_bytes());
+    buf.extend_from_slice(&0u32.to_be_bytes());
+    for (attr, size, off) in [
+        (0x0003_0000u32, primary_size, 0u32),
+        (0, secondary_size, offset),
+    ] {
+        buf.extend_from_slice(&attr.to_be_bytes());
+        buf.extend_from_slice(&size.to_be_bytes());
+        buf.extend_from_slice(&off.to_be_bytes());
+        buf.extend_from_slice(&[0, 0, 0, 0]);
+    }
+    buf
+}
+
+fn read_u16(buf: &[u8], at: usize, be: bool) -> Option<u16> {
+    let b: [u8; 2] = buf.get(at..at.checked_add(2)?)?.try_into().ok()?;
+    Some(if be {
+        u16::from_be_bytes(b)
+    } else {
+        u16::from_le_bytes(b)
+    })
+}
+
+fn read_u32(buf: &[u8], at: usize, be: bool) -> Option<u32> {
+    let b: [u8; 4] = buf.get(at..at.checked_add(4)?)?.try_into().ok()?;
+    Some(if be {
+        u32::from_be_bytes(b)
+    } else {
+        u32::from_le_bytes(b)
+    })
+}
+
+fn write_u32(buf: &mut [u8], at: usize, value: u32, be: bool) -> bool {
+    let Some(dst) = buf.get_mut(at..at + 4) else {
+        return false;
+    };
+    dst.copy_from_slice(&if be {
+        value.to_be_bytes()
+    } else {
+        value.to_le_bytes()
+    });
+    true
+}
@@ -0,0 +1,33 @@
+//! Glob-importable re-exports of the types most programs need.
+//!
+//! ```no_run
+//! use ultrahdr::prelude::*;
+//!
+//! fn decode(bytes: &mut [u8]) -> Result<DecodedPacked> {
+//!     let mut comp = CompressedImage::from_bytes(
+//!         bytes,
+//!         ColorGamut::UHDR_CG_UNSPECIFIED,
+//!         ColorTransfer::UHDR_CT_UNSPECIFIED,
+//!         ColorRange::UHDR_CR_UNSPECIFIED,
+//!     )?;
+//!     let mut dec = Decoder::new()?;
+//!     dec.set_image(&mut comp)?;
+//!     dec.decode_packed_view(
+//!         ImgFormat::UHDR_IMG_FMT_32bppRGBA1010102,
+//!         ColorTransfer::UHDR_CT_PQ,
+//!     )?
+//!     .to_owned()
+//! }
+//! ```
+//!
+//! The enum aliases ([`ImgFormat`], [`ColorGamut`], [`Codec`], ...) name the `sys` enums,
+//! so their values are reachable as `ColorGamut::UHDR_CG_DISPLAY_P3` without spelling out
+//! `sys::uhdr_color_gamut`.
+
+pub use crate::sys;
+pub use crate::{
+    Codec, ColorGamut, ColorLabel, ColorRange, ColorTransfer, CompressedImage, DecodedPacked,
+    DecodedPackedView, Decoder, EncPreset, EncodedImage, Encoder, EncoderBuilder, EncoderEffort,
+    Error, GainMapMetadata, ImgFormat, ImgLabel, MergeOptions, OutputIntent, OwnedCompressedImage,
+    RawImage, Result,
+};
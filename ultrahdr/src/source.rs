@@ -0,0 +1,125 @@
+//! Container-independent access to the parts of a gain-map image.
+//!
+//! libultrahdr reads and writes the JPEG/MPF container itself. [`GainMapSource`] separates
+//! the container from the codec: a demuxer supplies the base image, the gain-map image and
+//! the metadata, and [`Encoder::set_gainmap_source`](crate::Encoder::set_gainmap_source) or
+//! [`Decoder::set_source`](crate::Decoder::set_source) feed them to libultrahdr.
+
+use crate::decoder::Decoder;
+use crate::error::Result;
+use crate::jpeg;
+use crate::metadata;
+use crate::sys;
+use crate::types::{CompressedImage, EncodedImage, GainMapMetadata};
+
+/// Supplies the compressed base image, gain-map image and metadata of a gain-map photo.
+///
+/// libultrahdr only codes JPEG bitstreams, so both images must be JPEG-coded; what an
+/// implementation abstracts is the container. A HEIF demuxer living in another crate can
+/// implement this for files whose items are JPEG-coded, or transcode HEVC/AV1 items first.
+pub trait GainMapSource {
+    /// Compressed base image, without any container-level gain-map metadata.
+    fn base_image(&self) -> Result<EncodedImage>;
+
+    /// Compressed gain-map image, or `None` for an image without a gain map.
+    fn gainmap_image(&self) -> Result<Option<EncodedImage>>;
+
+    /// Gain-map metadata; must be `Some` whenever [`gainmap_image`](Self::gainmap_image) is.
+    fn gainmap_metadata(&self) -> Result<Option<GainMapMetadata>>;
+}
+
+/// [`GainMapSource`] over an UltraHDR JPEG, with the gain map located through MPF.
+///
+/// JPEG has no gamut field outside an embedded ICC profile, so both images are reported as
+/// BT.709 sRGB full range. The bytes, including any ICC profile, are carried verbatim, so
+/// this only affects libultrahdr's parameter checks.
+#[derive(Debug, Clone, Copy)]
+pub struct JpegSource<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> JpegSource<'a> {
+    /// Wrap an (UltraHDR) JPEG stream.
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn encoded(data: Vec<u8>) -> EncodedImage {
+        EncodedImage {
+            data,
+            cg: sys::uhdr_color_gamut::UHDR_CG_BT_709,
+            ct: sys::uhdr_color_transfer::UHDR_CT_SRGB,
+            range: sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+        }
+    }
+}
+
+impl GainMapSource for JpegSource<'_> {
+    /// The primary image with its MPF index, ISO 21496-1 block and `hdrgm` XMP removed.
+    fn base_image(&self) -> Result<EncodedImage> {
+        let primary = jpeg::primary_image(self.bytes)?;
+        let (mut segments, scan_at) = jpeg::parse_header(primary)?;
+        metadata::strip_gainmap_segments(&mut segments);
+        Ok(Self::encoded(jpeg::serialize(
+            &segments,
+            &primary[scan_at..],
+        )?))
+    }
+
+    fn gainmap_image(&self) -> Result<Option<EncodedImage>> {
+        Ok(jpeg::secondary_image(self.bytes, 1)
+            .ok()
+            .map(|image| Self::encoded(image.to_vec())))
+    }
+
+    fn gainmap_metadata(&self) -> Result<Option<GainMapMetadata>> {
+        let mut comp = CompressedImage::from_slice(
+            self.bytes,
+            sys::uhdr_color_gamut::UHDR_CG_UNSPECIFIED,
+            sys::uhdr_color_transfer::UHDR_CT_UNSPECIFIED,
+            sys::uhdr_color_range::UHDR_CR_UNSPECIFIED,
+        )?;
+        let mut dec = Decoder::new()?;
+        dec.set_image(&mut comp)?;
+        dec.gainmap_metadata()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jpeg::Segment;
+    use crate::jpeg::tests::ultrahdr_like;
+
+    #[test]
+    fn jpeg_source_splits_base_and_gainmap() {
+        let xmp = Segment {
+            marker: jpeg::APP1,
+            data: metadata::primary_xmp_payload(10),
+        };
+        let exif = Segment {
+            marker: jpeg::APP1,
+            data: b"Exif\0\0MM".to_vec(),
+        };
+        let (stream, secondary) = ultrahdr_like(&[exif.clone(), xmp]);
+        let source = JpegSource::new(&stream);
+
+        let gainmap = source.gainmap_image().unwrap().unwrap();
+        assert_eq!(gainmap.data, secondary);
+
+        let base = source.base_image().unwrap();
+        let (segments, _) = jpeg::parse_header(&base.data).unwrap();
+        let markers: Vec<u8> = segments.iter().map(|s| s.marker).collect();
+        assert_eq!(markers, vec![jpeg::APP1, 0xDB]);
+        assert_eq!(segments[0], exif);
+        assert!(base.data.ends_with(&[1, 2, 3, 4, 0xFF, jpeg::EOI]));
+    }
+
+    #[test]
+    fn plain_jpeg_has_no_gainmap() {
+        let plain = jpeg::tests::jpeg(&[], &[1, 2]);
+        let source = JpegSource::new(&plain);
+        assert!(source.gainmap_image().unwrap().is_none());
+        assert_eq!(source.base_image().unwrap().data, plain);
+    }
+}
@@ -0,0 +1,248 @@
+//! Luminance statistics of decoded images, for exposure and headroom checks on HDR output.
+
+use crate::error::{Error, Result};
+use crate::sys;
+use crate::types::{ColorGamut, ColorTransfer, DecodedPacked, SDR_WHITE_NITS};
+
+/// Peak luminance assumed for HLG's reference OOTF (ITU-R BT.2100).
+const HLG_PEAK_NITS: f32 = 1000.0;
+/// Absolute luminance of a PQ signal value of 1.0 (SMPTE ST 2084).
+const PQ_PEAK_NITS: f32 = 10000.0;
+
+/// Luminance summary of a decoded image, in nits.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LumStats {
+    /// Darkest pixel luminance.
+    pub min_nits: f32,
+    /// Brightest pixel luminance.
+    pub max_nits: f32,
+    /// Mean pixel luminance.
+    pub mean_nits: f32,
+    /// Fraction of pixels (0..=1) with at least one channel at the format's ceiling.
+    ///
+    /// For 8888/1010102 that is the maximum code value; half-float has no code ceiling, so
+    /// only channels that saturated to the largest finite half or infinity count.
+    pub clipped_fraction: f32,
+}
+
+impl DecodedPacked {
+    /// Compute min/max/mean luminance and the clipped-pixel fraction of the buffer.
+    ///
+    /// Luminance uses the Rec.709, Display P3 or Rec.2020 weights of the buffer's gamut
+    /// (Rec.709 when unspecified). Values are mapped to nits by transfer: sRGB and linear
+    /// output take 1.0 as SDR white ([`SDR_WHITE_NITS`]), PQ is absolute, and HLG applies the
+    /// BT.2100 OOTF for a 1000-nit display. Alpha is ignored.
+    pub fn luminance_stats(&self) -> Result<LumStats> {
+        let mut min = f32::INFINITY;
+        let mut max = f32::NEG_INFINITY;
+        let mut sum = 0f64;
+        let mut pixels = 0usize;
+        let mut clipped = 0usize;
+        for (nits, is_clipped) in self.pixel_nits()? {
+            min = min.min(nits);
+            max = max.max(nits);
+            sum += f64::from(nits);
+            pixels += 1;
+            clipped += usize::from(is_clipped);
+        }
+        Ok(LumStats {
+            min_nits: min,
+            max_nits: max,
+            mean_nits: (sum / pixels as f64) as f32,
+            clipped_fraction: clipped as f32 / pixels as f32,
+        })
+    }
+
+    /// Luminance in nits of every pixel, in raster order, plus whether it clipped; mapped
+    /// as in [`luminance_stats`](Self::luminance_stats). Fails for an empty image.
+    pub(crate) fn pixel_nits(&self) -> Result<impl Iterator<Item = (f32, bool)> + '_> {
+        let weights = luma_weights(self.cg);
+        let to_nits = nits_mapping(self.fmt, self.ct)?;
+        let pixels = (self.width as usize)
+            .checked_mul(self.height as usize)
+            .ok_or_else(|| Error::invalid_param("image size overflow"))?;
+        if pixels == 0 {
+            return Err(Error::invalid_param("image has no pixels"));
+        }
+        let bpp = crate::types::bytes_per_pixel(self.fmt)?;
+        let data = self
+            .data
+            .get(..pixels * bpp)
+            .ok_or_else(|| Error::invalid_param("pixel buffer too small"))?;
+        let fmt = self.fmt;
+        Ok(data.chunks_exact(bpp).map(move |px| {
+            let (rgb, is_clipped) = decode_pixel(fmt, px);
+            (to_nits(rgb, weights), is_clipped)
+        }))
+    }
+}
+
+fn luma_weights(cg: ColorGamut) -> [f32; 3] {
+    match cg {
+        sys::uhdr_color_gamut::UHDR_CG_BT_2100 => [0.2627, 0.6780, 0.0593],
+        sys::uhdr_color_gamut::UHDR_CG_DISPLAY_P3 => [0.2290, 0.6917, 0.0793],
+        _ => [0.2126, 0.7152, 0.0722],
+    }
+}
+
+type NitsFn = fn([f32; 3], [f32; 3]) -> f32;
+
+fn nits_mapping(fmt: sys::uhdr_img_fmt, ct: ColorTransfer) -> Result<NitsFn> {
+    use sys::uhdr_color_transfer::*;
+    use sys::uhdr_img_fmt::*;
+    Ok(match (fmt, ct) {
+        (UHDR_IMG_FMT_32bppRGBA8888, UHDR_CT_SRGB) => {
+            |rgb, w| SDR_WHITE_NITS * dot(rgb.map(srgb_to_linear), w)
+        }
+        (UHDR_IMG_FMT_32bppRGBA1010102, UHDR_CT_PQ) => {
+            |rgb, w| PQ_PEAK_NITS * dot(rgb.map(pq_to_linear), w)
+        }
+        (UHDR_IMG_FMT_32bppRGBA1010102, UHDR_CT_HLG) => |rgb, w| {
+            let scene = dot(rgb.map(hlg_to_linear), w);
+            HLG_PEAK_NITS * scene.powf(1.2)
+        },
+        (UHDR_IMG_FMT_64bppRGBAHalfFloat, UHDR_CT_LINEAR) => |rgb, w| SDR_WHITE_NITS * dot(rgb, w),
+        _ => {
+            return Err(Error::invalid_param(
+                "luminance stats need 8888/sRGB, 1010102/PQ or HLG, or half-float/linear",
+            ));
+        }
+    })
+}
+
+/// Normalized RGB of one pixel plus whether any color channel hit the format's ceiling.
+fn decode_pixel(fmt: sys::uhdr_img_fmt, px: &[u8]) -> ([f32; 3], bool) {
+    match fmt {
+        sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA8888 => {
+            let rgb = [px[0], px[1], px[2]];
+            (rgb.map(|c| f32::from(c) / 255.0), rgb.contains(&u8::MAX))
+        }
+        sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA1010102 => {
+            let v = u32::from_le_bytes([px[0], px[1], px[2], px[3]]);
+            let rgb = [v & 0x3FF, (v >> 10) & 0x3FF, (v >> 20) & 0x3FF];
+            (rgb.map(|c| c as f32 / 1023.0), rgb.contains(&0x3FF))
+        }
+        _ => {
+            let half = |i: usize| half::f16::from_le_bytes([px[2 * i], px[2 * i + 1]]).to_f32();
+            let rgb = [half(0), half(1), half(2)];
+            let clipped = rgb.iter().any(|c| c.is_infinite() || *c >= 65504.0);
+            (
+                rgb.map(|c| if c.is_nan() { 0.0 } else { c.max(0.0) }),
+                clipped,
+            )
+        }
+    }
+}
+
+fn dot(rgb: [f32; 3], w: [f32; 3]) -> f32 {
+    rgb[0] * w[0] + rgb[1] * w[1] + rgb[2] * w[2]
+}
+
+fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn pq_to_linear(v: f32) -> f32 {
+    const M1: f32 = 2610.0 / 16384.0;
+    const M2: f32 = 2523.0 / 4096.0 * 128.0;
+    const C1: f32 = 3424.0 / 4096.0;
+    const C2: f32 = 2413.0 / 4096.0 * 32.0;
+    const C3: f32 = 2392.0 / 4096.0 * 32.0;
+    let p = v.max(0.0).powf(1.0 / M2);
+    ((p - C1).max(0.0) / (C2 - C3 * p)).powf(1.0 / M1)
+}
+
+fn hlg_to_linear(v: f32) -> f32 {
+    const A: f32 = 0.178_832_77;
+    const B: f32 = 0.284_668_92;
+    const C: f32 = 0.559_910_7;
+    if v <= 0.5 {
+        v * v / 3.0
+    } else {
+        (((v - C) / A).exp() + B) / 12.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(fmt: sys::uhdr_img_fmt, ct: ColorTransfer, data: Vec<u8>) -> DecodedPacked {
+        DecodedPacked {
+            fmt,
+            cg: sys::uhdr_color_gamut::UHDR_CG_BT_709,
+            ct,
+            range: sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+            width: 2,
+            height: 1,
+            data,
+        }
+    }
+
+    #[test]
+    fn srgb_black_and_white_map_to_zero_and_sdr_white() {
+        let img = image(
+            sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA8888,
+            sys::uhdr_color_transfer::UHDR_CT_SRGB,
+            vec![0, 0, 0, 255, 255, 255, 255, 255],
+        );
+        let stats = img.luminance_stats().unwrap();
+        assert_eq!(stats.min_nits, 0.0);
+        assert!((stats.max_nits - SDR_WHITE_NITS).abs() < 0.01);
+        assert!((stats.mean_nits - SDR_WHITE_NITS / 2.0).abs() < 0.01);
+        assert_eq!(stats.clipped_fraction, 0.5);
+    }
+
+    #[test]
+    fn pq_full_code_is_ten_thousand_nits() {
+        let white = 0x3FFu32 | (0x3FF << 10) | (0x3FF << 20) | (0x3 << 30);
+        let mut data = white.to_le_bytes().to_vec();
+        data.extend_from_slice(&(0x3u32 << 30).to_le_bytes());
+        let img = image(
+            sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA1010102,
+            sys::uhdr_color_transfer::UHDR_CT_PQ,
+            data,
+        );
+        let stats = img.luminance_stats().unwrap();
+        assert!((stats.max_nits - PQ_PEAK_NITS).abs() < 1.0);
+        assert_eq!(stats.min_nits, 0.0);
+        assert_eq!(stats.clipped_fraction, 0.5);
+    }
+
+    #[test]
+    fn linear_half_float_scales_by_sdr_white() {
+        // 1.0 and 2.0 in binary16, alpha 1.0.
+        let px = |v: u16| {
+            [v, v, v, 0x3C00]
+                .iter()
+                .flat_map(|c| c.to_le_bytes())
+                .collect::<Vec<_>>()
+        };
+        let mut data = px(0x3C00);
+        data.extend(px(0x4000));
+        let img = image(
+            sys::uhdr_img_fmt::UHDR_IMG_FMT_64bppRGBAHalfFloat,
+            sys::uhdr_color_transfer::UHDR_CT_LINEAR,
+            data,
+        );
+        let stats = img.luminance_stats().unwrap();
+        assert!((stats.min_nits - SDR_WHITE_NITS).abs() < 0.01);
+        assert!((stats.max_nits - 2.0 * SDR_WHITE_NITS).abs() < 0.01);
+        assert_eq!(stats.clipped_fraction, 0.0);
+    }
+
+    #[test]
+    fn mismatched_transfer_is_rejected() {
+        let img = image(
+            sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA8888,
+            sys::uhdr_color_transfer::UHDR_CT_PQ,
+            vec![0; 8],
+        );
+        let err = img.luminance_stats().unwrap_err();
+        assert_eq!(err.code, sys::uhdr_codec_err_t::UHDR_CODEC_INVALID_PARAM);
+    }
+}
@@ -1,8 +1,10 @@
 use crate::error::{Error, Result};
 use crate::sys;
-use std::ffi::c_void;
-use std::marker::PhantomData;
-use std::ptr;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ffi::c_void;
+use core::marker::PhantomData;
+use core::ptr;
 
 /// Pixel layout for packed buffers accepted/returned by libultrahdr.
 pub type ImgFormat = sys::uhdr_img_fmt_t;
@@ -18,12 +20,72 @@ pub type Codec = sys::uhdr_codec_t;
 pub type ImgLabel = sys::uhdr_img_label_t;
 /// Encoder tuning preset.
 pub type EncPreset = sys::uhdr_enc_preset_t;
+/// Axis for the mirror effect.
+pub type MirrorDirection = sys::uhdr_mirror_direction_t;
 /// Error codes returned by the underlying C API.
 pub type ErrorCode = sys::uhdr_codec_err_t;
 
+/// Human-readable names for the color enums, for logs and user-facing output.
+///
+/// The enums are bindgen types from [`sys`], so they cannot implement `Display` here; this
+/// trait gives them a `label()` instead while `Debug` keeps the C names.
+///
+/// ```
+/// use ultrahdr::{ColorGamut, ColorLabel, ColorTransfer};
+///
+/// assert_eq!(ColorGamut::UHDR_CG_DISPLAY_P3.label(), "Display P3");
+/// assert_eq!(ColorTransfer::UHDR_CT_PQ.label(), "PQ");
+/// ```
+pub trait ColorLabel {
+    /// Short friendly name, e.g. "Display P3", "PQ" or "Full range".
+    fn label(&self) -> &'static str;
+}
+
+impl ColorLabel for ColorGamut {
+    fn label(&self) -> &'static str {
+        match self {
+            sys::uhdr_color_gamut::UHDR_CG_BT_709 => "BT.709 / sRGB",
+            sys::uhdr_color_gamut::UHDR_CG_DISPLAY_P3 => "Display P3",
+            sys::uhdr_color_gamut::UHDR_CG_BT_2100 => "BT.2100 / Rec.2020",
+            sys::uhdr_color_gamut::UHDR_CG_UNSPECIFIED => "unspecified",
+        }
+    }
+}
+
+impl ColorLabel for ColorTransfer {
+    fn label(&self) -> &'static str {
+        match self {
+            sys::uhdr_color_transfer::UHDR_CT_LINEAR => "Linear",
+            sys::uhdr_color_transfer::UHDR_CT_HLG => "HLG",
+            sys::uhdr_color_transfer::UHDR_CT_PQ => "PQ",
+            sys::uhdr_color_transfer::UHDR_CT_SRGB => "sRGB",
+            sys::uhdr_color_transfer::UHDR_CT_UNSPECIFIED => "unspecified",
+        }
+    }
+}
+
+impl ColorLabel for ColorRange {
+    fn label(&self) -> &'static str {
+        match self {
+            sys::uhdr_color_range::UHDR_CR_LIMITED_RANGE => "Limited range",
+            sys::uhdr_color_range::UHDR_CR_FULL_RANGE => "Full range",
+            sys::uhdr_color_range::UHDR_CR_UNSPECIFIED => "unspecified",
+        }
+    }
+}
+
 /// Nominal SDR diffuse white used by libultrahdr for capacity math (ISO/TS 22028-5).
 pub const SDR_WHITE_NITS: f32 = 203.0;
 
+/// Bits per sample of the encoded gain-map image.
+///
+/// libultrahdr always writes the gain map as an 8-bit JPEG, independent of the base image,
+/// and exposes no setting to change it (the JPEG gain-map formats only define 8-bit
+/// storage). Banding in smooth HDR gradients is instead controlled through
+/// [`Encoder::set_gainmap_gamma`](crate::Encoder::set_gainmap_gamma), the gain-map quality
+/// and [`Encoder::set_gainmap_scale_factor`](crate::Encoder::set_gainmap_scale_factor).
+pub const GAINMAP_BIT_DEPTH: u8 = 8;
+
 /// Owned compressed JPEG (and optional gain-map) returned by an [`Encoder`].
 #[derive(Debug, Clone)]
 pub struct EncodedImage {
@@ -44,6 +106,7 @@ pub struct EncodedView<'a> {
 }
 
 impl<'a> EncodedView<'a> {
+    #[cfg(any(feature = "std", test))]
     pub(crate) fn new(inner: &'a sys::uhdr_compressed_image) -> Self {
         Self { inner }
     }
@@ -57,8 +120,9 @@ impl<'a> EncodedView<'a> {
             return Err(Error::invalid_param("compressed size exceeds capacity"));
         }
         // SAFETY: bounded by data_sz verified above.
-        let slice =
-            unsafe { std::slice::from_raw_parts(self.inner.data as *const u8, self.inner.data_sz) };
+        let slice = unsafe {
+            core::slice::from_raw_parts(self.inner.data as *const u8, self.inner.data_sz)
+        };
         Ok(slice)
     }
 
@@ -81,7 +145,10 @@ impl<'a> EncodedView<'a> {
 }
 
 /// Owned packed pixels plus metadata returned by a [`Decoder`].
-#[derive(Debug, Clone)]
+///
+/// Equality and hashing compare the format, color metadata, dimensions and every pixel
+/// byte; the color enums are integer-backed, so `Eq`/`Hash` are exact.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct DecodedPacked {
     /// Output pixel layout.
     pub fmt: ImgFormat,
@@ -100,6 +167,150 @@ pub struct DecodedPacked {
 }
 
 impl DecodedPacked {
+    /// Split into `(format, width, height, pixels)` for handing to graphics APIs.
+    ///
+    /// Rows are tightly packed, `width * bytes_per_pixel(format)` bytes each.
+    pub fn into_parts(self) -> (ImgFormat, u32, u32, Vec<u8>) {
+        (self.fmt, self.width, self.height, self.data)
+    }
+
+    /// Decode row `y` of a half-float image to `f32`, four channels (RGBA) per pixel.
+    ///
+    /// Fails unless `fmt` is `UHDR_IMG_FMT_64bppRGBAHalfFloat`, or if `y` is out of range or
+    /// the buffer is shorter than the dimensions need.
+    pub fn row_f32(&self, y: usize) -> Result<Vec<f32>> {
+        check_half_float(self.fmt)?;
+        if y >= self.height as usize {
+            return Err(Error::invalid_param("row out of range"));
+        }
+        let row_bytes = self.width as usize * 8;
+        let row = y
+            .checked_mul(row_bytes)
+            .and_then(|start| self.data.get(start..start.checked_add(row_bytes)?))
+            .ok_or_else(|| Error::invalid_param("pixel buffer does not match dimensions"))?;
+        Ok(half_pixels(row).flatten().collect())
+    }
+
+    /// Iterate over the pixels of a half-float image as `[r, g, b, a]` in `f32`, row by row.
+    ///
+    /// Fails up front unless `fmt` is `UHDR_IMG_FMT_64bppRGBAHalfFloat` and the buffer
+    /// holds `width * height` pixels.
+    pub fn pixels_f32(&self) -> Result<impl Iterator<Item = [f32; 4]> + '_> {
+        check_half_float(self.fmt)?;
+        let len = (self.width as usize)
+            .checked_mul(self.height as usize)
+            .and_then(|px| px.checked_mul(8))
+            .filter(|&len| len <= self.data.len())
+            .ok_or_else(|| Error::invalid_param("pixel buffer does not match dimensions"))?;
+        Ok(half_pixels(&self.data[..len]))
+    }
+
+    /// Copy the pixels into an [`image::DynamicImage`].
+    ///
+    /// | source format        | `DynamicImage` variant | conversion                          |
+    /// |----------------------|------------------------|-------------------------------------|
+    /// | `8bppYCbCr400`       | `ImageLuma8`           | none                                |
+    /// | `32bppRGBA8888`      | `ImageRgba8`           | none                                |
+    /// | `32bppRGBA1010102`   | `ImageRgba16`          | 10-bit and 2-bit alpha codes scaled to 16 bits |
+    /// | `64bppRGBAHalfFloat` | `ImageRgba32F`         | widened to `f32`                    |
+    ///
+    /// No tone mapping is applied: the values keep the image's transfer function
+    /// ([`DecodedPacked::ct`]), so 10-bit output is still PQ/HLG coded and half-float output is
+    /// linear. Fails for other formats or if the buffer is shorter than the dimensions need.
+    #[cfg(feature = "image")]
+    pub fn to_dynamic_image(&self) -> Result<image::DynamicImage> {
+        use image::{DynamicImage, ImageBuffer};
+
+        let short = || Error::invalid_param("pixel buffer does not match dimensions");
+        let len = (self.width as usize)
+            .checked_mul(self.height as usize)
+            .and_then(|px| px.checked_mul(bytes_per_pixel(self.fmt).ok()?))
+            .ok_or_else(|| Error::invalid_param("unsupported format for DynamicImage"))?;
+        let data = self.data.get(..len).ok_or_else(short)?;
+        let (w, h) = (self.width, self.height);
+        let img = match self.fmt {
+            sys::uhdr_img_fmt::UHDR_IMG_FMT_8bppYCbCr400 => DynamicImage::ImageLuma8(
+                ImageBuffer::from_raw(w, h, data.to_vec()).ok_or_else(short)?,
+            ),
+            sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA8888 => DynamicImage::ImageRgba8(
+                ImageBuffer::from_raw(w, h, data.to_vec()).ok_or_else(short)?,
+            ),
+            sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA1010102 => {
+                let widen10 = |c: u32| ((c << 6) | (c >> 4)) as u16;
+                let pixels = data
+                    .chunks_exact(4)
+                    .flat_map(|px| {
+                        let v = u32::from_le_bytes([px[0], px[1], px[2], px[3]]);
+                        [
+                            widen10(v & 0x3FF),
+                            widen10((v >> 10) & 0x3FF),
+                            widen10((v >> 20) & 0x3FF),
+                            (v >> 30) as u16 * 0x5555,
+                        ]
+                    })
+                    .collect();
+                DynamicImage::ImageRgba16(ImageBuffer::from_raw(w, h, pixels).ok_or_else(short)?)
+            }
+            _ => {
+                let pixels = data
+                    .chunks_exact(2)
+                    .map(|c| half::f16::from_le_bytes([c[0], c[1]]).to_f32())
+                    .collect();
+                DynamicImage::ImageRgba32F(ImageBuffer::from_raw(w, h, pixels).ok_or_else(short)?)
+            }
+        };
+        Ok(img)
+    }
+
+    /// View RGBA8888 or RGBA1010102 pixels as one little-endian `u32` per pixel.
+    ///
+    /// Fails for other formats, or if the buffer is not 4-byte aligned (the global
+    /// allocator aligns `Vec<u8>` allocations to at least 8 bytes on supported platforms).
+    #[cfg(feature = "bytemuck")]
+    pub fn as_u32_slice(&self) -> Result<&[u32]> {
+        match self.fmt {
+            sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA8888
+            | sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA1010102 => {}
+            _ => return Err(Error::invalid_param("u32 view needs a 32bpp format")),
+        }
+        bytemuck::try_cast_slice(&self.data)
+            .map_err(|_| Error::invalid_param("pixel buffer not aligned for u32"))
+    }
+
+    /// View RGBA half-float pixels as four [`half::f16`] channels per pixel.
+    ///
+    /// Fails for other formats or if the buffer is not 2-byte aligned.
+    #[cfg(feature = "bytemuck")]
+    pub fn as_f16_slice(&self) -> Result<&[half::f16]> {
+        if self.fmt != sys::uhdr_img_fmt::UHDR_IMG_FMT_64bppRGBAHalfFloat {
+            return Err(Error::invalid_param("f16 view needs the half-float format"));
+        }
+        bytemuck::try_cast_slice(&self.data)
+            .map_err(|_| Error::invalid_param("pixel buffer not aligned for f16"))
+    }
+
+    /// Split a decoded gain map into one `8bppYCbCr400` image per channel.
+    ///
+    /// Single-channel maps are returned as-is; RGBA8888 maps yield R, G and B planes.
+    #[cfg(any(feature = "std", test))]
+    pub(crate) fn split_gainmap_channels(self) -> Result<Vec<DecodedPacked>> {
+        match self.fmt {
+            sys::uhdr_img_fmt::UHDR_IMG_FMT_8bppYCbCr400 => Ok(vec![self]),
+            sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA8888 => Ok((0..3)
+                .map(|c| DecodedPacked {
+                    fmt: sys::uhdr_img_fmt::UHDR_IMG_FMT_8bppYCbCr400,
+                    cg: self.cg,
+                    ct: self.ct,
+                    range: self.range,
+                    width: self.width,
+                    height: self.height,
+                    data: self.data.iter().skip(c).step_by(4).copied().collect(),
+                })
+                .collect()),
+            _ => Err(Error::invalid_param("unexpected gain map layout")),
+        }
+    }
+
     /// Re-expose the owned pixels as a [`RawImage`] descriptor.
     pub fn as_raw_image(&mut self) -> Result<RawImage<'_>> {
         RawImage::packed(
@@ -114,11 +325,18 @@ impl DecodedPacked {
     }
 }
 
+impl From<DecodedPacked> for (ImgFormat, u32, u32, Vec<u8>) {
+    fn from(img: DecodedPacked) -> Self {
+        img.into_parts()
+    }
+}
+
 /// Owns a packed raw buffer and exposes it as `uhdr_raw_image`.
 #[derive(Debug, Clone)]
 pub struct OwnedPackedImage {
     buf: Vec<u8>,
     raw: sys::uhdr_raw_image,
+    orientation: u8,
 }
 
 impl OwnedPackedImage {
@@ -131,12 +349,49 @@ impl OwnedPackedImage {
         ct: ColorTransfer,
         range: ColorRange,
     ) -> Result<Self> {
-        let bpp = bytes_per_pixel(fmt)?;
-        let len = (width as usize)
-            .checked_mul(height as usize)
-            .and_then(|v| v.checked_mul(bpp))
-            .ok_or_else(|| Error::invalid_param("buffer size overflow"))?;
-        let mut buf = vec![0u8; len];
+        let len = packed_buffer_len(fmt, width, height)?;
+        Self::from_vec(vec![0u8; len], fmt, width, height, cg, ct, range)
+    }
+
+    /// Adopt an existing pixel buffer without copying or re-zeroing it.
+    ///
+    /// `buf.len()` must be exactly `width * height * bytes_per_pixel(fmt)`; use
+    /// [`from_vec_with_stride`](Self::from_vec_with_stride) for padded rows.
+    pub fn from_vec(
+        buf: Vec<u8>,
+        fmt: ImgFormat,
+        width: u32,
+        height: u32,
+        cg: ColorGamut,
+        ct: ColorTransfer,
+        range: ColorRange,
+    ) -> Result<Self> {
+        Self::from_vec_with_stride(buf, fmt, width, height, width, cg, ct, range)
+    }
+
+    /// Adopt an existing buffer whose rows are `stride` pixels apart.
+    ///
+    /// `stride` must be at least `width` and `buf.len()` exactly
+    /// `stride * height * bytes_per_pixel(fmt)`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_vec_with_stride(
+        mut buf: Vec<u8>,
+        fmt: ImgFormat,
+        width: u32,
+        height: u32,
+        stride: u32,
+        cg: ColorGamut,
+        ct: ColorTransfer,
+        range: ColorRange,
+    ) -> Result<Self> {
+        if stride < width {
+            return Err(Error::invalid_param("stride smaller than width"));
+        }
+        if buf.len() != packed_buffer_len(fmt, stride, height)? {
+            return Err(Error::invalid_param(
+                "buffer length does not match stride * height * bytes_per_pixel",
+            ));
+        }
         let mut planes = [ptr::null_mut(); 3];
         planes[0] = buf.as_mut_ptr() as *mut c_void;
         Ok(Self {
@@ -149,22 +404,47 @@ impl OwnedPackedImage {
                 w: width,
                 h: height,
                 planes,
-                stride: [width, 0, 0],
+                stride: [stride, 0, 0],
             },
+            orientation: 1,
         })
     }
 
+    #[cfg(feature = "std")]
     pub(crate) fn as_raw_mut(&mut self) -> &mut sys::uhdr_raw_image {
         // keep plane pointer up to date (in case of moves).
         self.raw.planes[0] = self.buf.as_mut_ptr() as *mut c_void;
         &mut self.raw
     }
 
+    /// Tag the pixels with an EXIF orientation (1-8, 1 = upright).
+    ///
+    /// [`Encoder::set_raw_owned_image`](crate::Encoder::set_raw_owned_image) carries it
+    /// over and the encoded stream gets a matching EXIF Orientation tag, so viewers that
+    /// honor EXIF rotate on display and no pixels are moved. This is metadata only: if the
+    /// pixels are also rotated by an encoder effect, keep the orientation at 1 or the
+    /// rotation is applied twice.
+    pub fn set_orientation(&mut self, orientation: u8) -> Result<()> {
+        crate::exif::check_orientation(orientation)?;
+        self.orientation = orientation;
+        Ok(())
+    }
+
+    /// EXIF orientation attached to the pixels (1 unless set).
+    pub fn orientation(&self) -> u8 {
+        self.orientation
+    }
+
     /// Mutable access to the backing pixel buffer.
     pub fn buffer(&mut self) -> &mut [u8] {
         &mut self.buf
     }
 
+    /// Distance between rows in pixels.
+    pub fn stride(&self) -> u32 {
+        self.raw.stride[0]
+    }
+
     /// Logical width in pixels.
     pub fn width(&self) -> u32 {
         self.raw.w
@@ -193,6 +473,7 @@ pub struct DecodedPackedView<'a> {
 }
 
 impl<'a> DecodedPackedView<'a> {
+    #[cfg(any(feature = "std", test))]
     pub(crate) fn new(img: &'a mut sys::uhdr_raw_image) -> Result<Self> {
         let bpp = bytes_per_pixel(img.fmt)?;
         Ok(Self { img, bpp })
@@ -242,10 +523,57 @@ impl<'a> DecodedPackedView<'a> {
         // SAFETY: bounds checked above; plane is valid for lifetime 'a.
         let base = img.planes[plane_idx] as *const u8;
         let start = unsafe { base.add(y * stride_bytes) };
-        let slice = unsafe { std::slice::from_raw_parts(start, row_bytes) };
+        let slice = unsafe { core::slice::from_raw_parts(start, row_bytes) };
         Ok(slice)
     }
 
+    /// Iterate over the packed rows top to bottom, skipping stride padding.
+    ///
+    /// Yields exactly [`height`](Self::height) items, each validated like
+    /// [`row`](Self::row).
+    pub fn rows(&self) -> impl Iterator<Item = Result<&'a [u8]>> + '_ {
+        (0..self.img.h as usize).map(|y| self.row(y))
+    }
+
+    /// Borrow the whole image as one slice when its rows are tightly packed.
+    ///
+    /// Zero-copy fast path for e.g. GPU uploads: returns `width * height * bpp` bytes
+    /// without allocating. Fails with `UHDR_CODEC_INVALID_PARAM` if the stride differs from
+    /// the width; go through [`to_owned`](Self::to_owned) or [`rows`](Self::rows) then.
+    pub fn as_flat_slice(&self) -> Result<&'a [u8]> {
+        let img: &sys::uhdr_raw_image = &*self.img;
+        let plane_idx = sys::UHDR_PLANE_PACKED as usize;
+        if img.stride[plane_idx] != img.w {
+            return Err(Error::invalid_param(
+                "rows are padded (stride != width); copy with to_owned",
+            ));
+        }
+        let len = packed_len(img)?;
+        if img.planes[plane_idx].is_null() {
+            return Err(Error::invalid_param("null packed plane"));
+        }
+        // SAFETY: the decoder's plane holds `height` rows of `stride == width` pixels and is
+        // valid for lifetime 'a.
+        Ok(unsafe { core::slice::from_raw_parts(img.planes[plane_idx] as *const u8, len) })
+    }
+
+    /// Decode row `y` of a half-float view to `f32`, four channels (RGBA) per pixel.
+    ///
+    /// Fails unless the format is `UHDR_IMG_FMT_64bppRGBAHalfFloat`; bounds are checked as
+    /// in [`row`](Self::row).
+    pub fn row_f32(&self, y: usize) -> Result<Vec<f32>> {
+        check_half_float(self.img.fmt)?;
+        Ok(half_pixels(self.row(y)?).flatten().collect())
+    }
+
+    /// Iterate over the pixels of a half-float view as `[r, g, b, a]` in `f32`, row by row,
+    /// skipping stride padding.
+    pub fn pixels_f32(&self) -> Result<impl Iterator<Item = [f32; 4]> + 'a> {
+        check_half_float(self.img.fmt)?;
+        let rows = self.rows().collect::<Result<Vec<_>>>()?;
+        Ok(rows.into_iter().flat_map(half_pixels))
+    }
+
     /// Override the color gamut metadata attached to this view.
     pub fn set_color_gamut(&mut self, cg: ColorGamut) {
         self.img.cg = cg;
@@ -261,10 +589,47 @@ impl<'a> DecodedPackedView<'a> {
         self.img.range = range;
     }
 
+    #[cfg(feature = "std")]
     pub(crate) fn as_raw_mut(&mut self) -> &mut sys::uhdr_raw_image {
         self.img
     }
 
+    /// Bytes needed to hold a tightly packed copy of this view.
+    pub fn required_owned_len(&self) -> Result<usize> {
+        packed_len(self.img)
+    }
+
+    /// Copy the pixels into `out`, respecting stride.
+    ///
+    /// `out` is cleared and refilled without reallocating when its capacity is at least
+    /// [`required_owned_len`](Self::required_owned_len), so huge images need no extra copy.
+    pub fn to_owned_into(&self, out: &mut Vec<u8>) -> Result<()> {
+        copy_raw_packed_into(self.img, out)
+    }
+
+    /// Copy only the pixels inside `rect` into an owned buffer.
+    pub fn crop_to_owned(&self, rect: Rect) -> Result<DecodedPacked> {
+        if !rect.fits_within(self.img.w, self.img.h) {
+            return Err(Error::invalid_param("region outside image bounds"));
+        }
+        let start = rect.x as usize * self.bpp;
+        let end = start + rect.width as usize * self.bpp;
+        let mut data = Vec::with_capacity((end - start) * rect.height as usize);
+        for y in rect.y..rect.y + rect.height {
+            data.extend_from_slice(&self.row(y as usize)?[start..end]);
+        }
+        let (cg, ct, range) = self.meta();
+        Ok(DecodedPacked {
+            fmt: self.img.fmt,
+            cg,
+            ct,
+            range,
+            width: rect.width,
+            height: rect.height,
+            data,
+        })
+    }
+
     /// Copy the pixels into an owned buffer, respecting stride.
     pub fn to_owned(&self) -> Result<DecodedPacked> {
         let img: &sys::uhdr_raw_image = &*self.img;
@@ -283,7 +648,11 @@ impl<'a> DecodedPackedView<'a> {
 }
 
 /// Parsed metadata describing an embedded gain map.
+///
+/// With the `serde` feature this implements `Serialize`/`Deserialize`, using the field
+/// names below and three-element arrays for the per-channel values.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GainMapMetadata {
     /// Maximum per-channel gain applied by the gain map.
     pub max_content_boost: [f32; 3],
@@ -295,16 +664,26 @@ pub struct GainMapMetadata {
     pub offset_sdr: [f32; 3],
     /// Per-channel HDR offset.
     pub offset_hdr: [f32; 3],
-    /// Lower bound of the HDR capacity.
+    /// Lower bound of the HDR capacity, as a linear factor over SDR white.
     pub hdr_capacity_min: f32,
-    /// Upper bound of the HDR capacity.
+    /// Upper bound of the HDR capacity, as a linear factor over SDR white.
     pub hdr_capacity_max: f32,
     /// Whether to reuse the base image color gamut for the gain map.
     pub use_base_cg: bool,
+    /// Whether the base image is the HDR rendition (the gain map maps HDR down to SDR).
+    ///
+    /// The C metadata struct has no such field; [`Decoder`](crate::Decoder) fills it in from
+    /// the gain-map image's ISO flags or `hdrgm:BaseRenditionIsHDR` XMP property. Defaults to
+    /// `false` when deserializing data written without it.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub base_rendition_is_hdr: bool,
 }
 
 impl GainMapMetadata {
-    pub(crate) fn from_sys(meta: &sys::uhdr_gainmap_metadata) -> Self {
+    /// Convert from the C metadata struct, e.g. one obtained through [`sys`] directly.
+    ///
+    /// `base_rendition_is_hdr` is set to `false`, as the C struct has no such field.
+    pub fn from_sys(meta: &sys::uhdr_gainmap_metadata) -> Self {
         Self {
             max_content_boost: meta.max_content_boost,
             min_content_boost: meta.min_content_boost,
@@ -314,7 +693,98 @@ impl GainMapMetadata {
             hdr_capacity_min: meta.hdr_capacity_min,
             hdr_capacity_max: meta.hdr_capacity_max,
             use_base_cg: meta.use_base_cg != 0,
+            base_rendition_is_hdr: false,
+        }
+    }
+
+    /// Convert to the C metadata struct, the inverse of [`from_sys`](Self::from_sys).
+    ///
+    /// `use_base_cg` becomes `0`/`1`; `base_rendition_is_hdr` has no C counterpart and is
+    /// dropped.
+    pub fn to_sys(&self) -> sys::uhdr_gainmap_metadata {
+        sys::uhdr_gainmap_metadata {
+            max_content_boost: self.max_content_boost,
+            min_content_boost: self.min_content_boost,
+            gamma: self.gamma,
+            offset_sdr: self.offset_sdr,
+            offset_hdr: self.offset_hdr,
+            hdr_capacity_min: self.hdr_capacity_min,
+            hdr_capacity_max: self.hdr_capacity_max,
+            use_base_cg: self.use_base_cg as _,
+        }
+    }
+
+    /// Check the invariants libultrahdr enforces on caller-supplied metadata.
+    ///
+    /// Every value must be finite; per channel `min_content_boost` must be positive and at
+    /// most `max_content_boost`, `gamma` positive and the offsets non-negative; and
+    /// `1.0 <= hdr_capacity_min <= hdr_capacity_max`. Fails with `UHDR_CODEC_INVALID_PARAM`
+    /// naming the first violated rule. Every API taking metadata (e.g.
+    /// [`Encoder::set_gainmap_image`](crate::Encoder::set_gainmap_image)) runs this before
+    /// anything reaches the C library.
+    pub fn validate(&self) -> Result<()> {
+        let channels = [
+            &self.max_content_boost,
+            &self.min_content_boost,
+            &self.gamma,
+            &self.offset_sdr,
+            &self.offset_hdr,
+        ];
+        if channels
+            .iter()
+            .flat_map(|c| c.iter())
+            .any(|v| !v.is_finite())
+            || !self.hdr_capacity_min.is_finite()
+            || !self.hdr_capacity_max.is_finite()
+        {
+            return Err(Error::invalid_param("gain map metadata must be finite"));
+        }
+        for c in 0..3 {
+            if self.min_content_boost[c] <= 0.0 {
+                return Err(Error::invalid_param("min_content_boost must be positive"));
+            }
+            if self.max_content_boost[c] < self.min_content_boost[c] {
+                return Err(Error::invalid_param(
+                    "max_content_boost must be >= min_content_boost",
+                ));
+            }
+            if self.gamma[c] <= 0.0 {
+                return Err(Error::invalid_param("gamma must be positive"));
+            }
+            if self.offset_sdr[c] < 0.0 || self.offset_hdr[c] < 0.0 {
+                return Err(Error::invalid_param("offsets must be non-negative"));
+            }
+        }
+        if self.hdr_capacity_min < 1.0 {
+            return Err(Error::invalid_param("hdr_capacity_min must be >= 1.0"));
         }
+        if self.hdr_capacity_max < self.hdr_capacity_min {
+            return Err(Error::invalid_param(
+                "hdr_capacity_max must be >= hdr_capacity_min",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Display boost at which the gain map is applied in full, as a linear multiplier.
+    ///
+    /// Like libultrahdr's C struct, this type stores capacities and content boosts as
+    /// linear factors; the log2 values of the ISO 21496-1 and XMP encodings are converted
+    /// when parsing and writing. No `2^x` is needed here, so this is `hdr_capacity_max`.
+    pub fn max_boost_factor(&self) -> f32 {
+        self.hdr_capacity_max
+    }
+
+    /// Display boost below which none of the gain map is applied, as a linear multiplier.
+    ///
+    /// See [`max_boost_factor`](Self::max_boost_factor) for the representation.
+    pub fn min_boost_factor(&self) -> f32 {
+        self.hdr_capacity_min
+    }
+
+    /// Per-channel `(min, max)` content boosts, as linear multipliers.
+    pub fn content_boost_range(&self) -> ([f32; 3], [f32; 3]) {
+        (self.min_content_boost, self.max_content_boost)
     }
 
     /// Target display peak brightness in nits (capacity * SDR reference white).
@@ -323,7 +793,188 @@ impl GainMapMetadata {
     }
 }
 
-/// Borrowed descriptor over a caller-provided packed pixel buffer.
+impl From<&sys::uhdr_gainmap_metadata> for GainMapMetadata {
+    fn from(meta: &sys::uhdr_gainmap_metadata) -> Self {
+        Self::from_sys(meta)
+    }
+}
+
+impl From<&GainMapMetadata> for sys::uhdr_gainmap_metadata {
+    fn from(meta: &GainMapMetadata) -> Self {
+        meta.to_sys()
+    }
+}
+
+/// One-shot summary of an embedded gain map returned by
+/// [`Decoder::gainmap_info`](crate::Decoder::gainmap_info).
+#[derive(Debug, Clone)]
+pub struct GainMapInfo {
+    /// Parsed gain-map metadata.
+    pub metadata: GainMapMetadata,
+    /// Gain-map width in pixels.
+    pub width: u32,
+    /// Gain-map height in pixels.
+    pub height: u32,
+    /// Length in bytes of the gain-map JPEG as libultrahdr extracted it (the `data_sz` of
+    /// its gain-map block, the same bytes [`Decoder::gainmap_image`] returns). This is the
+    /// encoded size, not `width * height`, and is not read from the MPF entry.
+    ///
+    /// [`Decoder::gainmap_image`]: crate::Decoder::gainmap_image
+    pub byte_len: usize,
+}
+
+/// Header-level description of an image returned by
+/// [`Decoder::probe_info`](crate::Decoder::probe_info).
+#[derive(Debug, Clone)]
+pub struct ProbeInfo {
+    /// Base image width in pixels.
+    pub width: u32,
+    /// Base image height in pixels.
+    pub height: u32,
+    /// Pixel layout [`Decoder::decode_native`](crate::Decoder::decode_native) will produce:
+    /// linear half-float with a gain map, RGBA8888 without one or with
+    /// [`OutputIntent::SdrBase`].
+    pub fmt: ImgFormat,
+    /// Color gamut supplied with the compressed image.
+    ///
+    /// libultrahdr does not report the base color space from a probe; an unspecified gamut
+    /// is only resolved from the ICC profile while decoding.
+    pub cg: ColorGamut,
+    /// Transfer function supplied with the compressed image.
+    pub ct: ColorTransfer,
+    /// Chroma range supplied with the compressed image.
+    pub range: ColorRange,
+    /// Gain-map metadata, dimensions and size, if the image carries one.
+    pub gainmap: Option<GainMapInfo>,
+}
+
+impl ProbeInfo {
+    /// Whether the image carries a gain map.
+    pub fn has_gainmap(&self) -> bool {
+        self.gainmap.is_some()
+    }
+
+    /// Integer downscale factor of the gain map relative to the base image, i.e. the
+    /// [`Encoder::set_gainmap_scale_factor`](crate::Encoder::set_gainmap_scale_factor) it
+    /// was most likely encoded with.
+    ///
+    /// libultrahdr sizes the map as `ceil(base / factor)` on both axes; `None` is returned
+    /// without a gain map or when its dimensions match no single factor that way.
+    pub fn gainmap_scale_factor(&self) -> Option<u32> {
+        let gm = self.gainmap.as_ref()?;
+        if gm.width == 0 || gm.height == 0 {
+            return None;
+        }
+        let factor = (self.width as f64 / gm.width as f64).round().max(1.0) as u32;
+        (self.width.div_ceil(factor) == gm.width && self.height.div_ceil(factor) == gm.height)
+            .then_some(factor)
+    }
+}
+
+/// Which rendition [`Decoder`](crate::Decoder) produces, see
+/// [`Decoder::set_output_intent`](crate::Decoder::set_output_intent).
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum OutputIntent {
+    /// Reconstruct the HDR rendition by applying the gain map (for images that have one)
+    /// in the configured output format and transfer.
+    #[default]
+    Hdr,
+    /// Return the SDR base image as stored, 8-bit sRGB RGBA8888, without applying the
+    /// gain map.
+    SdrBase,
+}
+
+/// Encoder speed/quality trade-off, ordered from fastest to slowest.
+///
+/// A stable alternative to the [`EncPreset`] constants for effort sliders: compare or
+/// [`clamp`](Ord::clamp) values, or map a numeric level with
+/// [`from_level`](Self::from_level). Converts to an [`EncPreset`] wherever one is accepted.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EncoderEffort {
+    /// `UHDR_USAGE_REALTIME`: fastest encode.
+    Realtime,
+    /// Middle of the scale. libultrahdr has only two presets, so this currently selects
+    /// `UHDR_USAGE_BEST_QUALITY`, libultrahdr's own default.
+    #[default]
+    Balanced,
+    /// `UHDR_USAGE_BEST_QUALITY`: highest quality, slowest encode.
+    BestQuality,
+}
+
+impl EncoderEffort {
+    /// Effort for `level` on a `0..=2` scale (0 = [`Realtime`](Self::Realtime)); `None`
+    /// above 2.
+    pub fn from_level(level: u8) -> Option<Self> {
+        match level {
+            0 => Some(Self::Realtime),
+            1 => Some(Self::Balanced),
+            2 => Some(Self::BestQuality),
+            _ => None,
+        }
+    }
+
+    /// Position on the `0..=2` scale, the inverse of [`from_level`](Self::from_level).
+    pub fn level(self) -> u8 {
+        self as u8
+    }
+}
+
+impl From<EncoderEffort> for EncPreset {
+    fn from(effort: EncoderEffort) -> Self {
+        match effort {
+            EncoderEffort::Realtime => sys::uhdr_enc_preset::UHDR_USAGE_REALTIME,
+            EncoderEffort::Balanced | EncoderEffort::BestQuality => {
+                sys::uhdr_enc_preset::UHDR_USAGE_BEST_QUALITY
+            }
+        }
+    }
+}
+
+impl From<EncPreset> for EncoderEffort {
+    fn from(preset: EncPreset) -> Self {
+        match preset {
+            sys::uhdr_enc_preset::UHDR_USAGE_REALTIME => Self::Realtime,
+            sys::uhdr_enc_preset::UHDR_USAGE_BEST_QUALITY => Self::BestQuality,
+        }
+    }
+}
+
+/// Chroma subsampling of a JPEG coded on the Rust side.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum ChromaSubsampling {
+    /// Full-resolution chroma.
+    #[default]
+    Yuv444,
+    /// Chroma halved horizontally.
+    Yuv422,
+    /// Chroma halved horizontally and vertically.
+    Yuv420,
+}
+
+/// Pixel-aligned rectangle, used to select a region of a decoded image.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Rect {
+    /// Left edge in pixels.
+    pub x: u32,
+    /// Top edge in pixels.
+    pub y: u32,
+    /// Width in pixels.
+    pub width: u32,
+    /// Height in pixels.
+    pub height: u32,
+}
+
+impl Rect {
+    /// Whether the rectangle is non-empty and lies entirely within a `width`×`height` image.
+    pub fn fits_within(&self, width: u32, height: u32) -> bool {
+        self.width > 0
+            && self.height > 0
+            && self.x.checked_add(self.width).is_some_and(|r| r <= width)
+            && self.y.checked_add(self.height).is_some_and(|b| b <= height)
+    }
+}
+
+/// Borrowed descriptor over caller-provided packed or planar pixel buffers.
 pub struct RawImage<'a> {
     pub(crate) inner: sys::uhdr_raw_image,
     _marker: PhantomData<&'a mut [u8]>,
@@ -340,6 +991,9 @@ impl<'a> RawImage<'a> {
         ct: ColorTransfer,
         range: ColorRange,
     ) -> Result<Self> {
+        if width == 0 || height == 0 {
+            return Err(Error::invalid_param("image dimensions must be non-zero"));
+        }
         let bytes_per_pixel = bytes_per_pixel(fmt)?;
         let expected = width as usize * height as usize * bytes_per_pixel;
         if data.len() < expected {
@@ -364,6 +1018,75 @@ impl<'a> RawImage<'a> {
         })
     }
 
+    /// Create a planar descriptor, e.g. over the YUV buffers a hardware decoder hands out.
+    ///
+    /// Accepts the multi-plane formats of [`plane_layout`], such as
+    /// `UHDR_IMG_FMT_24bppYCbCrP010` (planes Y and interleaved CbCr, 16-bit little-endian
+    /// samples with the value in the top 10 bits) and `UHDR_IMG_FMT_12bppYCbCr420` (planes
+    /// Y, Cb and Cr, 8-bit samples); the encoder takes those two as raw input. `N` must
+    /// match the format's plane count. As in libultrahdr, `strides` count samples, not
+    /// bytes ([`Plane::row_samples`]): P010's interleaved chroma row holds `width` samples,
+    /// a 4:2:0 chroma row `width / 2`. Dimensions must be multiples of the chroma
+    /// subsampling, each stride at least its row's sample count, and each plane long enough
+    /// for its rows (the last row may omit stride padding).
+    #[allow(clippy::too_many_arguments)]
+    pub fn planar<const N: usize>(
+        fmt: ImgFormat,
+        width: u32,
+        height: u32,
+        planes: [&'a mut [u8]; N],
+        strides: [u32; N],
+        cg: ColorGamut,
+        ct: ColorTransfer,
+        range: ColorRange,
+    ) -> Result<Self> {
+        if width == 0 || height == 0 {
+            return Err(Error::invalid_param("image dimensions must be non-zero"));
+        }
+        let layout = plane_layout(fmt)?;
+        if layout.is_packed() {
+            return Err(Error::invalid_param(
+                "format is not planar; use RawImage::packed",
+            ));
+        }
+        if layout.planes().len() != N {
+            return Err(Error::invalid_param("plane count does not match format"));
+        }
+        let mut ptrs = [ptr::null_mut(); 3];
+        let mut stride = [0; 3];
+        for (i, (plane, spec)) in planes.into_iter().zip(layout.planes()).enumerate() {
+            if !width.is_multiple_of(spec.subsample_x) || !height.is_multiple_of(spec.subsample_y) {
+                return Err(Error::invalid_param(
+                    "dimensions must be multiples of the chroma subsampling",
+                ));
+            }
+            let samples = spec.row_samples(width);
+            let rows = spec.rows(height);
+            if (strides[i] as usize) < samples {
+                return Err(Error::invalid_param("plane stride shorter than its row"));
+            }
+            let needed = (strides[i] as usize * (rows - 1) + samples) * spec.bytes_per_sample;
+            if plane.len() < needed {
+                return Err(Error::invalid_param("plane buffer too small for its rows"));
+            }
+            ptrs[i] = plane.as_mut_ptr() as *mut c_void;
+            stride[i] = strides[i];
+        }
+        Ok(Self {
+            inner: sys::uhdr_raw_image {
+                fmt,
+                cg,
+                ct,
+                range,
+                w: width,
+                h: height,
+                planes: ptrs,
+                stride,
+            },
+            _marker: PhantomData,
+        })
+    }
+
     /// Create a packed RGBA8888 descriptor over the provided pixel buffer.
     pub fn rgba8888(
         width: u32,
@@ -384,6 +1107,31 @@ impl<'a> RawImage<'a> {
         )
     }
 
+    /// Create a packed RGBA8888 descriptor borrowing an [`image::RgbaImage`]'s pixels.
+    ///
+    /// Width and height come from the image. Fails if its buffer is not exactly
+    /// `width * height * 4` bytes (an `ImageBuffer` may be built over a longer one).
+    #[cfg(feature = "image")]
+    pub fn from_rgba_image(
+        img: &'a mut image::RgbaImage,
+        cg: ColorGamut,
+        ct: ColorTransfer,
+        range: ColorRange,
+    ) -> Result<Self> {
+        let (width, height) = img.dimensions();
+        let expected = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|px| px.checked_mul(4));
+        let data: &'a mut [u8] = img;
+        if expected != Some(data.len()) {
+            return Err(Error::invalid_param(
+                "RgbaImage buffer length does not match width * height * 4",
+            ));
+        }
+        Self::rgba8888(width, height, data, cg, ct, range)
+    }
+
+    #[cfg(feature = "std")]
     pub(crate) fn as_mut_ptr(&mut self) -> *mut sys::uhdr_raw_image {
         &mut self.inner
     }
@@ -414,49 +1162,203 @@ impl<'a> RawImage<'a> {
 /// Borrowed descriptor over a caller-provided compressed JPEG buffer.
 pub struct CompressedImage<'a> {
     pub(crate) inner: sys::uhdr_compressed_image,
+    /// Whether the buffer came from [`from_slice`](Self::from_slice) and must not be written.
+    pub(crate) read_only: bool,
     _marker: PhantomData<&'a mut [u8]>,
 }
 
 impl<'a> CompressedImage<'a> {
-    /// Wrap a mutable buffer containing JPEG bytes.
+    /// Wrap a mutable buffer containing JPEG bytes. Fails on an empty buffer.
     pub fn from_bytes(
         data: &'a mut [u8],
         cg: ColorGamut,
         ct: ColorTransfer,
         range: ColorRange,
-    ) -> Self {
-        Self {
+    ) -> Result<Self> {
+        Self::wrap(
+            data.as_mut_ptr() as *mut c_void,
+            data.len(),
+            false,
+            cg,
+            ct,
+            range,
+        )
+    }
+
+    /// Wrap a read-only buffer (e.g. `Bytes` or a memory map) containing JPEG bytes.
+    ///
+    /// Only [`Decoder::set_image`](crate::Decoder::set_image) accepts such images: the
+    /// decoder reads `data`/`data_sz` and never writes through them. The encoder entry
+    /// points reject them; use [`from_bytes`](Self::from_bytes) there. Fails on an empty
+    /// buffer.
+    pub fn from_slice(
+        data: &'a [u8],
+        cg: ColorGamut,
+        ct: ColorTransfer,
+        range: ColorRange,
+    ) -> Result<Self> {
+        Self::wrap(
+            data.as_ptr() as *mut c_void,
+            data.len(),
+            true,
+            cg,
+            ct,
+            range,
+        )
+    }
+
+    fn wrap(
+        data: *mut c_void,
+        len: usize,
+        read_only: bool,
+        cg: ColorGamut,
+        ct: ColorTransfer,
+        range: ColorRange,
+    ) -> Result<Self> {
+        if len == 0 {
+            return Err(Error::invalid_param("empty input"));
+        }
+        Ok(Self {
             inner: sys::uhdr_compressed_image {
-                data: data.as_mut_ptr() as *mut c_void,
-                data_sz: data.len(),
-                capacity: data.len(),
+                data,
+                data_sz: len,
+                capacity: len,
                 cg,
                 ct,
                 range,
             },
+            read_only,
             _marker: PhantomData,
-        }
+        })
     }
 
+    #[cfg(feature = "std")]
     pub(crate) fn as_mut_ptr(&mut self) -> *mut sys::uhdr_compressed_image {
         &mut self.inner
     }
-}
 
-/// Copy a packed raw image plane into an owned Vec<u8>, honoring stride.
-pub(crate) fn copy_raw_packed(img: &sys::uhdr_raw_image) -> Result<Vec<u8>> {
-    let bytes_per_pixel = bytes_per_pixel(img.fmt)?;
-    let plane_idx = sys::UHDR_PLANE_PACKED as usize;
-    let data_ptr = img.planes[plane_idx];
-    if data_ptr.is_null() {
-        return Err(Error::invalid_param("null packed plane"));
+    /// The wrapped bytes.
+    #[cfg(any(feature = "std", test))]
+    pub(crate) fn as_slice(&self) -> &[u8] {
+        // SAFETY: `wrap` only accepts a non-empty buffer borrowed for 'a.
+        unsafe { core::slice::from_raw_parts(self.inner.data as *const u8, self.inner.data_sz) }
     }
-    let stride_px = img.stride[plane_idx] as usize;
-    if stride_px == 0 {
-        return Err(Error::invalid_param("zero stride"));
+}
+
+/// Owns a compressed JPEG buffer and exposes it as `uhdr_compressed_image`, the owned
+/// counterpart of [`CompressedImage`].
+#[derive(Debug, Clone)]
+pub struct OwnedCompressedImage {
+    buf: Vec<u8>,
+    raw: sys::uhdr_compressed_image,
+}
+
+impl OwnedCompressedImage {
+    /// Take ownership of JPEG bytes. Fails on an empty buffer.
+    pub fn new(buf: Vec<u8>, cg: ColorGamut, ct: ColorTransfer, range: ColorRange) -> Result<Self> {
+        if buf.is_empty() {
+            return Err(Error::invalid_param("empty input"));
+        }
+        let mut img = Self {
+            buf,
+            raw: sys::uhdr_compressed_image {
+                data: ptr::null_mut(),
+                data_sz: 0,
+                capacity: 0,
+                cg,
+                ct,
+                range,
+            },
+        };
+        img.as_raw_mut();
+        Ok(img)
+    }
+
+    /// The owned bytes.
+    pub fn bytes(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Color metadata.
+    pub fn meta(&self) -> (ColorGamut, ColorTransfer, ColorRange) {
+        (self.raw.cg, self.raw.ct, self.raw.range)
+    }
+
+    /// Give back the bytes.
+    pub fn into_vec(self) -> Vec<u8> {
+        self.buf
+    }
+
+    pub(crate) fn as_raw_mut(&mut self) -> &mut sys::uhdr_compressed_image {
+        // keep the data pointer up to date (in case of moves and clones).
+        self.raw.data = self.buf.as_mut_ptr() as *mut c_void;
+        self.raw.data_sz = self.buf.len();
+        self.raw.capacity = self.buf.len();
+        &mut self.raw
+    }
+}
+
+// SAFETY: `raw.data` only caches a pointer into `buf`, re-derived by `as_raw_mut` before
+// every use and never dereferenced through `&self`; the value is as thread-safe as the
+// `Vec<u8>` it owns.
+unsafe impl Send for OwnedCompressedImage {}
+unsafe impl Sync for OwnedCompressedImage {}
+
+impl TryFrom<EncodedImage> for OwnedCompressedImage {
+    type Error = Error;
+
+    fn try_from(img: EncodedImage) -> Result<Self> {
+        Self::new(img.data, img.cg, img.ct, img.range)
+    }
+}
+
+/// Compressed inputs accepted by [`Decoder::set_image`](crate::Decoder::set_image) and
+/// [`Encoder::set_compressed_image`](crate::Encoder::set_compressed_image): a borrowed
+/// [`CompressedImage`] or an [`OwnedCompressedImage`].
+pub trait AsCompressedImage {
+    /// Borrow the bytes as a [`CompressedImage`] descriptor.
+    fn as_compressed_image(&mut self) -> CompressedImage<'_>;
+}
+
+impl AsCompressedImage for CompressedImage<'_> {
+    fn as_compressed_image(&mut self) -> CompressedImage<'_> {
+        CompressedImage {
+            inner: self.inner,
+            read_only: self.read_only,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl AsCompressedImage for OwnedCompressedImage {
+    fn as_compressed_image(&mut self) -> CompressedImage<'_> {
+        CompressedImage {
+            inner: *self.as_raw_mut(),
+            read_only: false,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Copy a packed raw image plane into an owned Vec<u8>, honoring stride.
+pub(crate) fn copy_raw_packed(img: &sys::uhdr_raw_image) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    copy_raw_packed_into(img, &mut out)?;
+    Ok(out)
+}
+
+/// Validated layout of a packed plane: (row bytes, stride bytes, height).
+fn packed_layout(img: &sys::uhdr_raw_image) -> Result<(usize, usize, usize)> {
+    let bytes_per_pixel = bytes_per_pixel(img.fmt)?;
+    let plane_idx = sys::UHDR_PLANE_PACKED as usize;
+    if img.planes[plane_idx].is_null() {
+        return Err(Error::invalid_param("null packed plane"));
+    }
+    let stride_px = img.stride[plane_idx] as usize;
+    if stride_px == 0 {
+        return Err(Error::invalid_param("zero stride"));
     }
     let width = img.w as usize;
-    let height = img.h as usize;
     if stride_px < width {
         return Err(Error::invalid_param("stride smaller than width"));
     }
@@ -467,18 +1369,37 @@ pub(crate) fn copy_raw_packed(img: &sys::uhdr_raw_image) -> Result<Vec<u8>> {
     let row_bytes = width
         .checked_mul(bytes_per_pixel)
         .ok_or_else(|| Error::invalid_param("row overflow"))?;
+    Ok((row_bytes, stride_bytes, img.h as usize))
+}
+
+/// Number of bytes a tightly packed copy of `img` occupies.
+pub(crate) fn packed_len(img: &sys::uhdr_raw_image) -> Result<usize> {
+    let (row_bytes, _, height) = packed_layout(img)?;
+    row_bytes
+        .checked_mul(height)
+        .ok_or_else(|| Error::invalid_param("buffer size overflow"))
+}
 
-    let mut out = vec![0u8; row_bytes * height];
-    let mut src = data_ptr as *const u8;
-    let mut dst = 0;
+/// Copy a packed raw image into `out`, replacing its contents but reusing its allocation.
+pub(crate) fn copy_raw_packed_into(img: &sys::uhdr_raw_image, out: &mut Vec<u8>) -> Result<()> {
+    let (row_bytes, stride_bytes, height) = packed_layout(img)?;
+    let len = packed_len(img)?;
+    out.clear();
+    out.reserve_exact(len);
+    let mut src = img.planes[sys::UHDR_PLANE_PACKED as usize] as *const u8;
+    if stride_bytes == row_bytes {
+        // No row padding: the plane is one contiguous run.
+        // SAFETY: bounds are validated above; src points into buffer provided by decoder.
+        out.extend_from_slice(unsafe { core::slice::from_raw_parts(src, len) });
+        return Ok(());
+    }
     for _ in 0..height {
         // SAFETY: bounds are validated above; src points into buffer provided by decoder.
-        let row = unsafe { std::slice::from_raw_parts(src, stride_bytes) };
-        out[dst..dst + row_bytes].copy_from_slice(&row[..row_bytes]);
-        dst += row_bytes;
+        let row = unsafe { core::slice::from_raw_parts(src, row_bytes) };
+        out.extend_from_slice(row);
         src = unsafe { src.add(stride_bytes) };
     }
-    Ok(out)
+    Ok(())
 }
 
 /// Copy a compressed image buffer into an owned Vec<u8>.
@@ -491,12 +1412,49 @@ pub(crate) fn copy_compressed_image(img: &sys::uhdr_compressed_image) -> Result<
         return Err(Error::invalid_param("compressed size exceeds capacity"));
     }
     // SAFETY: data/data_sz provided by encoder/decoder.
-    let slice = unsafe { std::slice::from_raw_parts(img.data as *const u8, size) };
+    let slice = unsafe { core::slice::from_raw_parts(img.data as *const u8, size) };
     Ok(slice.to_vec())
 }
 
+/// Pack normalized channels into one `UHDR_IMG_FMT_32bppRGBA1010102` pixel.
+///
+/// libultrahdr reads each pixel as a little-endian `u32` with red in bits 0-9, green in
+/// 10-19, blue in 20-29 and alpha in 30-31; write the result with `to_le_bytes`. Channels
+/// are clamped to `[0, 1]` (NaN becomes 0) and rounded to the nearest code: `r * 1023` for
+/// color, `a * 3` for alpha. Color values are code values in the image's transfer function
+/// (e.g. PQ), not linear light.
+///
+/// ```
+/// use ultrahdr::{pack_rgba1010102, unpack_rgba1010102};
+///
+/// let px = pack_rgba1010102(1.0, 0.5, 0.0, 1.0);
+/// assert_eq!(px & 0x3FF, 1023);
+/// assert_eq!(unpack_rgba1010102(px)[3], 1.0);
+/// ```
+pub fn pack_rgba1010102(r: f32, g: f32, b: f32, a: f32) -> u32 {
+    let quantize =
+        |v: f32, max: f32| (if v.is_nan() { 0.0 } else { v.clamp(0.0, 1.0) } * max).round() as u32;
+    quantize(r, 1023.0)
+        | (quantize(g, 1023.0) << 10)
+        | (quantize(b, 1023.0) << 20)
+        | (quantize(a, 3.0) << 30)
+}
+
+/// Split a `UHDR_IMG_FMT_32bppRGBA1010102` pixel into normalized `[r, g, b, a]`, the
+/// inverse of [`pack_rgba1010102`].
+pub fn unpack_rgba1010102(px: u32) -> [f32; 4] {
+    [
+        (px & 0x3FF) as f32 / 1023.0,
+        ((px >> 10) & 0x3FF) as f32 / 1023.0,
+        ((px >> 20) & 0x3FF) as f32 / 1023.0,
+        (px >> 30) as f32 / 3.0,
+    ]
+}
+
 /// Bytes-per-pixel helper for the supported packed formats.
 ///
+/// See [`plane_layout`] for the per-plane geometry of every format.
+///
 /// ```
 /// use ultrahdr::{bytes_per_pixel, ImgFormat};
 ///
@@ -507,17 +1465,215 @@ pub(crate) fn copy_compressed_image(img: &sys::uhdr_compressed_image) -> Result<
 /// ```
 pub fn bytes_per_pixel(fmt: ImgFormat) -> Result<usize> {
     match fmt {
+        sys::uhdr_img_fmt::UHDR_IMG_FMT_8bppYCbCr400 => Ok(1),
         sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA8888 => Ok(4),
         sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA1010102 => Ok(4),
         sys::uhdr_img_fmt::UHDR_IMG_FMT_64bppRGBAHalfFloat => Ok(8),
-        _ => Err(Error::invalid_param("unsupported packed format for helper")),
+        _ => Err(Error::invalid_param(
+            "not a packed format; use plane_layout for planar formats",
+        )),
     }
 }
 
+/// Bytes of `width`×`height` packed pixels, rejecting empty images and overflow.
+fn packed_buffer_len(fmt: ImgFormat, width: u32, height: u32) -> Result<usize> {
+    if width == 0 || height == 0 {
+        return Err(Error::invalid_param("image dimensions must be non-zero"));
+    }
+    let bpp = bytes_per_pixel(fmt)?;
+    (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|v| v.checked_mul(bpp))
+        .ok_or_else(|| Error::invalid_param("buffer size overflow"))
+}
+
+/// Geometry of one plane of an [`ImgFormat`], see [`plane_layout`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Plane {
+    /// Bytes per stored sample (a packed 10-bit RGBA word counts as one 4-byte sample).
+    pub bytes_per_sample: usize,
+    /// Horizontal subsampling factor relative to the full-resolution grid.
+    pub subsample_x: u32,
+    /// Vertical subsampling factor relative to the full-resolution grid.
+    pub subsample_y: u32,
+    /// Samples stored per plane pixel (4 for packed RGBA, 2 for interleaved CbCr).
+    pub samples_per_pixel: usize,
+}
+
+impl Plane {
+    const fn new(
+        bytes_per_sample: usize,
+        subsample_x: u32,
+        subsample_y: u32,
+        samples: usize,
+    ) -> Self {
+        Self {
+            bytes_per_sample,
+            subsample_x,
+            subsample_y,
+            samples_per_pixel: samples,
+        }
+    }
+
+    /// Samples in one unpadded row of a `width`-pixel image; libultrahdr strides use the
+    /// same unit for planar formats.
+    pub fn row_samples(&self, width: u32) -> usize {
+        width.div_ceil(self.subsample_x) as usize * self.samples_per_pixel
+    }
+
+    /// Bytes in one unpadded row of a `width`-pixel image.
+    pub fn row_bytes(&self, width: u32) -> usize {
+        self.row_samples(width) * self.bytes_per_sample
+    }
+
+    /// Rows in this plane for a `height`-pixel image.
+    pub fn rows(&self, height: u32) -> usize {
+        height.div_ceil(self.subsample_y) as usize
+    }
+}
+
+/// Plane structure of an [`ImgFormat`]: one [`Plane`] for packed layouts, two or three
+/// for planar YCbCr.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct PlaneLayout {
+    planes: &'static [Plane],
+}
+
+impl PlaneLayout {
+    /// The planes in the order libultrahdr expects them in `uhdr_raw_image::planes`.
+    pub fn planes(&self) -> &'static [Plane] {
+        self.planes
+    }
+
+    /// Whether all samples live in a single interleaved plane.
+    pub fn is_packed(&self) -> bool {
+        self.planes.len() == 1
+    }
+
+    /// Bytes of a tightly packed `width` x `height` frame over all planes.
+    pub fn frame_bytes(&self, width: u32, height: u32) -> usize {
+        self.planes
+            .iter()
+            .map(|p| p.row_bytes(width) * p.rows(height))
+            .sum()
+    }
+}
+
+/// Describe the planes of any concrete libultrahdr pixel format.
+///
+/// Unlike [`bytes_per_pixel`], which only covers the packed formats, this handles the
+/// planar YCbCr layouts too; only `UHDR_IMG_FMT_UNSPECIFIED` is rejected.
+///
+/// ```
+/// use ultrahdr::{plane_layout, ImgFormat};
+///
+/// let p010 = plane_layout(ImgFormat::UHDR_IMG_FMT_24bppYCbCrP010).unwrap();
+/// assert_eq!(p010.planes().len(), 2);
+/// assert_eq!(p010.frame_bytes(4, 2), 4 * 2 * 2 + 4 * 2);
+/// ```
+pub fn plane_layout(fmt: ImgFormat) -> Result<PlaneLayout> {
+    use sys::uhdr_img_fmt::*;
+    const fn full(bytes: usize, samples: usize) -> Plane {
+        Plane::new(bytes, 1, 1, samples)
+    }
+    const fn yuv(x: u32, y: u32) -> [Plane; 3] {
+        [full(1, 1), Plane::new(1, x, y, 1), Plane::new(1, x, y, 1)]
+    }
+    const P010: [Plane; 2] = [full(2, 1), Plane::new(2, 2, 2, 2)];
+    const YUV420: [Plane; 3] = yuv(2, 2);
+    const YUV444: [Plane; 3] = yuv(1, 1);
+    const YUV422: [Plane; 3] = yuv(2, 1);
+    const YUV440: [Plane; 3] = yuv(1, 2);
+    const YUV411: [Plane; 3] = yuv(4, 1);
+    const YUV410: [Plane; 3] = yuv(4, 2);
+    const YUV444_10: [Plane; 3] = [full(2, 1); 3];
+    const Y400: [Plane; 1] = [full(1, 1)];
+    const RGBA8888: [Plane; 1] = [full(1, 4)];
+    const RGBA_HALF: [Plane; 1] = [full(2, 4)];
+    const RGBA1010102: [Plane; 1] = [full(4, 1)];
+    const RGB888: [Plane; 1] = [full(1, 3)];
+    let planes: &'static [Plane] = match fmt {
+        UHDR_IMG_FMT_24bppYCbCrP010 => &P010,
+        UHDR_IMG_FMT_12bppYCbCr420 => &YUV420,
+        UHDR_IMG_FMT_8bppYCbCr400 => &Y400,
+        UHDR_IMG_FMT_32bppRGBA8888 => &RGBA8888,
+        UHDR_IMG_FMT_64bppRGBAHalfFloat => &RGBA_HALF,
+        UHDR_IMG_FMT_32bppRGBA1010102 => &RGBA1010102,
+        UHDR_IMG_FMT_24bppYCbCr444 => &YUV444,
+        UHDR_IMG_FMT_16bppYCbCr422 => &YUV422,
+        UHDR_IMG_FMT_16bppYCbCr440 => &YUV440,
+        UHDR_IMG_FMT_12bppYCbCr411 => &YUV411,
+        UHDR_IMG_FMT_10bppYCbCr410 => &YUV410,
+        UHDR_IMG_FMT_24bppRGB888 => &RGB888,
+        UHDR_IMG_FMT_30bppYCbCr444 => &YUV444_10,
+        UHDR_IMG_FMT_UNSPECIFIED => {
+            return Err(Error::invalid_param("unspecified pixel format"));
+        }
+    };
+    Ok(PlaneLayout { planes })
+}
+
+fn check_half_float(fmt: ImgFormat) -> Result<()> {
+    if fmt != sys::uhdr_img_fmt::UHDR_IMG_FMT_64bppRGBAHalfFloat {
+        return Err(Error::invalid_param(
+            "f32 access needs the half-float format",
+        ));
+    }
+    Ok(())
+}
+
+/// Decode packed RGBA half-float pixels; `bytes` must hold whole pixels.
+fn half_pixels(bytes: &[u8]) -> impl Iterator<Item = [f32; 4]> + '_ {
+    bytes.chunks_exact(8).map(|px| {
+        core::array::from_fn(|c| half::f16::from_le_bytes([px[2 * c], px[2 * c + 1]]).to_f32())
+    })
+}
+
+/// Raw pixel layouts accepted by [`Encoder::set_raw_image`](crate::Encoder::set_raw_image).
+///
+/// P010 and RGBA1010102/half-float carry the HDR intent; 4:2:0 and RGBA8888 carry the SDR
+/// intent. Build P010 and 4:2:0 inputs with [`RawImage::planar`]; only the packed formats
+/// are covered by [`bytes_per_pixel`] and [`RawImage::packed`].
+pub fn supported_input_formats() -> &'static [ImgFormat] {
+    &[
+        sys::uhdr_img_fmt::UHDR_IMG_FMT_24bppYCbCrP010,
+        sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA1010102,
+        sys::uhdr_img_fmt::UHDR_IMG_FMT_64bppRGBAHalfFloat,
+        sys::uhdr_img_fmt::UHDR_IMG_FMT_12bppYCbCr420,
+        sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA8888,
+    ]
+}
+
+/// Pixel layouts [`Decoder::set_out_img_format`](crate::Decoder::set_out_img_format) can produce.
+///
+/// RGBA8888 pairs with sRGB, RGBA1010102 with HLG/PQ, and half-float with linear output.
+pub fn supported_output_formats() -> &'static [ImgFormat] {
+    &[
+        sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA8888,
+        sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA1010102,
+        sys::uhdr_img_fmt::UHDR_IMG_FMT_64bppRGBAHalfFloat,
+    ]
+}
+
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
 
+    /// Luma-uniform metadata with libultrahdr's default offsets; override fields with `..`.
+    pub(crate) fn sample_metadata(max_boost: f32) -> GainMapMetadata {
+        GainMapMetadata {
+            max_content_boost: [max_boost; 3],
+            min_content_boost: [1.0; 3],
+            gamma: [1.0; 3],
+            offset_sdr: [1.0 / 64.0; 3],
+            offset_hdr: [1.0 / 64.0; 3],
+            hdr_capacity_min: 1.0,
+            hdr_capacity_max: max_boost,
+            use_base_cg: true,
+            base_rendition_is_hdr: false,
+        }
+    }
+
     #[test]
     fn raw_image_rgba_checks_buffer_size() {
         let mut buf = vec![0u8; 3];
@@ -533,6 +1689,207 @@ mod tests {
         assert_eq!(err.code, sys::uhdr_codec_err_t::UHDR_CODEC_INVALID_PARAM);
     }
 
+    #[test]
+    fn owned_compressed_image_tracks_its_buffer() {
+        let meta = (
+            sys::uhdr_color_gamut::UHDR_CG_BT_709,
+            sys::uhdr_color_transfer::UHDR_CT_SRGB,
+            sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+        );
+        let owned = OwnedCompressedImage::new(vec![0xFF, 0xD8, 0xFF, 0xD9], meta.0, meta.1, meta.2)
+            .unwrap();
+        let copy = owned.clone();
+        drop(owned);
+        let mut copy = std::thread::spawn(move || copy).join().unwrap();
+        let view = copy.as_compressed_image();
+        assert!(!view.read_only);
+        assert_eq!(view.as_slice(), [0xFF, 0xD8, 0xFF, 0xD9]);
+        assert_eq!(view.inner.cg, meta.0);
+
+        let empty = EncodedImage {
+            data: Vec::new(),
+            cg: meta.0,
+            ct: meta.1,
+            range: meta.2,
+        };
+        assert!(OwnedCompressedImage::try_from(empty).is_err());
+    }
+
+    #[test]
+    fn owned_packed_image_adopts_vec() {
+        let fmt = sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA8888;
+        let meta = (
+            sys::uhdr_color_gamut::UHDR_CG_BT_709,
+            sys::uhdr_color_transfer::UHDR_CT_SRGB,
+            sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+        );
+        let buf = vec![9u8; 3 * 2 * 4];
+        let ptr = buf.as_ptr();
+        let mut img = OwnedPackedImage::from_vec(buf, fmt, 3, 2, meta.0, meta.1, meta.2).unwrap();
+        assert_eq!(img.stride(), 3);
+        assert_eq!(img.buffer().as_ptr(), ptr);
+        assert!(img.buffer().iter().all(|&b| b == 9));
+
+        let padded = vec![0u8; 4 * 2 * 4];
+        let img =
+            OwnedPackedImage::from_vec_with_stride(padded, fmt, 3, 2, 4, meta.0, meta.1, meta.2)
+                .unwrap();
+        assert_eq!((img.width(), img.stride()), (3, 4));
+
+        let short = vec![0u8; 3 * 2 * 4 - 1];
+        assert!(OwnedPackedImage::from_vec(short, fmt, 3, 2, meta.0, meta.1, meta.2).is_err());
+        let narrow = vec![0u8; 2 * 2 * 4];
+        assert!(
+            OwnedPackedImage::from_vec_with_stride(narrow, fmt, 3, 2, 2, meta.0, meta.1, meta.2)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn plane_layout_covers_every_format() {
+        use sys::uhdr_img_fmt::*;
+        for (fmt, bits) in [
+            (UHDR_IMG_FMT_24bppYCbCrP010, 24),
+            (UHDR_IMG_FMT_12bppYCbCr420, 12),
+            (UHDR_IMG_FMT_8bppYCbCr400, 8),
+            (UHDR_IMG_FMT_32bppRGBA8888, 32),
+            (UHDR_IMG_FMT_64bppRGBAHalfFloat, 64),
+            (UHDR_IMG_FMT_32bppRGBA1010102, 32),
+            (UHDR_IMG_FMT_24bppYCbCr444, 24),
+            (UHDR_IMG_FMT_16bppYCbCr422, 16),
+            (UHDR_IMG_FMT_16bppYCbCr440, 16),
+            (UHDR_IMG_FMT_12bppYCbCr411, 12),
+            (UHDR_IMG_FMT_10bppYCbCr410, 10),
+            (UHDR_IMG_FMT_24bppRGB888, 24),
+        ] {
+            let layout = plane_layout(fmt).unwrap();
+            assert_eq!(layout.frame_bytes(8, 8) * 8, 64 * bits, "{fmt:?}");
+            if let Ok(bpp) = bytes_per_pixel(fmt) {
+                assert!(layout.is_packed());
+                assert_eq!(layout.planes()[0].row_bytes(1), bpp, "{fmt:?}");
+            }
+        }
+        // 10-bit 4:4:4 stores each sample in 16 bits.
+        let yuv444_10 = plane_layout(UHDR_IMG_FMT_30bppYCbCr444).unwrap();
+        assert_eq!(yuv444_10.frame_bytes(8, 8), 64 * 6);
+        assert!(plane_layout(UHDR_IMG_FMT_UNSPECIFIED).is_err());
+    }
+
+    #[test]
+    fn planar_validates_each_plane() {
+        let meta = (
+            sys::uhdr_color_gamut::UHDR_CG_BT_2100,
+            sys::uhdr_color_transfer::UHDR_CT_HLG,
+            sys::uhdr_color_range::UHDR_CR_LIMITED_RANGE,
+        );
+        let p010 = sys::uhdr_img_fmt::UHDR_IMG_FMT_24bppYCbCrP010;
+        let (mut y, mut uv) = (vec![0u8; 4 * 2 * 2], vec![0u8; 4 * 2]);
+        let img = RawImage::planar(
+            p010,
+            4,
+            2,
+            [&mut y, &mut uv],
+            [4, 4],
+            meta.0,
+            meta.1,
+            meta.2,
+        )
+        .unwrap();
+        assert_eq!(img.inner.stride, [4, 4, 0]);
+        assert!(!img.inner.planes[1].is_null());
+
+        let mut short_uv = vec![0u8; 7];
+        let err = RawImage::planar(
+            p010,
+            4,
+            2,
+            [&mut y, &mut short_uv],
+            [4, 4],
+            meta.0,
+            meta.1,
+            meta.2,
+        )
+        .err()
+        .unwrap();
+        assert!(err.to_string().contains("too small"), "{err}");
+
+        let yuv420 = sys::uhdr_img_fmt::UHDR_IMG_FMT_12bppYCbCr420;
+        let (mut y, mut u, mut v) = (vec![0u8; 8 * 4], vec![0u8; 4 * 2], vec![0u8; 4 * 2]);
+        RawImage::planar(
+            yuv420,
+            8,
+            4,
+            [&mut y, &mut u, &mut v],
+            [8, 4, 4],
+            meta.0,
+            meta.1,
+            meta.2,
+        )
+        .unwrap();
+        let err = RawImage::planar(
+            yuv420,
+            8,
+            4,
+            [&mut y, &mut u, &mut v],
+            [8, 3, 4],
+            meta.0,
+            meta.1,
+            meta.2,
+        )
+        .err()
+        .unwrap();
+        assert!(err.to_string().contains("stride"), "{err}");
+        let err = RawImage::planar(
+            yuv420,
+            8,
+            4,
+            [&mut y, &mut u],
+            [8, 4],
+            meta.0,
+            meta.1,
+            meta.2,
+        )
+        .err()
+        .unwrap();
+        assert!(err.to_string().contains("plane count"), "{err}");
+    }
+
+    #[test]
+    fn empty_and_zero_sized_inputs_are_rejected() {
+        let mut empty: [u8; 0] = [];
+        let err = CompressedImage::from_slice(
+            &empty,
+            sys::uhdr_color_gamut::UHDR_CG_UNSPECIFIED,
+            sys::uhdr_color_transfer::UHDR_CT_UNSPECIFIED,
+            sys::uhdr_color_range::UHDR_CR_UNSPECIFIED,
+        )
+        .err()
+        .expect("empty slice accepted");
+        assert_eq!(err.detail.as_deref(), Some("empty input"));
+        assert!(
+            CompressedImage::from_bytes(
+                &mut empty,
+                sys::uhdr_color_gamut::UHDR_CG_UNSPECIFIED,
+                sys::uhdr_color_transfer::UHDR_CT_UNSPECIFIED,
+                sys::uhdr_color_range::UHDR_CR_UNSPECIFIED,
+            )
+            .is_err()
+        );
+
+        let mut buf = vec![0u8; 16];
+        let err = RawImage::rgba8888(
+            0,
+            4,
+            &mut buf,
+            sys::uhdr_color_gamut::UHDR_CG_BT_709,
+            sys::uhdr_color_transfer::UHDR_CT_SRGB,
+            sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+        )
+        .err()
+        .expect("zero width accepted");
+        assert_eq!(err.code, sys::uhdr_codec_err_t::UHDR_CODEC_INVALID_PARAM);
+    }
+
     #[test]
     fn encoded_view_validates_backing_buffer() {
         // Null data pointer should be rejected.
@@ -607,6 +1964,8 @@ mod tests {
         let view = DecodedPackedView::new(&mut raw).unwrap();
         let err = view.row(0).unwrap_err();
         assert_eq!(err.code, sys::uhdr_codec_err_t::UHDR_CODEC_INVALID_PARAM);
+        assert_eq!(view.rows().count(), height as usize);
+        assert!(view.rows().all(|row| row.is_err()));
     }
 
     #[test]
@@ -643,6 +2002,613 @@ mod tests {
         assert_eq!(owned.data.len(), width as usize * height as usize * bpp);
         assert_eq!(&owned.data[..8], &[1, 2, 3, 4, 5, 6, 7, 8]);
         assert_eq!(&owned.data[8..], &[9, 10, 11, 12, 13, 14, 15, 16]);
+        let rows = view.rows().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(rows.concat(), owned.data);
+        let err = view.as_flat_slice().unwrap_err();
+        assert!(err.to_string().contains("stride"), "{err}");
+
+        raw.stride[0] = width;
+        let view = DecodedPackedView::new(&mut raw).unwrap();
+        let flat = view.as_flat_slice().unwrap();
+        assert_eq!(flat.len(), width as usize * height as usize * bpp);
+        assert_eq!(flat.as_ptr(), buf.as_ptr());
+    }
+
+    #[test]
+    fn limited_range_propagates_through_packed_descriptors() {
+        let limited = sys::uhdr_color_range::UHDR_CR_LIMITED_RANGE;
+        let mut buf = vec![0u8; 4 * 4];
+        let raw = RawImage::rgba8888(
+            2,
+            2,
+            &mut buf,
+            sys::uhdr_color_gamut::UHDR_CG_BT_709,
+            sys::uhdr_color_transfer::UHDR_CT_SRGB,
+            limited,
+        )
+        .unwrap();
+        assert_eq!(raw.meta().2, limited);
+
+        let owned = OwnedPackedImage::new(
+            sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA1010102,
+            2,
+            2,
+            sys::uhdr_color_gamut::UHDR_CG_BT_2100,
+            sys::uhdr_color_transfer::UHDR_CT_PQ,
+            limited,
+        )
+        .unwrap();
+        assert_eq!(owned.meta().2, limited);
+
+        let mut pixels = vec![7u8; 2 * 2 * 4];
+        let planes = [
+            pixels.as_mut_ptr() as *mut c_void,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        ];
+        let mut raw = sys::uhdr_raw_image {
+            fmt: sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA8888,
+            cg: sys::uhdr_color_gamut::UHDR_CG_BT_709,
+            ct: sys::uhdr_color_transfer::UHDR_CT_SRGB,
+            range: limited,
+            w: 2,
+            h: 2,
+            planes,
+            stride: [2, 0, 0],
+        };
+        let view = DecodedPackedView::new(&mut raw).unwrap();
+        let mut decoded = view.to_owned().unwrap();
+        assert_eq!(decoded.range, limited);
+        let reexposed = decoded.as_raw_image().unwrap();
+        assert_eq!(reexposed.meta().2, limited);
+
+        let mut jpeg = vec![0xFFu8, 0xD8, 0xFF, 0xD9];
+        let comp = CompressedImage::from_bytes(
+            &mut jpeg,
+            sys::uhdr_color_gamut::UHDR_CG_BT_709,
+            sys::uhdr_color_transfer::UHDR_CT_SRGB,
+            limited,
+        )
+        .unwrap();
+        assert_eq!(comp.inner.range, limited);
+        let encoded = EncodedView::new(&comp.inner).to_owned().unwrap();
+        assert_eq!(encoded.range, limited);
+    }
+
+    #[test]
+    fn compressed_image_from_slice_borrows_without_copying() {
+        let jpeg = [0xFFu8, 0xD8, 0xFF, 0xD9];
+        let comp = CompressedImage::from_slice(
+            &jpeg,
+            sys::uhdr_color_gamut::UHDR_CG_BT_709,
+            sys::uhdr_color_transfer::UHDR_CT_SRGB,
+            sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+        )
+        .unwrap();
+        assert!(comp.read_only);
+        assert_eq!(comp.inner.data as *const u8, jpeg.as_ptr());
+        assert_eq!(EncodedView::new(&comp.inner).bytes().unwrap(), &jpeg);
+    }
+
+    /// Build a strided buffer where pixel bytes count up from 1 and padding is 0xAA.
+    fn strided_buffer(width: usize, height: usize, stride_px: usize, bpp: usize) -> Vec<u8> {
+        let mut buf = vec![0xAAu8; stride_px * height * bpp];
+        let mut v = 1u8;
+        for y in 0..height {
+            let start = y * stride_px * bpp;
+            for b in &mut buf[start..start + width * bpp] {
+                *b = v;
+                v = v.wrapping_add(1);
+            }
+        }
+        buf
+    }
+
+    fn copy_packed(
+        fmt: ImgFormat,
+        width: u32,
+        height: u32,
+        stride_px: u32,
+        buf: &mut [u8],
+    ) -> Result<Vec<u8>> {
+        let img = sys::uhdr_raw_image {
+            fmt,
+            cg: sys::uhdr_color_gamut::UHDR_CG_BT_2100,
+            ct: sys::uhdr_color_transfer::UHDR_CT_PQ,
+            range: sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+            w: width,
+            h: height,
+            planes: [
+                buf.as_mut_ptr() as *mut c_void,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            ],
+            stride: [stride_px, 0, 0],
+        };
+        copy_raw_packed(&img)
+    }
+
+    #[test]
+    fn copy_raw_packed_rgba1010102_drops_stride_padding() {
+        let (w, h, stride) = (3usize, 2usize, 5usize);
+        let mut buf = strided_buffer(w, h, stride, 4);
+        let out = copy_packed(
+            sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA1010102,
+            w as u32,
+            h as u32,
+            stride as u32,
+            &mut buf,
+        )
+        .unwrap();
+        let expected: Vec<u8> = (1..=(w * h * 4) as u8).collect();
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn copy_raw_packed_half_float_drops_stride_padding() {
+        let (w, h, stride) = (2usize, 3usize, 3usize);
+        let mut buf = strided_buffer(w, h, stride, 8);
+        let out = copy_packed(
+            sys::uhdr_img_fmt::UHDR_IMG_FMT_64bppRGBAHalfFloat,
+            w as u32,
+            h as u32,
+            stride as u32,
+            &mut buf,
+        )
+        .unwrap();
+        assert_eq!(out.len(), w * h * 8);
+        let expected: Vec<u8> = (1..=(w * h * 8) as u8).collect();
+        assert_eq!(out, expected);
+        assert!(!out.contains(&0xAA));
+    }
+
+    #[test]
+    fn copy_raw_packed_contiguous_and_padded_paths_agree() {
+        let (w, h) = (3usize, 4usize);
+        let mut padded = strided_buffer(w, h, w + 2, 4);
+        let mut tight = strided_buffer(w, h, w, 4);
+        let fmt = sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA8888;
+        let from_padded = copy_packed(fmt, w as u32, h as u32, w as u32 + 2, &mut padded).unwrap();
+        let from_tight = copy_packed(fmt, w as u32, h as u32, w as u32, &mut tight).unwrap();
+        assert_eq!(from_tight, from_padded);
+        assert_eq!(from_tight, tight);
+    }
+
+    #[test]
+    fn split_gainmap_channels_deinterleaves_rgb() {
+        let gainmap = DecodedPacked {
+            fmt: sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA8888,
+            cg: sys::uhdr_color_gamut::UHDR_CG_UNSPECIFIED,
+            ct: sys::uhdr_color_transfer::UHDR_CT_UNSPECIFIED,
+            range: sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+            width: 2,
+            height: 1,
+            data: vec![1, 2, 3, 255, 4, 5, 6, 255],
+        };
+        let planes = gainmap.split_gainmap_channels().unwrap();
+        let data: Vec<_> = planes.iter().map(|p| p.data.as_slice()).collect();
+        assert_eq!(data, [&[1, 4], &[2, 5], &[3, 6]]);
+        assert!(
+            planes
+                .iter()
+                .all(|p| p.fmt == sys::uhdr_img_fmt::UHDR_IMG_FMT_8bppYCbCr400 && p.width == 2)
+        );
+    }
+
+    #[test]
+    fn copy_raw_packed_rejects_zero_stride() {
+        let mut buf = vec![0u8; 8];
+        let err = copy_packed(
+            sys::uhdr_img_fmt::UHDR_IMG_FMT_64bppRGBAHalfFloat,
+            1,
+            1,
+            0,
+            &mut buf,
+        )
+        .unwrap_err();
+        assert_eq!(err.code, sys::uhdr_codec_err_t::UHDR_CODEC_INVALID_PARAM);
+    }
+
+    #[test]
+    fn to_owned_into_reuses_capacity() {
+        let (w, h, stride) = (3usize, 2usize, 4usize);
+        let mut buf = strided_buffer(w, h, stride, 4);
+        let mut img = sys::uhdr_raw_image {
+            fmt: sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA8888,
+            cg: sys::uhdr_color_gamut::UHDR_CG_BT_709,
+            ct: sys::uhdr_color_transfer::UHDR_CT_SRGB,
+            range: sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+            w: w as u32,
+            h: h as u32,
+            planes: [
+                buf.as_mut_ptr() as *mut c_void,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            ],
+            stride: [stride as u32, 0, 0],
+        };
+        let view = DecodedPackedView::new(&mut img).unwrap();
+        let len = view.required_owned_len().unwrap();
+        assert_eq!(len, w * h * 4);
+
+        let mut out = Vec::with_capacity(len);
+        out.extend_from_slice(&[0xFF; 5]);
+        let ptr = out.as_ptr();
+        view.to_owned_into(&mut out).unwrap();
+        assert_eq!(out.as_ptr(), ptr);
+        assert_eq!(out, view.to_owned().unwrap().data);
+    }
+
+    #[test]
+    fn crop_to_owned_copies_region_and_rejects_out_of_bounds() {
+        let (w, h, stride) = (4usize, 3usize, 5usize);
+        let mut buf = strided_buffer(w, h, stride, 4);
+        let mut img = sys::uhdr_raw_image {
+            fmt: sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA8888,
+            cg: sys::uhdr_color_gamut::UHDR_CG_BT_709,
+            ct: sys::uhdr_color_transfer::UHDR_CT_SRGB,
+            range: sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+            w: w as u32,
+            h: h as u32,
+            planes: [
+                buf.as_mut_ptr() as *mut c_void,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            ],
+            stride: [stride as u32, 0, 0],
+        };
+        let view = DecodedPackedView::new(&mut img).unwrap();
+        let full = view.to_owned().unwrap();
+
+        let rect = Rect {
+            x: 1,
+            y: 1,
+            width: 2,
+            height: 2,
+        };
+        let crop = view.crop_to_owned(rect).unwrap();
+        assert_eq!((crop.width, crop.height), (2, 2));
+        let row_bytes = w * 4;
+        let mut expected = Vec::new();
+        for y in 1..3 {
+            expected.extend_from_slice(&full.data[y * row_bytes + 4..y * row_bytes + 12]);
+        }
+        assert_eq!(crop.data, expected);
+
+        for bad in [
+            Rect {
+                x: 3,
+                y: 0,
+                width: 2,
+                height: 1,
+            },
+            Rect {
+                x: 0,
+                y: 0,
+                width: 0,
+                height: 1,
+            },
+            Rect {
+                x: u32::MAX,
+                y: 0,
+                width: 2,
+                height: 1,
+            },
+        ] {
+            let err = view.crop_to_owned(bad).unwrap_err();
+            assert_eq!(err.code, sys::uhdr_codec_err_t::UHDR_CODEC_INVALID_PARAM);
+        }
+    }
+
+    #[test]
+    fn into_parts_returns_layout_and_pixels() {
+        let img = DecodedPacked {
+            fmt: sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA8888,
+            cg: sys::uhdr_color_gamut::UHDR_CG_BT_709,
+            ct: sys::uhdr_color_transfer::UHDR_CT_SRGB,
+            range: sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+            width: 2,
+            height: 1,
+            data: vec![1, 2, 3, 4, 5, 6, 7, 8],
+        };
+        #[cfg(feature = "bytemuck")]
+        {
+            let words = img.as_u32_slice().unwrap();
+            assert_eq!(
+                words,
+                [
+                    u32::from_le_bytes([1, 2, 3, 4]),
+                    u32::from_le_bytes([5, 6, 7, 8])
+                ]
+            );
+            assert!(img.as_f16_slice().is_err());
+        }
+        let (fmt, w, h, data): (ImgFormat, u32, u32, Vec<u8>) = img.into();
+        assert_eq!(fmt, sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA8888);
+        assert_eq!((w, h), (2, 1));
+        assert_eq!(data, [1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn decoded_packed_eq_and_hash_cover_pixels_and_metadata() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        let hash = |v: &DecodedPacked| {
+            let mut h = DefaultHasher::new();
+            v.hash(&mut h);
+            h.finish()
+        };
+        let a = DecodedPacked {
+            fmt: sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA8888,
+            cg: sys::uhdr_color_gamut::UHDR_CG_BT_709,
+            ct: sys::uhdr_color_transfer::UHDR_CT_SRGB,
+            range: sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+            width: 1,
+            height: 1,
+            data: vec![1, 2, 3, 4],
+        };
+        let b = a.clone();
+        assert_eq!(a, b);
+        assert_eq!(hash(&a), hash(&b));
+
+        let mut pixels = a.clone();
+        pixels.data[0] = 9;
+        assert_ne!(a, pixels);
+        let mut range = a.clone();
+        range.range = sys::uhdr_color_range::UHDR_CR_LIMITED_RANGE;
+        assert_ne!(a, range);
+    }
+
+    #[test]
+    fn gainmap_metadata_round_trips_through_sys() {
+        let meta = GainMapMetadata {
+            max_content_boost: [4.0, 3.5, 3.0],
+            min_content_boost: [1.0, 0.9, 0.8],
+            gamma: [1.0, 1.1, 1.2],
+            offset_hdr: [1.0 / 32.0; 3],
+            ..sample_metadata(4.0)
+        };
+        let raw = sys::uhdr_gainmap_metadata::from(&meta);
+        assert_eq!(raw.use_base_cg, 1);
+        assert_eq!(raw.max_content_boost, [4.0, 3.5, 3.0]);
+        let mut back = GainMapMetadata::from(&raw);
+        assert_eq!(format!("{back:?}"), format!("{meta:?}"));
+
+        back.use_base_cg = false;
+        back.hdr_capacity_max = 8.0;
+        let raw = back.to_sys();
+        assert_eq!(raw.use_base_cg, 0);
+        assert_eq!(raw.hdr_capacity_max, 8.0);
+    }
+
+    #[test]
+    fn gainmap_scale_factor_matches_ceil_division() {
+        let metadata = sample_metadata(4.0);
+        let probe = |gm: Option<(u32, u32)>| ProbeInfo {
+            width: 4001,
+            height: 3000,
+            fmt: sys::uhdr_img_fmt::UHDR_IMG_FMT_64bppRGBAHalfFloat,
+            cg: sys::uhdr_color_gamut::UHDR_CG_UNSPECIFIED,
+            ct: sys::uhdr_color_transfer::UHDR_CT_UNSPECIFIED,
+            range: sys::uhdr_color_range::UHDR_CR_UNSPECIFIED,
+            gainmap: gm.map(|(width, height)| GainMapInfo {
+                metadata: metadata.clone(),
+                width,
+                height,
+                byte_len: 1,
+            }),
+        };
+        assert_eq!(probe(Some((1001, 750))).gainmap_scale_factor(), Some(4));
+        assert_eq!(probe(Some((4001, 3000))).gainmap_scale_factor(), Some(1));
+        assert_eq!(probe(Some((1001, 1000))).gainmap_scale_factor(), None);
+        assert_eq!(probe(None).gainmap_scale_factor(), None);
+    }
+
+    #[test]
+    fn encoder_effort_orders_levels_and_maps_presets() {
+        use sys::uhdr_enc_preset as P;
+        assert!(EncoderEffort::Realtime < EncoderEffort::Balanced);
+        assert!(EncoderEffort::Balanced < EncoderEffort::BestQuality);
+        for level in 0..=2 {
+            assert_eq!(EncoderEffort::from_level(level).unwrap().level(), level);
+        }
+        assert_eq!(EncoderEffort::from_level(3), None);
+
+        assert_eq!(
+            EncPreset::from(EncoderEffort::Realtime),
+            P::UHDR_USAGE_REALTIME
+        );
+        assert_eq!(
+            EncPreset::from(EncoderEffort::Balanced),
+            P::UHDR_USAGE_BEST_QUALITY
+        );
+        for preset in [P::UHDR_USAGE_REALTIME, P::UHDR_USAGE_BEST_QUALITY] {
+            assert_eq!(EncPreset::from(EncoderEffort::from(preset)), preset);
+        }
+    }
+
+    #[test]
+    fn boost_helpers_use_linear_factors() {
+        let meta = GainMapMetadata {
+            max_content_boost: [4.0, 3.5, 3.0],
+            min_content_boost: [1.0, 0.9, 0.8],
+            ..sample_metadata(4.0)
+        };
+        assert_eq!(meta.min_boost_factor(), 1.0);
+        assert_eq!(meta.max_boost_factor(), 4.0);
+        assert_eq!(
+            meta.content_boost_range(),
+            ([1.0, 0.9, 0.8], [4.0, 3.5, 3.0])
+        );
+    }
+
+    #[test]
+    fn validate_rejects_malformed_metadata() {
+        let good = sample_metadata(4.0);
+        good.validate().unwrap();
+
+        let mut nan_gamma = good.clone();
+        nan_gamma.gamma[2] = f32::NAN;
+        let mut inverted = good.clone();
+        inverted.min_content_boost[1] = 8.0;
+        let mut zero_gamma = good.clone();
+        zero_gamma.gamma[0] = 0.0;
+        let mut capacity = good.clone();
+        capacity.hdr_capacity_max = 0.5;
+        for (bad, rule) in [
+            (nan_gamma, "finite"),
+            (inverted, "max_content_boost"),
+            (zero_gamma, "gamma"),
+            (capacity, "hdr_capacity_max"),
+        ] {
+            let err = bad.validate().unwrap_err();
+            assert_eq!(err.code, sys::uhdr_codec_err_t::UHDR_CODEC_INVALID_PARAM);
+            assert!(err.to_string().contains(rule), "{err}");
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn gainmap_metadata_serde_round_trip() {
+        let meta = GainMapMetadata {
+            max_content_boost: [4.0, 3.5, 3.0],
+            ..sample_metadata(4.0)
+        };
+        let text = toml::to_string(&meta).unwrap();
+        assert!(text.contains("use_base_cg = true"), "{text}");
+        let back: GainMapMetadata = toml::from_str(&text).unwrap();
+        assert_eq!(format!("{back:?}"), format!("{meta:?}"));
+
+        let older = text.replace("base_rendition_is_hdr = false\n", "");
+        assert_ne!(older, text);
+        let back: GainMapMetadata = toml::from_str(&older).unwrap();
+        assert!(!back.base_rendition_is_hdr);
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn raw_image_borrows_rgba_image() {
+        let mut img = image::RgbaImage::from_pixel(3, 2, image::Rgba([1, 2, 3, 4]));
+        let raw = RawImage::from_rgba_image(
+            &mut img,
+            sys::uhdr_color_gamut::UHDR_CG_BT_709,
+            sys::uhdr_color_transfer::UHDR_CT_SRGB,
+            sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+        )
+        .unwrap();
+        assert_eq!((raw.width(), raw.height()), (3, 2));
+        assert_eq!(raw.fmt(), sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA8888);
+
+        let mut oversized = image::RgbaImage::from_raw(1, 1, vec![0; 8]).unwrap();
+        assert!(
+            RawImage::from_rgba_image(
+                &mut oversized,
+                sys::uhdr_color_gamut::UHDR_CG_BT_709,
+                sys::uhdr_color_transfer::UHDR_CT_SRGB,
+                sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+            )
+            .is_err()
+        );
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn to_dynamic_image_maps_formats_to_variants() {
+        let packed = |fmt, data: Vec<u8>| DecodedPacked {
+            fmt,
+            cg: sys::uhdr_color_gamut::UHDR_CG_BT_2100,
+            ct: sys::uhdr_color_transfer::UHDR_CT_PQ,
+            range: sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+            width: 1,
+            height: 1,
+            data,
+        };
+        let img = packed(
+            sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA8888,
+            vec![1, 2, 3, 4],
+        );
+        let rgba8 = img.to_dynamic_image().unwrap();
+        assert_eq!(rgba8.as_rgba8().unwrap().as_raw(), &[1, 2, 3, 4]);
+
+        let v: u32 = 0x3FF | (0x200 << 10) | (3 << 30);
+        let img = packed(
+            sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA1010102,
+            v.to_le_bytes().to_vec(),
+        );
+        let rgba16 = img.to_dynamic_image().unwrap();
+        assert_eq!(
+            rgba16.as_rgba16().unwrap().as_raw(),
+            &[0xFFFF, 0x8020, 0, 0xFFFF]
+        );
+
+        let half = [0x3C00u16, 0x4000, 0, 0x3C00]
+            .iter()
+            .flat_map(|h| h.to_le_bytes())
+            .collect();
+        let img = packed(sys::uhdr_img_fmt::UHDR_IMG_FMT_64bppRGBAHalfFloat, half);
+        let rgba32f = img.to_dynamic_image().unwrap();
+        assert_eq!(
+            rgba32f.as_rgba32f().unwrap().as_raw(),
+            &[1.0, 2.0, 0.0, 1.0]
+        );
+
+        let img = packed(sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA8888, vec![1, 2]);
+        assert!(img.to_dynamic_image().is_err());
+    }
+
+    #[test]
+    fn half_float_rows_and_pixels_decode_to_f32() {
+        let half = [
+            0x3C00u16, 0x4000, 0x3800, 0x3C00, 0x0000, 0xC000, 0x7C00, 0x3C00,
+        ];
+        let mut img = DecodedPacked {
+            fmt: sys::uhdr_img_fmt::UHDR_IMG_FMT_64bppRGBAHalfFloat,
+            cg: sys::uhdr_color_gamut::UHDR_CG_BT_2100,
+            ct: sys::uhdr_color_transfer::UHDR_CT_LINEAR,
+            range: sys::uhdr_color_range::UHDR_CR_FULL_RANGE,
+            width: 1,
+            height: 2,
+            data: half.iter().flat_map(|h| h.to_le_bytes()).collect(),
+        };
+        assert_eq!(img.row_f32(0).unwrap(), [1.0, 2.0, 0.5, 1.0]);
+        assert_eq!(img.row_f32(1).unwrap(), [0.0, -2.0, f32::INFINITY, 1.0]);
+        assert!(img.row_f32(2).is_err());
+        let pixels: Vec<_> = img.pixels_f32().unwrap().collect();
+        assert_eq!(pixels[0], [1.0, 2.0, 0.5, 1.0]);
+        assert_eq!(pixels.len(), 2);
+
+        img.data.truncate(8);
+        assert!(img.row_f32(1).is_err());
+        assert!(img.pixels_f32().is_err());
+        img.fmt = sys::uhdr_img_fmt::UHDR_IMG_FMT_32bppRGBA8888;
+        assert!(img.row_f32(0).is_err());
+    }
+
+    #[test]
+    fn rgba1010102_packing_matches_bit_layout_and_round_trips() {
+        assert_eq!(pack_rgba1010102(1.0, 0.0, 0.0, 0.0), 0x3FF);
+        assert_eq!(pack_rgba1010102(0.0, 1.0, 0.0, 0.0), 0x3FF << 10);
+        assert_eq!(pack_rgba1010102(0.0, 0.0, 1.0, 0.0), 0x3FF << 20);
+        assert_eq!(pack_rgba1010102(0.0, 0.0, 0.0, 1.0), 0b11 << 30);
+        assert_eq!(
+            pack_rgba1010102(2.0, -1.0, f32::NAN, 0.5),
+            0x3FF | (2 << 30)
+        );
+        for code in [0u32, 1, 511, 512, 1022, 1023] {
+            let v = code as f32 / 1023.0;
+            let px = pack_rgba1010102(v, v, v, 1.0);
+            assert_eq!(unpack_rgba1010102(px), [v, v, v, 1.0]);
+            assert_eq!(pack_rgba1010102(v, v, v, 1.0), px);
+        }
+    }
+
+    #[test]
+    fn supported_output_formats_are_packed() {
+        for &fmt in supported_output_formats() {
+            assert!(bytes_per_pixel(fmt).is_ok(), "{fmt:?} has no packed size");
+            assert!(supported_input_formats().contains(&fmt));
+        }
     }
 
     #[test]